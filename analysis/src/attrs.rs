@@ -0,0 +1,141 @@
+use interface::{
+    nodes::{attr::Attr, func::Func},
+    tokens::Token,
+    Error, Spanned,
+};
+use std::collections::HashMap;
+
+/// what an attribute takes between its parentheses
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AttrArgs {
+    None,
+    /// at most one string, such as the reason for a deprecation
+    OptionalStr,
+    /// one or more names, such as the lints to allow
+    Names,
+}
+
+impl AttrArgs {
+    fn accepts(&self, args: &[Spanned<Token>]) -> bool {
+        match self {
+            AttrArgs::None => args.is_empty(),
+            AttrArgs::OptionalStr => matches!(args, [] | [(Token::Str(_), _)]),
+            AttrArgs::Names => {
+                !args.is_empty() && args.iter().all(|(arg, _)| matches!(arg, Token::Ident(_)))
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for AttrArgs {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            AttrArgs::None => write!(f, "no arguments"),
+            AttrArgs::OptionalStr => write!(f, "an optional string"),
+            AttrArgs::Names => write!(f, "one or more names"),
+        }
+    }
+}
+
+/// problems with the attributes of a program
+#[derive(Debug, Default)]
+pub struct AttrCheck {
+    /// known attributes used wrongly
+    pub errors: Vec<Error>,
+    /// unknown attributes, which are ignored
+    pub warnings: Vec<Error>,
+}
+
+///
+/// attributes that functions may have, and the arguments they take
+///
+#[derive(Clone, Debug, Default)]
+pub struct AttrRegistry {
+    attrs: HashMap<String, AttrArgs>,
+}
+
+impl AttrRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// the attributes understood by the interpreter and its tools
+    pub fn with_std() -> Self {
+        let mut registry = Self::new();
+        registry.register("memo", AttrArgs::None);
+        registry.register("test", AttrArgs::None);
+        registry.register("deprecated", AttrArgs::OptionalStr);
+        registry.register("inline", AttrArgs::None);
+        for level in ["allow", "warn", "deny"] {
+            registry.register(level, AttrArgs::Names);
+        }
+        registry
+    }
+
+    /// register an attribute, replacing any attribute of the same name
+    pub fn register(&mut self, name: &str, args: AttrArgs) {
+        self.attrs.insert(name.to_string(), args);
+    }
+
+    pub fn get(&self, name: &str) -> Option<AttrArgs> {
+        self.attrs.get(name).copied()
+    }
+
+    ///
+    /// check the attributes of functions against the registry
+    ///
+    pub fn check(&self, funcs: &HashMap<String, Func>) -> AttrCheck {
+        let mut check = AttrCheck::default();
+        for attr in funcs.values().flat_map(|func| &func.attrs) {
+            self.check_attr(attr, &mut check);
+        }
+        check.errors.sort_by_key(|e| e.span.start);
+        check.warnings.sort_by_key(|e| e.span.start);
+        check
+    }
+
+    fn check_attr(&self, attr: &Attr, check: &mut AttrCheck) {
+        let (name, span) = &attr.name;
+        match self.get(name) {
+            Some(args) if !args.accepts(&attr.args) => check.errors.push(Error::new(
+                attr.span.clone(),
+                format!("Attribute '{}' takes {}", name, args),
+            )),
+            Some(_) => {}
+            None => check.warnings.push(Error::new(
+                span.clone(),
+                format!("Unknown attribute '{}'", name),
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use parser::parsers::parse_program;
+
+    use super::AttrRegistry;
+
+    #[test]
+    fn checks_attributes() {
+        let src = "#[memo]\n#[deprecated(\"use g\")]\nfn f(n) { n }\n#[inline(1)]\n#[colour]\nfn g() { 1 }";
+        let funcs = parse_program(src);
+        assert!(funcs["f"].memo);
+        assert_eq!(funcs["f"].attrs.len(), 2);
+
+        let check = AttrRegistry::with_std().check(&funcs);
+        let messages = |errs: Vec<interface::Error>| {
+            errs.into_iter()
+                .map(|e| (e.span, e.msg))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            messages(check.errors),
+            vec![(45..57, "Attribute 'inline' takes no arguments".to_string())]
+        );
+        assert_eq!(
+            messages(check.warnings),
+            vec![(60..66, "Unknown attribute 'colour'".to_string())]
+        );
+    }
+}
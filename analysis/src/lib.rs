@@ -0,0 +1,9 @@
+//!
+//! static analyses of programs, shared by the interpreter and the language server
+//!
+
+pub mod attrs;
+pub mod lint;
+pub mod purity;
+pub mod resolver;
+pub mod types;
@@ -0,0 +1,408 @@
+use interface::{
+    nodes::{expr::Expr, func::Func},
+    tokens::Token,
+    Span, Spanned,
+};
+use std::collections::{HashMap, HashSet};
+
+use crate::resolver::{DefKind, Resolution};
+
+/// a kind of code that is allowed but probably a mistake
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Rule {
+    UnusedVariable,
+    UnusedParameter,
+    /// statements after a `return`, which does not leave the function
+    CodeAfterReturn,
+    ShadowedBinding,
+    /// functions that neither `main` nor a test ever calls
+    UnusedFunction,
+    ConstantCondition,
+    /// lint names in attributes that are not rules
+    UnknownLint,
+}
+
+impl Rule {
+    pub const ALL: [Rule; 7] = [
+        Rule::UnusedVariable,
+        Rule::UnusedParameter,
+        Rule::CodeAfterReturn,
+        Rule::ShadowedBinding,
+        Rule::UnusedFunction,
+        Rule::ConstantCondition,
+        Rule::UnknownLint,
+    ];
+
+    /// the name of the rule in attributes and config files
+    pub fn name(&self) -> &'static str {
+        match self {
+            Rule::UnusedVariable => "unused_variable",
+            Rule::UnusedParameter => "unused_parameter",
+            Rule::CodeAfterReturn => "code_after_return",
+            Rule::ShadowedBinding => "shadowed_binding",
+            Rule::UnusedFunction => "unused_function",
+            Rule::ConstantCondition => "constant_condition",
+            Rule::UnknownLint => "unknown_lint",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Rule> {
+        Rule::ALL.into_iter().find(|rule| rule.name() == name)
+    }
+}
+
+/// what to do about the code a rule matches
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Level {
+    Allow,
+    Warn,
+    /// report it as an error
+    Deny,
+}
+
+impl Level {
+    /// the level set by an attribute or a config file, such as `allow`
+    pub fn from_name(name: &str) -> Option<Level> {
+        match name {
+            "allow" => Some(Level::Allow),
+            "warn" => Some(Level::Warn),
+            "deny" => Some(Level::Deny),
+            _ => None,
+        }
+    }
+}
+
+///
+/// the level of each rule for a whole program; rules warn unless set otherwise,
+/// and `#[allow(..)]`, `#[warn(..)]` and `#[deny(..)]` change them for a function
+///
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LintConfig {
+    levels: HashMap<Rule, Level>,
+}
+
+impl LintConfig {
+    pub fn set(&mut self, rule: Rule, level: Level) {
+        self.levels.insert(rule, level);
+    }
+
+    pub fn level(&self, rule: Rule) -> Level {
+        self.levels.get(&rule).copied().unwrap_or(Level::Warn)
+    }
+}
+
+/// code matched by a rule
+#[derive(Clone, Debug, PartialEq)]
+pub struct Lint {
+    pub rule: Rule,
+    pub level: Level,
+    pub msg: String,
+    pub span: Span,
+}
+
+///
+/// check functions whose names were resolved by `res` against the rules
+/// that are not allowed, in source order
+///
+pub fn lint(funcs: &HashMap<String, Func>, res: &Resolution, config: &LintConfig) -> Vec<Lint> {
+    let mut uses = HashMap::<usize, usize>::new();
+    for def in res.uses.values() {
+        *uses.entry(*def).or_default() += 1;
+    }
+    let mut lints = Vec::new();
+    let unused = unused_funcs(funcs, res);
+    for (name, func) in funcs {
+        let mut linter = Linter {
+            res,
+            uses: &uses,
+            levels: levels(func, config, &mut lints),
+            lints: &mut lints,
+            scope: Vec::new(),
+        };
+        if unused.contains(name.as_str()) {
+            linter.push(
+                Rule::UnusedFunction,
+                format!("Function '{}' is never called from 'main'", name),
+                func.name_span.clone(),
+            );
+        }
+        for (arg, span) in func.args.iter().zip(&func.arg_spans) {
+            linter.binding(Rule::UnusedParameter, "parameter", arg, span);
+        }
+        linter.expr(&func.body);
+    }
+    lints.retain(|lint| lint.level != Level::Allow);
+    lints.sort_by_key(|lint| (lint.span.start, lint.span.end));
+    lints
+}
+
+///
+/// the levels of the rules in a function, after its attributes;
+/// lint names in them that are not rules are linted themselves
+///
+fn levels(func: &Func, config: &LintConfig, lints: &mut Vec<Lint>) -> LintConfig {
+    let mut levels = config.clone();
+    for attr in &func.attrs {
+        let level = match Level::from_name(&attr.name.0) {
+            Some(level) => level,
+            None => continue,
+        };
+        for (arg, span) in &attr.args {
+            let name = match arg {
+                Token::Ident(name) => name,
+                _ => continue,
+            };
+            match Rule::from_name(name) {
+                Some(rule) => levels.set(rule, level),
+                None => lints.push(Lint {
+                    rule: Rule::UnknownLint,
+                    level: config.level(Rule::UnknownLint),
+                    msg: format!("Unknown lint '{}'", name),
+                    span: span.clone(),
+                }),
+            }
+        }
+    }
+    levels
+}
+
+///
+/// functions that neither `main` nor a test reaches; none if there is no `main`,
+/// since then the functions are a library
+///
+fn unused_funcs<'a>(funcs: &'a HashMap<String, Func>, res: &Resolution) -> HashSet<&'a str> {
+    if !funcs.contains_key("main") {
+        return HashSet::new();
+    }
+    let calls = |func: &Func| {
+        res.uses
+            .iter()
+            .filter(|(span, _)| func.body.1.start <= span.start && span.end <= func.body.1.end)
+            .map(|(_, &def)| &res.defs[def])
+            .filter(|def| def.kind == DefKind::Func)
+            .map(|def| def.name.clone())
+            .collect::<Vec<_>>()
+    };
+    let mut todo = funcs
+        .iter()
+        .filter(|(name, func)| {
+            *name == "main" || func.attrs.iter().any(|attr| attr.name.0 == "test")
+        })
+        .map(|(name, _)| name.clone())
+        .collect::<Vec<_>>();
+    let mut reached = HashSet::new();
+    while let Some(name) = todo.pop() {
+        if let Some(func) = funcs.get(&name) {
+            if reached.insert(name) {
+                todo.extend(calls(func));
+            }
+        }
+    }
+    funcs
+        .keys()
+        .filter(|name| !reached.contains(*name))
+        .map(String::as_str)
+        .collect()
+}
+
+/// whether the value of an expression may be that of a `return`
+fn ends_in_return(expr: &Spanned<Expr>) -> bool {
+    match &expr.0 {
+        Expr::Return(_) => true,
+        Expr::Var(_, _, _, body) | Expr::Then(_, body) => ends_in_return(body),
+        Expr::If(_, a, b) => ends_in_return(a) || ends_in_return(b),
+        _ => false,
+    }
+}
+
+/// where the statements after the first of a chain start, if there are any
+fn rest_start(expr: &Spanned<Expr>) -> Option<usize> {
+    match &expr.0 {
+        Expr::Then(a, b) => Some(rest_start(a).unwrap_or(b.1.start)),
+        _ => None,
+    }
+}
+
+/// whether an expression is made of literals only, so that it always has the same value
+fn is_constant(expr: &Spanned<Expr>) -> bool {
+    match &expr.0 {
+        Expr::Value(_) => true,
+        Expr::Binary(a, _, b) => is_constant(a) && is_constant(b),
+        _ => false,
+    }
+}
+
+struct Linter<'a> {
+    res: &'a Resolution,
+    /// how many times each definition is used
+    uses: &'a HashMap<usize, usize>,
+    levels: LintConfig,
+    lints: &'a mut Vec<Lint>,
+    /// names of the locals in scope, innermost last
+    scope: Vec<String>,
+}
+
+impl<'a> Linter<'a> {
+    fn push(&mut self, rule: Rule, msg: String, span: Span) {
+        self.lints.push(Lint {
+            rule,
+            level: self.levels.level(rule),
+            msg,
+            span,
+        });
+    }
+
+    /// bring a local into scope, checking that it is used and shadows nothing
+    fn binding(&mut self, unused: Rule, what: &str, name: &str, span: &Span) {
+        // a leading underscore says that it is meant to be unused
+        let used = self
+            .res
+            .bindings
+            .get(span)
+            .is_none_or(|def| self.uses.contains_key(def));
+        if !used && !name.starts_with('_') {
+            self.push(unused, format!("Unused {} '{}'", what, name), span.clone());
+        }
+        if self.scope.iter().any(|other| other == name) {
+            self.push(
+                Rule::ShadowedBinding,
+                format!("'{}' shadows an earlier binding of the same name", name),
+                span.clone(),
+            );
+        }
+        self.scope.push(name.to_string());
+    }
+
+    fn code_after_return(&mut self, span: Span) {
+        self.push(
+            Rule::CodeAfterReturn,
+            "'return' does not leave the function, so this runs and the returned value is discarded"
+                .to_string(),
+            span,
+        );
+    }
+
+    fn expr(&mut self, expr: &Spanned<Expr>) {
+        match &expr.0 {
+            Expr::Error | Expr::Value(_) | Expr::Local(_) => {}
+            Expr::Var((name, span), _, val, body) => {
+                self.expr(val);
+                self.binding(Rule::UnusedVariable, "variable", name, span);
+                self.expr(body);
+                self.scope.pop();
+            }
+            Expr::Then(a, b) => {
+                self.expr(a);
+                if ends_in_return(a) {
+                    self.code_after_return(b.1.clone());
+                }
+                self.expr(b);
+            }
+            Expr::Binary(a, _, b) => {
+                self.expr(a);
+                self.expr(b);
+            }
+            Expr::Call(f, (args, _)) => {
+                self.expr(f);
+                args.iter().for_each(|arg| self.expr(arg));
+            }
+            Expr::Return(a) => {
+                // `return a; b` returns `b`, since the statements after it are part of it
+                if let Some(start) = rest_start(a) {
+                    self.code_after_return(start..a.1.end);
+                }
+                self.expr(a);
+            }
+            Expr::If(cond, a, b) => {
+                if is_constant(cond) {
+                    self.push(
+                        Rule::ConstantCondition,
+                        "Condition of 'if' is always the same".to_string(),
+                        cond.1.clone(),
+                    );
+                }
+                self.expr(cond);
+                self.expr(a);
+                self.expr(b);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{lint, Level, LintConfig, Rule};
+    use crate::resolver::resolve_program;
+
+    fn check(src: &str, config: &LintConfig) -> Vec<(Rule, Level, String)> {
+        let (funcs, res) = resolve_program(src, &["println"]);
+        lint(&funcs, &res, config)
+            .into_iter()
+            .map(|lint| {
+                let text = src
+                    .chars()
+                    .skip(lint.span.start)
+                    .take(lint.span.end - lint.span.start)
+                    .collect();
+                (lint.rule, lint.level, text)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn finds_lints() {
+        let src = "fn f(a, b, _c) {
+    var x = 1;
+    var a = 2;
+    if 1 < 2 { return a } else { return 0 };
+    println(a)
+}
+fn g() { 1 }
+fn main() { f(1, 2, 3) }";
+        let lints = check(src, &LintConfig::default());
+        let warn = |rule, text: &str| (rule, Level::Warn, text.to_string());
+        assert_eq!(
+            lints,
+            vec![
+                warn(Rule::UnusedParameter, "a"),
+                warn(Rule::UnusedParameter, "b"),
+                warn(Rule::UnusedVariable, "x"),
+                warn(Rule::ShadowedBinding, "a"),
+                warn(Rule::ConstantCondition, "1 < 2"),
+                warn(Rule::CodeAfterReturn, "println(a)"),
+                warn(Rule::UnusedFunction, "g"),
+            ]
+        );
+    }
+
+    #[test]
+    fn finds_code_after_return() {
+        let src = "fn f(a) { return a; println(a); a }
+fn g(a) { if a { return 1 }; 2 }
+#[allow(code_after_return)]
+fn h(a) { return a; 1 }";
+        let lints = check(src, &LintConfig::default());
+        let warn = |text: &str| (Rule::CodeAfterReturn, Level::Warn, text.to_string());
+        assert_eq!(lints, vec![warn("println(a); a"), warn("2")]);
+    }
+
+    #[test]
+    fn follows_config_and_attributes() {
+        let src = "#[allow(unused_parameter)]
+#[deny(unused_variable, unused_function, colour)]
+fn f(a) { var x = 1; 2 }
+fn g(b) { b }";
+        let mut config = LintConfig::default();
+        config.set(Rule::UnusedParameter, Level::Deny);
+        config.set(Rule::UnknownLint, Level::Deny);
+        let lints = check(src, &config);
+        // there is no `main`, so every function may be used
+        assert_eq!(
+            lints,
+            vec![
+                (Rule::UnknownLint, Level::Deny, "colour".to_string()),
+                (Rule::UnusedVariable, Level::Deny, "x".to_string()),
+            ]
+        );
+    }
+}
@@ -0,0 +1,207 @@
+use interface::{
+    nodes::{expr::Expr, func::Func},
+    Error, Span, Spanned,
+};
+use std::collections::HashMap;
+
+use crate::resolver::{DefKind, Resolution};
+
+/// builtins whose effects would be lost when a cached result is reused
+pub const IMPURE_BUILTINS: [&str; 4] = ["print", "println", "eprint", "eprintln"];
+
+///
+/// reject `memo` functions that call or use impure builtins, directly or through other
+/// functions, and that call function values, which may be impure
+///
+pub fn check_memos(funcs: &HashMap<String, Func>, res: &Resolution) -> Vec<Error> {
+    let refs = funcs
+        .iter()
+        .map(|(name, func)| {
+            let mut refs = Refs::default();
+            refs.expr(&func.body, res);
+            (name.as_str(), refs)
+        })
+        .collect::<HashMap<_, _>>();
+
+    // the impure builtin each impure function ends up calling
+    let mut impure: HashMap<&str, &str> = HashMap::new();
+    loop {
+        let mut changed = false;
+        for (name, refs) in &refs {
+            if impure.contains_key(name) {
+                continue;
+            }
+            let builtin = refs
+                .names
+                .iter()
+                .find_map(|(callee, _, _)| match impure.get(callee) {
+                    Some(builtin) => Some(*builtin),
+                    None => IMPURE_BUILTINS.iter().copied().find(|b| b == callee),
+                });
+            if let Some(builtin) = builtin {
+                impure.insert(name, builtin);
+                changed = true;
+            }
+        }
+        if !changed {
+            break;
+        }
+    }
+
+    let mut errors = funcs
+        .iter()
+        .filter(|(_, func)| func.memo)
+        .filter_map(|(name, _)| {
+            let refs = &refs[name.as_str()];
+            let impure_ref = refs
+                .names
+                .iter()
+                .filter(|(callee, _, _)| {
+                    impure.contains_key(callee) || IMPURE_BUILTINS.contains(callee)
+                })
+                .map(|(callee, span, called)| {
+                    let verb = if *called { "call" } else { "use" };
+                    let msg = match impure.get(callee) {
+                        Some(builtin) => format!(
+                            "Memoised function '{}' must not {} '{}', which calls '{}'",
+                            name, verb, callee, builtin
+                        ),
+                        None => {
+                            format!(
+                                "Memoised function '{}' must not {} '{}'",
+                                name, verb, callee
+                            )
+                        }
+                    };
+                    Error::new(span.clone(), msg)
+                });
+            let dynamic = refs.dynamic.iter().map(|span| {
+                let msg = format!(
+                    "Memoised function '{}' must not call function values, which may be impure",
+                    name
+                );
+                Error::new(span.clone(), msg)
+            });
+            impure_ref.chain(dynamic).min_by_key(|e| e.span.start)
+        })
+        .collect::<Vec<_>>();
+    errors.sort_by_key(|e| e.span.start);
+    errors
+}
+
+/// what the body of a function refers to
+#[derive(Default)]
+struct Refs<'e> {
+    /// functions and builtins named, with the span of the name and whether it is called
+    names: Vec<(&'e str, Span, bool)>,
+    /// spans of the callees of calls of function values
+    dynamic: Vec<Span>,
+}
+
+impl<'e> Refs<'e> {
+    /// whether a name refers to a function or a builtin, rather than a value
+    fn is_func(expr: &Spanned<Expr>, res: &Resolution) -> bool {
+        res.def_of(&expr.1)
+            .is_some_and(|def| matches!(def.kind, DefKind::Func | DefKind::Builtin))
+    }
+
+    fn expr(&mut self, expr: &'e Spanned<Expr>, res: &Resolution) {
+        match &expr.0 {
+            Expr::Local(name) if Self::is_func(expr, res) => {
+                self.names.push((name, expr.1.clone(), false))
+            }
+            Expr::Call(func, (args, _)) => {
+                match &func.0 {
+                    Expr::Local(name) if Self::is_func(func, res) => {
+                        self.names.push((name, func.1.clone(), true))
+                    }
+                    _ => {
+                        self.dynamic.push(func.1.clone());
+                        self.expr(func, res);
+                    }
+                }
+                args.iter().for_each(|arg| self.expr(arg, res));
+            }
+            Expr::Var(_, _, a, b) | Expr::Then(a, b) | Expr::Binary(a, _, b) => {
+                self.expr(a, res);
+                self.expr(b, res);
+            }
+            Expr::If(cond, a, b) => {
+                self.expr(cond, res);
+                self.expr(a, res);
+                self.expr(b, res);
+            }
+            Expr::Return(a) => self.expr(a, res),
+            Expr::Error | Expr::Value(_) | Expr::Local(_) => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::check_memos;
+    use crate::resolver::resolve_program;
+
+    #[test]
+    fn rejects_impure_memos() {
+        let src = "memo fn f(n) { g(n) + h(n) }\nfn g(n) { println(n); n }\nfn h(n) { n }\nmemo fn k(n) { print(n) }\nmemo fn ok(n) { h(n) * 2 }";
+        let (funcs, res) = resolve_program(src, &["print", "println"]);
+
+        let errors = check_memos(&funcs, &res)
+            .into_iter()
+            .map(|e| (e.span, e.msg))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            errors,
+            vec![
+                (
+                    15..16,
+                    "Memoised function 'f' must not call 'g', which calls 'println'".to_string()
+                ),
+                (
+                    84..89,
+                    "Memoised function 'k' must not call 'print'".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_memos_that_use_impure_functions_as_values() {
+        let src = "memo fn f(n) { var p = println; p(n); n }
+memo fn g(n) { ap(println, n); n }
+fn ap(f, x) { f(x) }
+memo fn h(f, n) { f(n) }
+memo fn ok(n) { ap(inc, n) }
+fn inc(n) { n + 1 }";
+        let (funcs, res) = resolve_program(src, &["print", "println"]);
+
+        let errors = check_memos(&funcs, &res)
+            .into_iter()
+            .map(|e| (e.span, e.msg))
+            .collect::<Vec<_>>();
+        // the first `len` chars of `s` in the source
+        let at = |s: &str, len: usize| {
+            let start = src.find(s).unwrap();
+            start..start + len
+        };
+        assert_eq!(
+            errors,
+            vec![
+                (
+                    at("println", 7),
+                    "Memoised function 'f' must not use 'println'".to_string()
+                ),
+                (
+                    at("println, n", 7),
+                    "Memoised function 'g' must not use 'println'".to_string()
+                ),
+                (
+                    at("f(n) }", 1),
+                    "Memoised function 'h' must not call function values, which may be impure"
+                        .to_string()
+                ),
+            ]
+        );
+    }
+}
@@ -0,0 +1,297 @@
+use interface::{
+    nodes::{expr::Expr, func::Func},
+    Error, Span, Spanned,
+};
+use std::collections::HashMap;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DefKind {
+    Func,
+    Arg,
+    Var,
+    /// a binding made by the host or the REPL, visible to top-level expressions
+    Global,
+    /// a native function
+    Builtin,
+}
+
+/// something a name can refer to
+#[derive(Clone, Debug, PartialEq)]
+pub struct Def {
+    pub name: String,
+    pub kind: DefKind,
+    /// span of the name where it is bound; none for names defined by the host
+    pub span: Option<Span>,
+    /// local slot of arguments, `var`s and globals, in order of binding
+    pub slot: Option<usize>,
+}
+
+///
+/// the definition each name in a program refers to
+///
+#[derive(Debug, Default, PartialEq)]
+pub struct Resolution {
+    pub defs: Vec<Def>,
+    /// index of the definition of each `Expr::Local`, by the span of the use
+    pub uses: HashMap<Span, usize>,
+    /// index of each definition by the span of its name
+    pub bindings: HashMap<Span, usize>,
+    /// uses of names that are not defined
+    pub errors: Vec<Error>,
+}
+
+impl Resolution {
+    /// the definition a use refers to
+    pub fn def_of(&self, use_span: &Span) -> Option<&Def> {
+        self.uses.get(use_span).map(|&i| &self.defs[i])
+    }
+
+    /// the definition bound at a span
+    pub fn binding(&self, def_span: &Span) -> Option<&Def> {
+        self.bindings.get(def_span).map(|&i| &self.defs[i])
+    }
+
+    ///
+    /// the definition of the name at an offset, which is either a use or the definition itself
+    ///
+    pub fn lookup(&self, offset: usize) -> Option<usize> {
+        let contains = |span: &Span| span.start <= offset && offset <= span.end;
+        self.uses
+            .iter()
+            .chain(self.bindings.iter())
+            .find(|(span, _)| contains(span))
+            .map(|(_, &i)| i)
+    }
+
+    /// spans of all uses of a definition, in source order
+    pub fn references(&self, def: usize) -> Vec<Span> {
+        let mut spans = self
+            .uses
+            .iter()
+            .filter(|(_, &i)| i == def)
+            .map(|(span, _)| span.clone())
+            .collect::<Vec<_>>();
+        spans.sort_by_key(|span| span.start);
+        spans
+    }
+
+    fn define(&mut self, def: Def) -> usize {
+        self.defs.push(def);
+        let i = self.defs.len() - 1;
+        if let Some(span) = &self.defs[i].span {
+            self.bindings.insert(span.clone(), i);
+        }
+        i
+    }
+}
+
+///
+/// resolves names against the functions and builtins it was told about
+///
+#[derive(Debug, Default)]
+pub struct Resolver {
+    defs: Vec<Def>,
+    funcs: HashMap<String, usize>,
+    builtins: HashMap<String, usize>,
+}
+
+impl Resolver {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// make a native function known
+    pub fn builtin(&mut self, name: &str) {
+        self.defs.push(Def {
+            name: name.to_string(),
+            kind: DefKind::Builtin,
+            span: None,
+            slot: None,
+        });
+        self.builtins.insert(name.to_string(), self.defs.len() - 1);
+    }
+
+    /// make functions known, replacing those of the same name
+    pub fn funcs(&mut self, funcs: &HashMap<String, Func>) {
+        for (name, func) in funcs {
+            self.defs.push(Def {
+                name: name.clone(),
+                kind: DefKind::Func,
+                span: Some(func.name_span.clone()),
+                slot: None,
+            });
+            self.funcs.insert(name.clone(), self.defs.len() - 1);
+        }
+    }
+
+    fn start(&self) -> Resolution {
+        let mut res = Resolution::default();
+        // functions that were replaced are not bound anymore
+        for (i, def) in self.defs.iter().enumerate() {
+            res.defs.push(def.clone());
+            if self.funcs.get(&def.name) == Some(&i) {
+                res.bindings.insert(def.span.clone().unwrap(), i);
+            }
+        }
+        res
+    }
+
+    ///
+    /// resolve the names in the bodies of `funcs`
+    ///
+    pub fn resolve_funcs(&self, funcs: &HashMap<String, Func>) -> Resolution {
+        let mut res = self.start();
+        // report errors in source order
+        let mut funcs = funcs.values().collect::<Vec<_>>();
+        funcs.sort_by_key(|func| func.name_span.start);
+        for func in funcs {
+            let scope = func
+                .args
+                .iter()
+                .zip(&func.arg_spans)
+                .enumerate()
+                .map(|(slot, (name, span))| {
+                    res.define(Def {
+                        name: name.clone(),
+                        kind: DefKind::Arg,
+                        span: Some(span.clone()),
+                        slot: Some(slot),
+                    })
+                })
+                .collect();
+            Walker {
+                resolver: self,
+                res: &mut res,
+                scope,
+            }
+            .expr(&func.body);
+        }
+        res
+    }
+
+    ///
+    /// resolve the names in a top-level expression, with `globals` in scope
+    /// and later ones shadowing earlier ones
+    ///
+    pub fn resolve_expr(&self, expr: &Spanned<Expr>, globals: &[String]) -> Resolution {
+        let mut res = self.start();
+        let scope = globals
+            .iter()
+            .enumerate()
+            .map(|(slot, name)| {
+                res.define(Def {
+                    name: name.clone(),
+                    kind: DefKind::Global,
+                    span: None,
+                    slot: Some(slot),
+                })
+            })
+            .collect();
+        Walker {
+            resolver: self,
+            res: &mut res,
+            scope,
+        }
+        .expr(expr);
+        res
+    }
+}
+
+struct Walker<'a> {
+    resolver: &'a Resolver,
+    res: &'a mut Resolution,
+    /// definitions of the locals in scope, innermost last
+    scope: Vec<usize>,
+}
+
+impl<'a> Walker<'a> {
+    fn lookup(&self, name: &str) -> Option<usize> {
+        self.scope
+            .iter()
+            .rev()
+            .find(|&&i| self.res.defs[i].name == name)
+            .or_else(|| self.resolver.funcs.get(name))
+            .or_else(|| self.resolver.builtins.get(name))
+            .copied()
+    }
+
+    fn expr(&mut self, expr: &Spanned<Expr>) {
+        match &expr.0 {
+            Expr::Error | Expr::Value(_) => {}
+            Expr::Local(name) => match self.lookup(name) {
+                Some(def) => {
+                    self.res.uses.insert(expr.1.clone(), def);
+                }
+                None => self.res.errors.push(Error::new(
+                    expr.1.clone(),
+                    format!("No such variable '{}' in scope", name),
+                )),
+            },
+            Expr::Var((name, span), _, val, body) => {
+                self.expr(val);
+                let def = self.res.define(Def {
+                    name: name.clone(),
+                    kind: DefKind::Var,
+                    span: Some(span.clone()),
+                    slot: Some(self.scope.len()),
+                });
+                self.scope.push(def);
+                self.expr(body);
+                self.scope.pop();
+            }
+            Expr::Then(a, b) | Expr::Binary(a, _, b) => {
+                self.expr(a);
+                self.expr(b);
+            }
+            Expr::Call(func, (args, _)) => {
+                self.expr(func);
+                args.iter().for_each(|arg| self.expr(arg));
+            }
+            Expr::Return(a) => self.expr(a),
+            Expr::If(cond, a, b) => {
+                self.expr(cond);
+                self.expr(a);
+                self.expr(b);
+            }
+        }
+    }
+}
+
+///
+/// parse a program that has no errors and resolve the names in it, knowing `builtins`
+///
+#[cfg(test)]
+pub(crate) fn resolve_program(src: &str, builtins: &[&str]) -> (HashMap<String, Func>, Resolution) {
+    let funcs = parser::parsers::parse_program(src);
+    let mut resolver = Resolver::new();
+    builtins.iter().for_each(|name| resolver.builtin(name));
+    resolver.funcs(&funcs);
+    let res = resolver.resolve_funcs(&funcs);
+    (funcs, res)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{resolve_program, DefKind};
+
+    #[test]
+    fn resolves_names() {
+        let src = "fn f(x) { var y = x; var x = y + 1; g(x, print) }\nfn g(a, b) { c }";
+        let (_, res) = resolve_program(src, &["print"]);
+
+        let def = |offset| {
+            let def = &res.defs[res.lookup(offset).unwrap()];
+            (def.kind, def.span.clone(), def.slot)
+        };
+        // `x` in `var y = x` is the argument, later uses are the shadowing `var`
+        assert_eq!(def(18), (DefKind::Arg, Some(5..6), Some(0)));
+        assert_eq!(def(38), (DefKind::Var, Some(25..26), Some(2)));
+        assert_eq!(def(36), (DefKind::Func, Some(53..54), None));
+        assert_eq!(def(43), (DefKind::Builtin, None, None));
+        assert_eq!(res.references(res.lookup(25).unwrap()), vec![38..39]);
+
+        assert_eq!(res.errors.len(), 1);
+        assert_eq!(res.errors[0].span, 63..64);
+        assert_eq!(res.errors[0].msg, "No such variable 'c' in scope");
+    }
+}
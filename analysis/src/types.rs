@@ -0,0 +1,785 @@
+use interface::{
+    nodes::{binaryop::BinaryOp, expr::Expr, func::Func, types::Type, value::Value},
+    Error, Span, Spanned,
+};
+use std::collections::HashMap;
+
+use crate::resolver::{DefKind, Resolution};
+
+/// a type inferred by the checker
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Ty {
+    /// a type variable, standing for any type
+    Var(u32),
+    Int,
+    Bool,
+    Str,
+    Null,
+    List(Box<Ty>),
+    Func(Vec<Ty>, Box<Ty>),
+    /// elements of a list that have different types, which are no type in particular
+    Mixed,
+}
+
+impl Ty {
+    /// type variables in order of appearance
+    fn vars(&self, vars: &mut Vec<u32>) {
+        match self {
+            Ty::Var(v) if !vars.contains(v) => vars.push(*v),
+            Ty::List(elem) => elem.vars(vars),
+            Ty::Func(params, ret) => {
+                params.iter().for_each(|p| p.vars(vars));
+                ret.vars(vars);
+            }
+            _ => {}
+        }
+    }
+
+    /// print the type, naming variables `'a`, `'b`, ... in order of appearance in `vars`
+    fn show(&self, vars: &mut Vec<u32>) -> String {
+        match self {
+            Ty::Var(v) => {
+                let i = vars.iter().position(|x| x == v).unwrap_or_else(|| {
+                    vars.push(*v);
+                    vars.len() - 1
+                });
+                let letter = (b'a' + (i % 26) as u8) as char;
+                match i / 26 {
+                    0 => format!("'{}", letter),
+                    n => format!("'{}{}", letter, n),
+                }
+            }
+            Ty::Int => "int".to_string(),
+            Ty::Bool => "bool".to_string(),
+            Ty::Str => "str".to_string(),
+            Ty::Null => "null".to_string(),
+            Ty::Mixed => "mixed".to_string(),
+            Ty::List(elem) => format!("[{}]", elem.show(vars)),
+            Ty::Func(params, ret) => {
+                let params = params.iter().map(|p| p.show(vars)).collect::<Vec<_>>();
+                format!("fn({}) -> {}", params.join(", "), ret.show(vars))
+            }
+        }
+    }
+}
+
+impl From<&Type> for Ty {
+    fn from(ty: &Type) -> Self {
+        match ty {
+            Type::Int => Ty::Int,
+            Type::Bool => Ty::Bool,
+            Type::Str => Ty::Str,
+            Type::Null => Ty::Null,
+            Type::List(ty) => Ty::List(Box::new(ty.as_ref().into())),
+        }
+    }
+}
+
+impl std::fmt::Display for Ty {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.show(&mut Vec::new()))
+    }
+}
+
+/// a type that is polymorphic in some of its variables
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Scheme {
+    pub vars: Vec<u32>,
+    pub ty: Ty,
+}
+
+impl Scheme {
+    fn mono(ty: Ty) -> Self {
+        Self {
+            vars: Vec::new(),
+            ty,
+        }
+    }
+}
+
+impl std::fmt::Display for Scheme {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.ty)
+    }
+}
+
+///
+/// types inferred for a program
+///
+#[derive(Debug, Default, PartialEq)]
+pub struct Types {
+    /// type of each function
+    pub funcs: HashMap<String, Scheme>,
+    /// type of each name, by the span of its use or binding
+    pub names: HashMap<Span, Ty>,
+    /// type errors, in source order
+    pub errors: Vec<Error>,
+}
+
+impl Types {
+    /// the name at an offset and its type
+    pub fn at(&self, offset: usize) -> Option<(&Span, &Ty)> {
+        self.names
+            .iter()
+            .find(|(span, _)| span.start <= offset && offset <= span.end)
+    }
+}
+
+///
+/// infer the types of functions whose names were resolved by `res`,
+/// checking them against their annotations
+///
+pub fn check(funcs: &HashMap<String, Func>, res: &Resolution) -> Types {
+    let mut infer = Infer {
+        res,
+        subst: Vec::new(),
+        env: HashMap::new(),
+        mono: Vec::new(),
+        names: Vec::new(),
+        errors: Vec::new(),
+    };
+    let mut funcs_types = HashMap::new();
+    // functions are generalized before the functions that call them are checked
+    for group in groups(funcs, res) {
+        for (name, scheme) in infer.group(&group, funcs) {
+            funcs_types.insert(name.to_string(), scheme);
+        }
+    }
+
+    let names = infer
+        .names
+        .iter()
+        .map(|(span, ty)| (span.clone(), infer.prune(ty)))
+        .collect();
+    let mut errors = infer.errors;
+    errors.sort_by_key(|e| e.span.start);
+    Types {
+        funcs: funcs_types,
+        names,
+        errors,
+    }
+}
+
+///
+/// infer the type of a top-level expression whose names were resolved by `res`,
+/// given the types of the functions and the values of the globals in scope
+///
+pub fn check_expr(
+    expr: &Spanned<Expr>,
+    res: &Resolution,
+    funcs: &HashMap<String, Scheme>,
+    globals: &[Value],
+) -> Result<Scheme, Vec<Error>> {
+    // new type variables must not clash with those of the functions' types
+    let mut vars = Vec::new();
+    funcs.values().for_each(|scheme| scheme.ty.vars(&mut vars));
+    let mut infer = Infer {
+        res,
+        subst: vec![None; vars.iter().max().map_or(0, |&v| v as usize + 1)],
+        env: HashMap::new(),
+        mono: Vec::new(),
+        names: Vec::new(),
+        errors: Vec::new(),
+    };
+    for (def, d) in res.defs.iter().enumerate() {
+        let scheme = match (d.kind, d.slot) {
+            (DefKind::Func, _) => funcs.get(&d.name).cloned(),
+            (DefKind::Global, Some(slot)) => {
+                globals.get(slot).map(|val| Scheme::mono(infer.value(val)))
+            }
+            _ => None,
+        };
+        if let Some(scheme) = scheme {
+            infer.env.insert(def, scheme);
+        }
+    }
+    let ty = infer.infer(expr);
+    if !infer.errors.is_empty() {
+        infer.errors.sort_by_key(|e| e.span.start);
+        return Err(infer.errors);
+    }
+    Ok(infer.generalize(&ty))
+}
+
+struct Infer<'a> {
+    res: &'a Resolution,
+    /// what each type variable was unified with
+    subst: Vec<Option<Ty>>,
+    /// type of each definition checked so far, by index into the resolution's definitions
+    env: HashMap<usize, Scheme>,
+    /// definitions in scope whose types are not generalized
+    mono: Vec<usize>,
+    names: Vec<(Span, Ty)>,
+    errors: Vec<Error>,
+}
+
+impl<'a> Infer<'a> {
+    fn fresh(&mut self) -> Ty {
+        self.subst.push(None);
+        Ty::Var(self.subst.len() as u32 - 1)
+    }
+
+    /// follow the substitution until the outermost type is known
+    fn shallow(&self, ty: &Ty) -> Ty {
+        let mut ty = ty.clone();
+        while let Ty::Var(v) = ty {
+            match &self.subst[v as usize] {
+                Some(t) => ty = t.clone(),
+                None => break,
+            }
+        }
+        ty
+    }
+
+    /// apply the substitution everywhere in a type
+    fn prune(&self, ty: &Ty) -> Ty {
+        match self.shallow(ty) {
+            Ty::List(elem) => Ty::List(Box::new(self.prune(&elem))),
+            Ty::Func(params, ret) => Ty::Func(
+                params.iter().map(|p| self.prune(p)).collect(),
+                Box::new(self.prune(&ret)),
+            ),
+            ty => ty,
+        }
+    }
+
+    fn occurs(&self, v: u32, ty: &Ty) -> bool {
+        let mut vars = Vec::new();
+        self.prune(ty).vars(&mut vars);
+        vars.contains(&v)
+    }
+
+    fn unify(&mut self, a: &Ty, b: &Ty) -> bool {
+        match (self.shallow(a), self.shallow(b)) {
+            (Ty::Var(x), Ty::Var(y)) if x == y => true,
+            (Ty::Var(v), ty) | (ty, Ty::Var(v)) => {
+                if self.occurs(v, &ty) {
+                    return false;
+                }
+                self.subst[v as usize] = Some(ty);
+                true
+            }
+            (Ty::List(a), Ty::List(b)) => self.unify(&a, &b),
+            (Ty::Func(pa, ra), Ty::Func(pb, rb)) if pa.len() == pb.len() => {
+                pa.iter().zip(&pb).all(|(a, b)| self.unify(a, b)) && self.unify(&ra, &rb)
+            }
+            (a, b) => a == b,
+        }
+    }
+
+    /// unify the type found at `span` with the type the context expects
+    fn expect(&mut self, found: &Ty, expected: &Ty, span: &Span) {
+        if !self.unify(found, expected) {
+            let mut vars = Vec::new();
+            let expected = self.prune(expected).show(&mut vars);
+            let found = self.prune(found).show(&mut vars);
+            self.error(
+                span,
+                format!("Type mismatch: expected {}, found {}", expected, found),
+            );
+        }
+    }
+
+    fn error(&mut self, span: &Span, msg: String) {
+        self.errors.push(Error::new(span.clone(), msg));
+    }
+
+    fn instantiate(&mut self, scheme: &Scheme) -> Ty {
+        let fresh = scheme
+            .vars
+            .iter()
+            .map(|&v| (v, self.fresh()))
+            .collect::<HashMap<_, _>>();
+        substitute(&self.prune(&scheme.ty), &fresh)
+    }
+
+    /// quantify over the variables that no type in scope depends on
+    fn generalize(&self, ty: &Ty) -> Scheme {
+        let ty = self.prune(ty);
+        let mut bound = Vec::new();
+        self.mono
+            .iter()
+            .for_each(|def| self.prune(&self.env[def].ty).vars(&mut bound));
+        let mut vars = Vec::new();
+        ty.vars(&mut vars);
+        vars.retain(|v| !bound.contains(v));
+        Scheme { vars, ty }
+    }
+
+    /// bind a definition to a type that is not generalized while it is in scope
+    fn bind(&mut self, span: &Span, ty: Ty) {
+        if let Some(&def) = self.res.bindings.get(span) {
+            self.env.insert(def, Scheme::mono(ty.clone()));
+            self.mono.push(def);
+        }
+        self.names.push((span.clone(), ty));
+    }
+
+    /// type of a standard native function
+    fn builtin(&mut self, name: &str) -> Ty {
+        let a = self.fresh();
+        match name {
+            "len" => Ty::Func(vec![a], Box::new(Ty::Int)),
+            "get" => Ty::Func(vec![Ty::List(Box::new(a.clone())), Ty::Int], Box::new(a)),
+            "str" => Ty::Func(vec![a], Box::new(Ty::Str)),
+            "int" => Ty::Func(vec![a], Box::new(Ty::Int)),
+            "assert" => Ty::Func(vec![Ty::Bool], Box::new(Ty::Null)),
+            "assert_eq" => Ty::Func(vec![a.clone(), a], Box::new(Ty::Null)),
+            // functions registered by the host can be anything
+            _ => a,
+        }
+    }
+
+    ///
+    /// check a group of mutually recursive functions, returning their generalized types
+    ///
+    fn group<'f>(
+        &mut self,
+        names: &[&'f str],
+        funcs: &'f HashMap<String, Func>,
+    ) -> Vec<(&'f str, Scheme)> {
+        // the functions are monomorphic within their own group
+        let group = self.mono.len();
+        let types = names
+            .iter()
+            .map(|name| {
+                let func = &funcs[*name];
+                let params = func
+                    .arg_types
+                    .iter()
+                    .map(|ty| match ty {
+                        Some((ty, _)) => ty.into(),
+                        None => self.fresh(),
+                    })
+                    .collect::<Vec<_>>();
+                let ret = match &func.ret_type {
+                    Some((ty, _)) => ty.into(),
+                    None => self.fresh(),
+                };
+                let ty = Ty::Func(params, Box::new(ret));
+                self.bind(&func.name_span, ty.clone());
+                ty
+            })
+            .collect::<Vec<_>>();
+
+        for (name, ty) in names.iter().zip(&types) {
+            let func = &funcs[*name];
+            let (params, ret) = match ty {
+                Ty::Func(params, ret) => (params, ret),
+                _ => unreachable!(),
+            };
+            let scope = self.mono.len();
+            for (span, param) in func.arg_spans.iter().zip(params) {
+                self.bind(span, param.clone());
+            }
+            let body = self.infer(&func.body);
+            self.expect(&body, ret, &tail(&func.body).1);
+            self.mono.truncate(scope);
+        }
+
+        self.mono.truncate(group);
+        names
+            .iter()
+            .zip(types)
+            .map(|(name, ty)| {
+                let scheme = self.generalize(&ty);
+                if let Some(&def) = self.res.bindings.get(&funcs[*name].name_span) {
+                    self.env.insert(def, scheme.clone());
+                }
+                (*name, scheme)
+            })
+            .collect()
+    }
+
+    fn infer(&mut self, expr: &Spanned<Expr>) -> Ty {
+        let span = &expr.1;
+        match &expr.0 {
+            Expr::Error => self.fresh(),
+            Expr::Value(val) => self.value(val),
+            Expr::Local(_) => {
+                let ty = match self.res.uses.get(span) {
+                    Some(&def) => match (&self.res.defs[def], self.env.get(&def)) {
+                        (_, Some(scheme)) => {
+                            let scheme = scheme.clone();
+                            self.instantiate(&scheme)
+                        }
+                        (def, None) if def.kind == DefKind::Builtin => {
+                            let name = def.name.clone();
+                            self.builtin(&name)
+                        }
+                        // globals of the host are not known here
+                        _ => self.fresh(),
+                    },
+                    // the resolver has reported it already
+                    None => self.fresh(),
+                };
+                self.names.push((span.clone(), ty.clone()));
+                ty
+            }
+            Expr::Var((_, name_span), ann, val, body) => {
+                let ty = self.infer(val);
+                if let Some((ann, _)) = ann {
+                    self.expect(&ty, &ann.into(), &val.1);
+                }
+                let scheme = self.generalize(&ty);
+                self.names.push((name_span.clone(), ty));
+                let scope = self.mono.len();
+                if let Some(&def) = self.res.bindings.get(name_span) {
+                    self.env.insert(def, scheme);
+                }
+                let ty = self.infer(body);
+                self.mono.truncate(scope);
+                ty
+            }
+            Expr::Then(a, b) => {
+                self.statement(a);
+                self.infer(b)
+            }
+            Expr::Binary(a, op, b) => {
+                let (ta, tb) = (self.infer(a), self.infer(b));
+                match op {
+                    BinaryOp::Eq | BinaryOp::Neq => {
+                        self.expect(&tb, &ta, &b.1);
+                        Ty::Bool
+                    }
+                    BinaryOp::And | BinaryOp::Or => {
+                        self.expect(&ta, &Ty::Bool, &a.1);
+                        self.expect(&tb, &Ty::Bool, &b.1);
+                        Ty::Bool
+                    }
+                    BinaryOp::Gt | BinaryOp::Geq | BinaryOp::Lt | BinaryOp::Leq => {
+                        self.expect(&ta, &Ty::Int, &a.1);
+                        self.expect(&tb, &Ty::Int, &b.1);
+                        Ty::Bool
+                    }
+                    _ => {
+                        self.expect(&ta, &Ty::Int, &a.1);
+                        self.expect(&tb, &Ty::Int, &b.1);
+                        Ty::Int
+                    }
+                }
+            }
+            Expr::Call(func, (args, args_span)) => self.call(func, args, args_span),
+            Expr::Return(a) => self.infer(a),
+            Expr::If(cond, a, b) => {
+                let tc = self.infer(cond);
+                self.expect(&tc, &Ty::Bool, &cond.1);
+                let ta = self.infer(a);
+                let tb = self.infer(b);
+                self.expect(&tb, &ta, &tail(b).1);
+                ta
+            }
+        }
+    }
+
+    ///
+    /// infer the type of an expression whose value is discarded; an `if` without `else`
+    /// may then have a value only in one branch
+    ///
+    fn statement(&mut self, expr: &Spanned<Expr>) -> Ty {
+        match &expr.0 {
+            // the parser magics up a `null` for a missing `else`
+            Expr::If(cond, a, b) if b.0 == Expr::Value(Value::Null) => {
+                let tc = self.infer(cond);
+                self.expect(&tc, &Ty::Bool, &cond.1);
+                self.infer(a);
+                Ty::Null
+            }
+            _ => self.infer(expr),
+        }
+    }
+
+    fn value(&mut self, val: &Value) -> Ty {
+        match val {
+            Value::Null => Ty::Null,
+            Value::Bool(_) => Ty::Bool,
+            Value::I32(_) | Value::I64(_) => Ty::Int,
+            Value::Str(_) => Ty::Str,
+            Value::List(xs) => {
+                let elem = self.fresh();
+                for x in xs {
+                    let ty = self.value(x);
+                    self.unify(&ty, &elem);
+                }
+                Ty::List(Box::new(elem))
+            }
+            Value::Func(_) => self.fresh(),
+        }
+    }
+
+    fn call(&mut self, func: &Spanned<Expr>, args: &[Spanned<Expr>], args_span: &Span) -> Ty {
+        // variadic natives have no type of their own
+        let builtin = self
+            .res
+            .def_of(&func.1)
+            .filter(|def| def.kind == DefKind::Builtin)
+            .map(|def| def.name.as_str());
+        match builtin {
+            Some("print" | "println" | "eprint" | "eprintln") => {
+                args.iter().for_each(|arg| {
+                    self.infer(arg);
+                });
+                return Ty::Null;
+            }
+            // the elements of a list may have different types, which then cannot be used
+            // as any type in particular
+            Some("list") => {
+                let types = args.iter().map(|arg| self.infer(arg)).collect::<Vec<_>>();
+                let subst = self.subst.clone();
+                let elem = self.fresh();
+                if !types.iter().all(|ty| self.unify(ty, &elem)) {
+                    self.subst = subst;
+                    return Ty::List(Box::new(Ty::Mixed));
+                }
+                return Ty::List(Box::new(elem));
+            }
+            _ => {}
+        }
+
+        let tf = self.infer(func);
+        let targs = args.iter().map(|arg| self.infer(arg)).collect::<Vec<_>>();
+        match self.shallow(&tf) {
+            Ty::Func(params, ret) => {
+                if params.len() != args.len() {
+                    self.error(
+                        args_span,
+                        format!(
+                            "Wrong number of arguments (expected {}, found {})",
+                            params.len(),
+                            args.len()
+                        ),
+                    );
+                } else {
+                    for ((param, ty), arg) in params.iter().zip(&targs).zip(args) {
+                        self.expect(ty, param, &arg.1);
+                    }
+                }
+                *ret
+            }
+            _ => {
+                let ret = self.fresh();
+                self.expect(&tf, &Ty::Func(targs, Box::new(ret.clone())), &func.1);
+                ret
+            }
+        }
+    }
+}
+
+fn substitute(ty: &Ty, vars: &HashMap<u32, Ty>) -> Ty {
+    match ty {
+        Ty::Var(v) => vars.get(v).cloned().unwrap_or(Ty::Var(*v)),
+        Ty::List(elem) => Ty::List(Box::new(substitute(elem, vars))),
+        Ty::Func(params, ret) => Ty::Func(
+            params.iter().map(|p| substitute(p, vars)).collect(),
+            Box::new(substitute(ret, vars)),
+        ),
+        ty => ty.clone(),
+    }
+}
+
+/// the expression whose value a block produces, for pointing errors at it
+fn tail(expr: &Spanned<Expr>) -> &Spanned<Expr> {
+    match &expr.0 {
+        Expr::Then(_, b) | Expr::Var(_, _, _, b) => tail(b),
+        _ => expr,
+    }
+}
+
+/// names of the user functions an expression refers to
+fn callees(expr: &Spanned<Expr>, res: &Resolution, out: &mut Vec<String>) {
+    match &expr.0 {
+        Expr::Error | Expr::Value(_) => {}
+        Expr::Local(name) => {
+            if res.def_of(&expr.1).map(|def| def.kind) == Some(DefKind::Func) {
+                out.push(name.clone());
+            }
+        }
+        Expr::Var(_, _, a, b) | Expr::Then(a, b) | Expr::Binary(a, _, b) => {
+            callees(a, res, out);
+            callees(b, res, out);
+        }
+        Expr::Call(func, (args, _)) => {
+            callees(func, res, out);
+            args.iter().for_each(|arg| callees(arg, res, out));
+        }
+        Expr::Return(a) => callees(a, res, out),
+        Expr::If(cond, a, b) => {
+            callees(cond, res, out);
+            callees(a, res, out);
+            callees(b, res, out);
+        }
+    }
+}
+
+///
+/// groups of mutually recursive functions, with the functions a group calls in earlier groups
+///
+fn groups<'f>(funcs: &'f HashMap<String, Func>, res: &Resolution) -> Vec<Vec<&'f str>> {
+    let mut names = funcs.keys().map(String::as_str).collect::<Vec<_>>();
+    names.sort_by_key(|name| funcs[*name].name_span.start);
+    let index = names
+        .iter()
+        .enumerate()
+        .map(|(i, name)| (*name, i))
+        .collect::<HashMap<_, _>>();
+    let edges = names
+        .iter()
+        .map(|name| {
+            let mut out = Vec::new();
+            callees(&funcs[*name].body, res, &mut out);
+            out.iter()
+                .filter_map(|callee| index.get(callee.as_str()).copied())
+                .collect()
+        })
+        .collect::<Vec<Vec<usize>>>();
+
+    // Tarjan's algorithm finds the groups in reverse topological order, callees first
+    struct Tarjan<'g> {
+        edges: &'g [Vec<usize>],
+        index: Vec<Option<usize>>,
+        low: Vec<usize>,
+        stack: Vec<usize>,
+        on_stack: Vec<bool>,
+        next: usize,
+        groups: Vec<Vec<usize>>,
+    }
+
+    impl<'g> Tarjan<'g> {
+        fn visit(&mut self, v: usize) {
+            self.index[v] = Some(self.next);
+            self.low[v] = self.next;
+            self.next += 1;
+            self.stack.push(v);
+            self.on_stack[v] = true;
+            for &w in &self.edges[v] {
+                match self.index[w] {
+                    None => {
+                        self.visit(w);
+                        self.low[v] = self.low[v].min(self.low[w]);
+                    }
+                    Some(i) if self.on_stack[w] => self.low[v] = self.low[v].min(i),
+                    _ => {}
+                }
+            }
+            if Some(self.low[v]) == self.index[v] {
+                let mut group = Vec::new();
+                while let Some(w) = self.stack.pop() {
+                    self.on_stack[w] = false;
+                    group.push(w);
+                    if w == v {
+                        break;
+                    }
+                }
+                group.sort();
+                self.groups.push(group);
+            }
+        }
+    }
+
+    let mut tarjan = Tarjan {
+        edges: &edges,
+        index: vec![None; names.len()],
+        low: vec![0; names.len()],
+        stack: Vec::new(),
+        on_stack: vec![false; names.len()],
+        next: 0,
+        groups: Vec::new(),
+    };
+    for v in 0..names.len() {
+        if tarjan.index[v].is_none() {
+            tarjan.visit(v);
+        }
+    }
+    tarjan
+        .groups
+        .into_iter()
+        .map(|group| group.into_iter().map(|v| names[v]).collect())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{check, Types};
+    use crate::resolver::resolve_program;
+
+    fn check_src(src: &str) -> Types {
+        let builtins = ["print", "println", "list", "len", "get", "str", "int"];
+        let (funcs, res) = resolve_program(src, &builtins);
+        assert!(res.errors.is_empty());
+        check(&funcs, &res)
+    }
+
+    #[test]
+    fn infers_types() {
+        let types = check_src(
+            "fn fib(n) { if n < 2 { n } else { fib(n - 1) + fib(n - 2) } }
+fn apply(f, x) { f(x) }
+fn main() { var id = apply; println(id(fib, 10), id(len, \"abc\")); get(list(1, 2), 0) == 1 }
+fn head(xss) { var xs: [int] = get(xss, 0); xs }",
+        );
+        assert!(types.errors.is_empty(), "{:?}", types.errors);
+        let ty = |name: &str| types.funcs[name].to_string();
+        assert_eq!(ty("fib"), "fn(int) -> int");
+        assert_eq!(ty("apply"), "fn(fn('a) -> 'b, 'a) -> 'b");
+        assert_eq!(ty("main"), "fn() -> bool");
+        assert_eq!(ty("head"), "fn([[int]]) -> [int]");
+        // the argument `n` of `fib`
+        assert_eq!(types.at(8).unwrap().1.to_string(), "int");
+    }
+
+    #[test]
+    fn reports_type_errors() {
+        let types = check_src(
+            "fn add(a: int, b: int) -> int { a + b }
+fn name() -> str { 1 }
+fn main() { add(1, \"x\"); if 1 { 2 }; list(1, name()) }",
+        );
+        let errors = types
+            .errors
+            .iter()
+            .map(|e| (e.span.clone(), e.msg.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            errors,
+            vec![
+                (59..60, "Type mismatch: expected str, found int"),
+                (82..85, "Type mismatch: expected int, found str"),
+                (91..92, "Type mismatch: expected bool, found int"),
+            ]
+        );
+    }
+
+    #[test]
+    fn accepts_if_without_else_and_mixed_lists() {
+        let types = check_src(
+            "fn f(n) { if n > 2 { n + 1 }; n }
+fn g() { var xs = list(1, \"a\"); println(xs); len(xs) + get(list(1, 2), 0) }",
+        );
+        assert!(types.errors.is_empty(), "{:?}", types.errors);
+        let ty = |name: &str| types.funcs[name].to_string();
+        assert_eq!(ty("f"), "fn(int) -> int");
+        assert_eq!(ty("g"), "fn() -> int");
+    }
+
+    #[test]
+    fn uses_values_of_if_without_else_and_mixed_lists_soundly() {
+        let types = check_src(
+            "fn h(b) { if b { 1 } }
+fn k() { get(list(1, \"a\"), 1) + 1 }
+fn m() -> [int] { list(1, \"a\") }",
+        );
+        let errors = types
+            .errors
+            .iter()
+            .map(|e| (e.span.clone(), e.msg.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            errors,
+            vec![
+                (10..20, "Type mismatch: expected int, found null"),
+                (32..52, "Type mismatch: expected int, found mixed"),
+                (77..89, "Type mismatch: expected [int], found [mixed]"),
+            ]
+        );
+    }
+}
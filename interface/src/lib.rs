@@ -6,8 +6,43 @@ pub type Span = std::ops::Range<usize>;
 
 pub type Spanned<T> = (T, Span);
 
+use nodes::value::Value;
+
 #[derive(Clone, Debug, PartialEq)]
 pub struct Error {
     pub span: Span,
     pub msg: String,
+    /// calls being evaluated when a runtime error occurred, innermost first
+    pub trace: Vec<Frame>,
+}
+
+impl Error {
+    /// an error without a stack trace, which runtime errors gain as they leave calls
+    pub fn new(span: Span, msg: impl ToString) -> Self {
+        Self {
+            span,
+            msg: msg.to_string(),
+            trace: Vec::new(),
+        }
+    }
+}
+
+/// a function call in a runtime error's stack trace
+#[derive(Clone, Debug, PartialEq)]
+pub struct Frame {
+    pub func: String,
+    pub call_span: Span,
+    pub args: Vec<Value>,
+}
+
+impl std::fmt::Display for Frame {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let args = self
+            .args
+            .iter()
+            .map(|arg| arg.to_string())
+            .collect::<Vec<_>>()
+            .join(", ");
+        write!(f, "{}({})", self.func, args)
+    }
 }
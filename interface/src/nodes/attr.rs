@@ -0,0 +1,13 @@
+use serde::Serialize;
+
+use crate::{tokens::Token, Span, Spanned};
+
+/// an attribute on a function, such as `#[deprecated("use g")]`
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Attr {
+    pub name: Spanned<String>,
+    /// literals and names between the parentheses, if any
+    pub args: Vec<Spanned<Token>>,
+    /// span of the whole attribute, from `#` to `]`
+    pub span: Span,
+}
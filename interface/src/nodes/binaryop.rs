@@ -1,4 +1,6 @@
-#[derive(Clone, Debug)]
+use serde::Serialize;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
 pub enum BinaryOp {
     Or,  // ||
     And, // &&
@@ -14,3 +16,38 @@ pub enum BinaryOp {
     Div, // /
     Mod, // %
 }
+
+impl std::fmt::Display for BinaryOp {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let op = match self {
+            Self::Or => "||",
+            Self::And => "&&",
+            Self::Eq => "==",
+            Self::Neq => "!=",
+            Self::Geq => ">=",
+            Self::Leq => "<=",
+            Self::Gt => ">",
+            Self::Lt => "<",
+            Self::Add => "+",
+            Self::Sub => "-",
+            Self::Mul => "*",
+            Self::Div => "/",
+            Self::Mod => "%",
+        };
+        write!(f, "{}", op)
+    }
+}
+
+impl BinaryOp {
+    /// binding power of the operator, higher binds tighter
+    pub fn precedence(&self) -> u8 {
+        match self {
+            Self::Or => 0,
+            Self::And => 1,
+            Self::Eq | Self::Neq => 2,
+            Self::Geq | Self::Leq | Self::Gt | Self::Lt => 3,
+            Self::Add | Self::Sub => 4,
+            Self::Mul | Self::Div | Self::Mod => 5,
+        }
+    }
+}
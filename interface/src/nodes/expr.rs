@@ -1,15 +1,23 @@
+use serde::Serialize;
+
 use crate::Spanned;
 
-use super::{binaryop::BinaryOp, value::Value};
+use super::{binaryop::BinaryOp, types::Type, value::Value};
 
 // An expression node in the AST. Children are spanned so we can generate useful runtime errors.
-#[derive(Debug)]
+#[derive(Clone, Debug, PartialEq, Serialize)]
 pub enum Expr {
     Error,
     Value(Value),
     // List(Vec<Spanned<Self>>),
     Local(String),
-    Var(String, Box<Spanned<Self>>, Box<Spanned<Self>>),
+    /// `var name: type = value; body`
+    Var(
+        Spanned<String>,
+        Option<Spanned<Type>>,
+        Box<Spanned<Self>>,
+        Box<Spanned<Self>>,
+    ),
     Then(Box<Spanned<Self>>, Box<Spanned<Self>>),
     Binary(Box<Spanned<Self>>, BinaryOp, Box<Spanned<Self>>),
     Call(Box<Spanned<Self>>, Spanned<Vec<Spanned<Self>>>),
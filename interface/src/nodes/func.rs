@@ -1,10 +1,25 @@
-use crate::Spanned;
+use serde::Serialize;
 
-use super::expr::Expr;
+use crate::{Span, Spanned};
+
+use super::{attr::Attr, expr::Expr, types::Type};
 
 // A function node in the AST.
-#[derive(Debug)]
+#[derive(Clone, Debug, PartialEq, Serialize)]
 pub struct Func {
     pub args: Vec<String>,
     pub body: Spanned<Expr>,
+    /// span of the function name
+    pub name_span: Span,
+    /// span of the argument list, including the parentheses
+    pub args_span: Span,
+    /// span of each argument name
+    pub arg_spans: Vec<Span>,
+    /// annotated type of each argument
+    pub arg_types: Vec<Option<Spanned<Type>>>,
+    /// annotated return type
+    pub ret_type: Option<Spanned<Type>>,
+    /// whether results are cached, declared with `memo fn` or `#[memo]`
+    pub memo: bool,
+    pub attrs: Vec<Attr>,
 }
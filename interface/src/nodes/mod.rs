@@ -1,4 +1,6 @@
+pub mod attr;
 pub mod binaryop;
 pub mod expr;
 pub mod func;
+pub mod types;
 pub mod value;
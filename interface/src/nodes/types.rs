@@ -0,0 +1,51 @@
+use serde::Serialize;
+
+use super::value::Value;
+
+/// a type written in an annotation, such as `int` in `fn f(x: int)`
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub enum Type {
+    Int,
+    Bool,
+    Str,
+    Null,
+    /// `[int]`
+    List(Box<Type>),
+}
+
+impl Type {
+    /// the type an annotation names, if any
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "int" => Some(Type::Int),
+            "bool" => Some(Type::Bool),
+            "str" => Some(Type::Str),
+            "null" => Some(Type::Null),
+            _ => None,
+        }
+    }
+
+    /// whether a value has this type
+    pub fn matches(&self, val: &Value) -> bool {
+        match (self, val) {
+            (Type::Int, Value::I32(_) | Value::I64(_))
+            | (Type::Bool, Value::Bool(_))
+            | (Type::Str, Value::Str(_))
+            | (Type::Null, Value::Null) => true,
+            (Type::List(ty), Value::List(xs)) => xs.iter().all(|x| ty.matches(x)),
+            _ => false,
+        }
+    }
+}
+
+impl std::fmt::Display for Type {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Type::Int => write!(f, "int"),
+            Type::Bool => write!(f, "bool"),
+            Type::Str => write!(f, "str"),
+            Type::Null => write!(f, "null"),
+            Type::List(ty) => write!(f, "[{}]", ty),
+        }
+    }
+}
@@ -1,25 +1,39 @@
+use serde::Serialize;
+
 use crate::{Error, Span};
 
-#[derive(Clone, Debug, PartialEq)]
+use super::types::Type;
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize)]
 pub enum Value {
     Null,
     Bool(bool),
     I32(i32),
     I64(i64),
-    // Str(String),
-    // List(Vec<Value>),
+    Str(String),
+    List(Vec<Value>),
     Func(String),
 }
 
 impl Value {
+    /// name of the runtime type of this value
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Null => "null",
+            Value::Bool(_) => "bool",
+            Value::I32(_) => "i32",
+            Value::I64(_) => "i64",
+            Value::Str(_) => "str",
+            Value::List(_) => "list",
+            Value::Func(_) => "function",
+        }
+    }
+
     pub fn num_i32(self, span: Span) -> Result<i32, Error> {
         if let Value::I32(x) = self {
             Ok(x)
         } else {
-            Err(Error {
-                span,
-                msg: format!("'{}' is not a number", self),
-            })
+            Err(Error::new(span, format!("'{}' is not a number", self)))
         }
     }
 
@@ -27,10 +41,19 @@ impl Value {
         if let Value::I64(x) = self {
             Ok(x)
         } else {
-            Err(Error {
+            Err(Error::new(span, format!("'{}' is not a number", self)))
+        }
+    }
+
+    /// fail unless the value has an annotated type
+    pub fn check_type(&self, ty: &Type, span: Span) -> Result<(), Error> {
+        if ty.matches(self) {
+            Ok(())
+        } else {
+            Err(Error::new(
                 span,
-                msg: format!("'{}' is not a number", self),
-            })
+                format!("'{}' is not of type {}", self, ty),
+            ))
         }
     }
 
@@ -38,10 +61,7 @@ impl Value {
         if let Value::Bool(b) = self {
             Ok(b)
         } else {
-            Err(Error {
-                span,
-                msg: format!("'{}' is not a bool", self),
-            })
+            Err(Error::new(span, format!("'{}' is not a bool", self)))
         }
     }
 }
@@ -53,15 +73,15 @@ impl std::fmt::Display for Value {
             Self::Bool(x) => write!(f, "{}", x),
             Self::I32(x) => write!(f, "{}", x),
             Self::I64(x) => write!(f, "{}", x),
-            // Self::Str(x) => write!(f, "{}", x),
-            // Self::List(xs) => write!(
-            //     f,
-            //     "[{}]",
-            //     xs.iter()
-            //         .map(|x| x.to_string())
-            //         .collect::<Vec<_>>()
-            //         .join(", ")
-            // ),
+            Self::Str(x) => write!(f, "{}", x),
+            Self::List(xs) => write!(
+                f,
+                "[{}]",
+                xs.iter()
+                    .map(|x| x.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
             Self::Func(name) => write!(f, "<function: {}>", name),
         }
     }
@@ -1,15 +1,17 @@
+use serde::Serialize;
 use std::fmt;
 
-#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize)]
 pub enum Token {
     // Null,
     // Bool(bool),
     Num(String),
-    // Str(String),
+    Str(String),
     Op(String),
     Ctrl(char),
     Ident(String),
     Fn,
+    Memo,
     Var,
     // Print,
     Return,
@@ -24,11 +26,12 @@ impl fmt::Display for Token {
             // Token::Null => write!(f, "null"),
             // Token::Bool(x) => write!(f, "{}", x),
             Token::Num(n) => write!(f, "{}", n),
-            // Token::Str(s) => write!(f, "{}", s),
+            Token::Str(s) => write!(f, "\"{}\"", s),
             Token::Op(s) => write!(f, "{}", s),
             Token::Ctrl(c) => write!(f, "{}", c),
             Token::Ident(s) => write!(f, "{}", s),
             Token::Fn => write!(f, "fn"),
+            Token::Memo => write!(f, "memo"),
             Token::Var => write!(f, "var"),
             Token::Return => write!(f, "return"),
             // Token::Print => write!(f, "print"),
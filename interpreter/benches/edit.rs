@@ -0,0 +1,53 @@
+//!
+//! how long the diagnostics of a large program take after an edit, against a fresh database
+//!
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use interpreter::db::Database;
+use parser::incremental::Edit;
+
+/// a program of `funcs` functions of about five lines each
+fn program(funcs: usize) -> String {
+    (0..funcs)
+        .map(|i| {
+            format!(
+                "// step {0}\nfn f{0}(n: int) -> int {{\n    var x = n * {0} + 1;\n    if x > 100 {{ f{1}(x - 100) }} else {{ x }}\n}}\n\n",
+                i,
+                i.saturating_sub(1)
+            )
+        })
+        .collect()
+}
+
+fn bench(c: &mut Criterion) {
+    for funcs in [100, 1000] {
+        let src = program(funcs);
+        // typing a digit in the middle of the program, and deleting it again
+        // the program is ascii, so its bytes are its chars
+        let at = src.find(&format!("n * {} ", funcs / 2)).unwrap() + 4;
+        let (add, delete) = (Edit::new(at..at, "7"), Edit::new(at..at + 1, ""));
+
+        let lines = src.lines().count();
+        c.bench_function(&format!("check {} lines", lines), |b| {
+            b.iter(|| {
+                let mut db = Database::new();
+                db.set_source("a.ipu", black_box(&src));
+                db.diagnostics("a.ipu")
+            })
+        });
+        let mut db = Database::new();
+        db.set_source("a.ipu", &src);
+        assert!(db.diagnostics("a.ipu").is_empty());
+        c.bench_function(&format!("edit and check {} lines twice", lines), |b| {
+            b.iter(|| {
+                db.edit_source("a.ipu", black_box(&add));
+                db.diagnostics("a.ipu");
+                db.edit_source("a.ipu", black_box(&delete));
+                db.diagnostics("a.ipu")
+            })
+        });
+    }
+}
+
+criterion_group!(benches, bench);
+criterion_main!(benches);
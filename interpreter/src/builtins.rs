@@ -0,0 +1,207 @@
+use interface::{nodes::value::Value, Error};
+use std::{cell::RefCell, collections::HashMap, io, rc::Rc};
+
+use crate::output::Output;
+
+/// a function implemented in Rust
+pub type NativeFn = Rc<dyn Fn(&[Value]) -> Result<Value, Error>>;
+
+/// number of arguments a native function accepts
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Arity {
+    Exact(usize),
+    /// any number of arguments
+    Variadic,
+}
+
+impl Arity {
+    pub fn accepts(&self, n: usize) -> bool {
+        match self {
+            Arity::Exact(m) => *m == n,
+            Arity::Variadic => true,
+        }
+    }
+}
+
+impl std::fmt::Display for Arity {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Arity::Exact(n) => write!(f, "{}", n),
+            Arity::Variadic => write!(f, "any number"),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Builtin {
+    pub arity: Arity,
+    pub func: NativeFn,
+}
+
+/// native functions callable from scripts, by name
+#[derive(Clone, Default)]
+pub struct Builtins {
+    funcs: HashMap<String, Builtin>,
+}
+
+impl Builtins {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// the standard library, printing to `output`
+    pub fn with_std(output: Rc<RefCell<dyn Output>>) -> Self {
+        let mut builtins = Self::new();
+        for name in ["print", "println", "eprint", "eprintln"] {
+            let output = output.clone();
+            builtins.register(name, Arity::Variadic, move |args| {
+                let mut s = format_values(args).map_err(native_err)?;
+                if name.ends_with("ln") {
+                    s.push('\n');
+                }
+                let mut output = output.borrow_mut();
+                let res: io::Result<()> = if name.starts_with('e') {
+                    output.err(&s)
+                } else {
+                    output.out(&s)
+                };
+                res.map_err(|e| native_err(format!("Failed to write output: {}", e)))?;
+                Ok(Value::Null)
+            });
+        }
+        builtins.register("list", Arity::Variadic, |args| {
+            Ok(Value::List(args.to_vec()))
+        });
+        builtins.register("len", Arity::Exact(1), |args| match &args[0] {
+            Value::Str(s) => Ok(Value::I32(s.chars().count() as i32)),
+            Value::List(xs) => Ok(Value::I32(xs.len() as i32)),
+            x => Err(native_err(format!("'{}' has no length", x))),
+        });
+        builtins.register("get", Arity::Exact(2), |args| match (&args[0], &args[1]) {
+            (Value::List(xs), Value::I32(i)) => usize::try_from(*i)
+                .ok()
+                .and_then(|i| xs.get(i))
+                .cloned()
+                .ok_or_else(|| native_err(format!("Index {} is out of bounds", i))),
+            (xs, i) => Err(native_err(format!("Cannot index '{}' with '{}'", xs, i))),
+        });
+        builtins.register("str", Arity::Exact(1), |args| {
+            Ok(Value::Str(args[0].to_string()))
+        });
+        builtins.register("int", Arity::Exact(1), |args| match &args[0] {
+            Value::Str(s) => s
+                .trim()
+                .parse()
+                .map(Value::I32)
+                .map_err(|_| native_err(format!("'{}' is not a number", s))),
+            Value::I32(x) => Ok(Value::I32(*x)),
+            x => Err(native_err(format!("'{}' is not a number", x))),
+        });
+        builtins.register("assert", Arity::Exact(1), |args| match &args[0] {
+            Value::Bool(true) => Ok(Value::Null),
+            Value::Bool(false) => Err(native_err("Assertion failed")),
+            x => Err(native_err(format!("'{}' is not a bool", x))),
+        });
+        builtins.register("assert_eq", Arity::Exact(2), |args| {
+            if args[0] == args[1] {
+                Ok(Value::Null)
+            } else {
+                Err(native_err(format!(
+                    "Assertion failed: '{}' is not equal to '{}'",
+                    args[0], args[1]
+                )))
+            }
+        });
+        builtins
+    }
+
+    ///
+    /// register a native function, replacing any function of the same name
+    ///
+    pub fn register(
+        &mut self,
+        name: impl Into<String>,
+        arity: Arity,
+        func: impl Fn(&[Value]) -> Result<Value, Error> + 'static,
+    ) {
+        self.funcs.insert(
+            name.into(),
+            Builtin {
+                arity,
+                func: Rc::new(func),
+            },
+        );
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Builtin> {
+        self.funcs.get(name)
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.funcs.contains_key(name)
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.funcs.keys().map(String::as_str)
+    }
+}
+
+///
+/// an error from a native function, which has no source location of its own;
+/// the interpreter points it at the arguments of the failing call
+///
+pub fn native_err(msg: impl ToString) -> Error {
+    Error::new(0..0, msg)
+}
+
+///
+/// format print arguments: `{}`s in a leading string are replaced by the
+/// remaining arguments, otherwise all arguments are joined by spaces
+///
+pub fn format_values(args: &[Value]) -> Result<String, String> {
+    let (fmt, args) = match args {
+        [Value::Str(fmt), args @ ..] if fmt.contains("{}") => (fmt, args),
+        _ => {
+            return Ok(args
+                .iter()
+                .map(|arg| arg.to_string())
+                .collect::<Vec<_>>()
+                .join(" "))
+        }
+    };
+    let pieces = fmt.split("{}").collect::<Vec<_>>();
+    if pieces.len() - 1 != args.len() {
+        return Err(format!(
+            "Format string expects {} arguments, found {}",
+            pieces.len() - 1,
+            args.len()
+        ));
+    }
+    let mut s = pieces[0].to_string();
+    for (arg, piece) in args.iter().zip(&pieces[1..]) {
+        s.push_str(&arg.to_string());
+        s.push_str(piece);
+    }
+    Ok(s)
+}
+
+#[cfg(test)]
+mod tests {
+    use interface::nodes::value::Value;
+
+    use super::format_values;
+
+    #[test]
+    fn formats_values() {
+        let s = |s: &str| Value::Str(s.to_string());
+        assert_eq!(
+            format_values(&[s("{} + {}"), Value::I32(1), Value::Bool(true)]),
+            Ok("1 + true".to_string())
+        );
+        assert_eq!(
+            format_values(&[s("a"), Value::I32(1)]),
+            Ok("a 1".to_string())
+        );
+        assert!(format_values(&[s("{} {}"), Value::I32(1)]).is_err());
+    }
+}
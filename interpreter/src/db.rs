@@ -0,0 +1,731 @@
+//!
+//! memoised queries from the text of a source to its diagnostics, shared by `ipu check`
+//! and the language server; a query is only computed again when something it reads
+//! changed since it was last computed, and what reads it is only computed again
+//! when its result is different
+//!
+
+use analysis::{
+    attrs::AttrRegistry,
+    lint::{lint, LintConfig},
+    purity::check_memos,
+    resolver::{Resolution, Resolver},
+    types::{check, Types},
+};
+use chumsky::prelude::Simple;
+use interface::{
+    nodes::{attr::Attr, expr::Expr, func::Func, types::Type},
+    tokens::Token,
+    Span, Spanned,
+};
+use parser::{
+    cst::{lexed_tokens, parse_cst, SyntaxElement, SyntaxNode},
+    incremental::{reparse, Edit},
+    lexer::lex,
+    parsers::funcs::collect_funcs,
+};
+use rowan::{GreenNode, NodeOrToken};
+use std::{cell::RefCell, collections::HashMap, fmt::Display, hash::Hash, rc::Rc, sync::Arc};
+
+use crate::{
+    builtins::Builtins,
+    diagnostic::{Diagnostic, Severity},
+    frontend::parse_tokens,
+    lints::to_diagnostic,
+    output::StdOutput,
+};
+
+/// a point in the history of the inputs, counting changes to them
+pub type Revision = u64;
+
+/// the queries of a source
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Query {
+    Tokens,
+    Ast,
+    /// a function item, parsed on its own
+    Item,
+    Resolution,
+    Types,
+    Diagnostics,
+    Lints,
+}
+
+/// what the lexer made of a source
+#[derive(Debug, PartialEq)]
+pub struct Lexed {
+    /// all tokens, including comments
+    pub tokens: Option<Vec<Spanned<Token>>>,
+    pub errs: Vec<Simple<char>>,
+    /// length of the source in chars
+    pub len: usize,
+}
+
+/// what the parser made of the tokens of a source
+#[derive(Debug, PartialEq)]
+pub struct Ast {
+    pub funcs: Option<HashMap<String, Func>>,
+    pub errs: Vec<Simple<Token>>,
+}
+
+/// the functions parsed from an item of a source without errors, with spans from its start
+type Item = Vec<(String, Func)>;
+
+/// a computed value and when it was last known to be up to date
+struct Memo<T> {
+    value: Arc<T>,
+    /// the last revision in which the value was checked against its inputs
+    verified_at: Revision,
+    /// the revision in which the value last changed
+    changed_at: Revision,
+}
+
+struct Slot<T>(Option<Memo<T>>);
+
+impl<T> Default for Slot<T> {
+    fn default() -> Self {
+        Self(None)
+    }
+}
+
+impl<T: PartialEq> Slot<T> {
+    ///
+    /// the value and the revision it last changed in, computed again
+    /// if an input changed in a revision after `inputs`
+    ///
+    fn get(
+        &mut self,
+        revision: Revision,
+        inputs: Revision,
+        compute: impl FnOnce() -> T,
+    ) -> (Arc<T>, Revision) {
+        match &mut self.0 {
+            Some(memo) if memo.verified_at >= inputs => memo.verified_at = revision,
+            slot => {
+                let value = compute();
+                match slot {
+                    // the same value as before, so nothing that reads it has to be computed again
+                    Some(memo) if *memo.value == value => memo.verified_at = revision,
+                    _ => {
+                        *slot = Some(Memo {
+                            value: Arc::new(value),
+                            verified_at: revision,
+                            changed_at: revision,
+                        })
+                    }
+                }
+            }
+        }
+        let memo = self.0.as_ref().unwrap();
+        (memo.value.clone(), memo.changed_at)
+    }
+}
+
+/// a source and the queries computed from it
+struct File {
+    src: Arc<str>,
+    /// the lossless tree of the source, built when first needed and reparsed on edits
+    tree: Option<GreenNode>,
+    /// what the items of the tree without errors parsed to, by the item
+    items: HashMap<GreenNode, Item>,
+    changed_at: Revision,
+    tokens: Slot<Lexed>,
+    ast: Slot<Ast>,
+    resolution: Slot<Resolution>,
+    types: Slot<Types>,
+    diagnostics: Slot<Vec<Diagnostic>>,
+    lints: Slot<Vec<Diagnostic>>,
+}
+
+///
+/// the sources of programs and what is known about them
+///
+/// ```
+/// use interpreter::db::Database;
+///
+/// let mut db = Database::new();
+/// db.set_source("a.ipu", "fn main() { x }");
+/// assert_eq!(db.diagnostics("a.ipu")[0].message, "No such variable 'x' in scope");
+/// db.set_source("a.ipu", "fn main() { 1 }");
+/// assert!(db.diagnostics("a.ipu").is_empty());
+/// ```
+///
+pub struct Database {
+    revision: Revision,
+    files: HashMap<String, File>,
+    /// names of the native functions, which do not change
+    builtins: Vec<String>,
+    attrs: AttrRegistry,
+    lint_config: LintConfig,
+    lint_config_changed_at: Revision,
+    /// how many times each query has been computed
+    executions: HashMap<Query, usize>,
+}
+
+impl Default for Database {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Database {
+    /// a database that knows the standard builtins and attributes
+    pub fn new() -> Self {
+        let builtins = Builtins::with_std(Rc::new(RefCell::new(StdOutput)));
+        Self {
+            revision: 0,
+            files: HashMap::new(),
+            builtins: builtins.names().map(str::to_string).collect(),
+            attrs: AttrRegistry::with_std(),
+            lint_config: LintConfig::default(),
+            lint_config_changed_at: 0,
+            executions: HashMap::new(),
+        }
+    }
+
+    pub fn revision(&self) -> Revision {
+        self.revision
+    }
+
+    /// how many times a query has been computed, for any source
+    pub fn executions(&self, query: Query) -> usize {
+        self.executions.get(&query).copied().unwrap_or_default()
+    }
+
+    ///
+    /// set the text of a source, starting a new revision unless it is the same as before
+    ///
+    pub fn set_source(&mut self, name: &str, src: &str) {
+        if self.source(name).as_deref() == Some(src) {
+            return;
+        }
+        self.revision += 1;
+        let src = Arc::from(src);
+        match self.files.get_mut(name) {
+            Some(file) => {
+                file.src = src;
+                file.tree = None;
+                file.changed_at = self.revision;
+            }
+            None => {
+                self.files.insert(
+                    name.to_string(),
+                    File {
+                        src,
+                        tree: None,
+                        items: HashMap::new(),
+                        changed_at: self.revision,
+                        tokens: Slot::default(),
+                        ast: Slot::default(),
+                        resolution: Slot::default(),
+                        types: Slot::default(),
+                        diagnostics: Slot::default(),
+                        lints: Slot::default(),
+                    },
+                );
+            }
+        }
+    }
+
+    ///
+    /// change part of a source, which must have been set, reparsing only the items
+    /// of its tree around the edit; starts a new revision unless nothing changed
+    ///
+    pub fn edit_source(&mut self, name: &str, edit: &Edit) {
+        let file = self.files.get_mut(name);
+        let file = file.unwrap_or_else(|| panic!("no source named '{}'", name));
+        let src = edit.apply(&file.src);
+        if *src == *file.src {
+            return;
+        }
+        let tree = match &file.tree {
+            Some(tree) => reparse(&SyntaxNode::new_root(tree.clone()), edit),
+            None => parse_cst(&src),
+        };
+        self.revision += 1;
+        file.src = Arc::from(src);
+        file.tree = Some(tree.green().into_owned());
+        file.changed_at = self.revision;
+    }
+
+    /// set the levels of the lint rules, starting a new revision unless they are the same
+    pub fn set_lint_config(&mut self, config: LintConfig) {
+        if self.lint_config != config {
+            self.revision += 1;
+            self.lint_config = config;
+            self.lint_config_changed_at = self.revision;
+        }
+    }
+
+    /// forget a source and everything computed from it
+    pub fn remove_source(&mut self, name: &str) {
+        if self.files.remove(name).is_some() {
+            self.revision += 1;
+        }
+    }
+
+    pub fn source(&self, name: &str) -> Option<Arc<str>> {
+        self.files.get(name).map(|file| file.src.clone())
+    }
+
+    fn count(executions: &mut HashMap<Query, usize>, query: Query) {
+        *executions.entry(query).or_default() += 1;
+    }
+
+    ///
+    /// the tokens of a source, which must have been set
+    ///
+    pub fn tokens(&mut self, name: &str) -> Arc<Lexed> {
+        self.tokens_at(name).0
+    }
+
+    fn tokens_at(&mut self, name: &str) -> (Arc<Lexed>, Revision) {
+        let revision = self.revision;
+        let file = self.files.get_mut(name);
+        let file = file.unwrap_or_else(|| panic!("no source named '{}'", name));
+        let executions = &mut self.executions;
+        let File {
+            src,
+            tree,
+            tokens,
+            changed_at,
+            ..
+        } = file;
+        tokens.get(revision, *changed_at, || {
+            Self::count(executions, Query::Tokens);
+            let tree = tree.get_or_insert_with(|| parse_cst(src).green().into_owned());
+            let (tokens, clean) = lexed_tokens(&SyntaxNode::new_root(tree.clone()), 0);
+            // only the lexer knows what it expected where it skipped text
+            let (tokens, errs) = match clean {
+                true => (Some(tokens), Vec::new()),
+                false => lex(src),
+            };
+            Lexed {
+                tokens,
+                errs,
+                len: src.chars().count(),
+            }
+        })
+    }
+
+    /// the functions of a source, as far as it parses
+    pub fn ast(&mut self, name: &str) -> Arc<Ast> {
+        self.ast_at(name).0
+    }
+
+    fn ast_at(&mut self, name: &str) -> (Arc<Ast>, Revision) {
+        let revision = self.revision;
+        let (lexed, inputs) = self.tokens_at(name);
+        let executions = &mut self.executions;
+        let file = self.files.get_mut(name).unwrap();
+        let File {
+            tree, items, ast, ..
+        } = file;
+        ast.get(revision, inputs, || {
+            Self::count(executions, Query::Ast);
+            match (&lexed.tokens, tree) {
+                // the tokens were made from the tree
+                (Some(_), Some(tree)) => parse_items(tree, items, executions),
+                _ => Ast {
+                    funcs: None,
+                    errs: Vec::new(),
+                },
+            }
+        })
+    }
+
+    /// what the names in the functions of a source refer to
+    pub fn resolution(&mut self, name: &str) -> Arc<Resolution> {
+        self.resolution_at(name).0
+    }
+
+    fn resolution_at(&mut self, name: &str) -> (Arc<Resolution>, Revision) {
+        let revision = self.revision;
+        let (ast, inputs) = self.ast_at(name);
+        let executions = &mut self.executions;
+        let builtins = &self.builtins;
+        let file = self.files.get_mut(name).unwrap();
+        file.resolution.get(revision, inputs, || {
+            Self::count(executions, Query::Resolution);
+            let funcs = ast.funcs.clone().unwrap_or_default();
+            let mut resolver = Resolver::new();
+            builtins.iter().for_each(|name| resolver.builtin(name));
+            resolver.funcs(&funcs);
+            resolver.resolve_funcs(&funcs)
+        })
+    }
+
+    /// the types inferred for the functions of a source
+    pub fn types(&mut self, name: &str) -> Arc<Types> {
+        self.types_at(name).0
+    }
+
+    fn types_at(&mut self, name: &str) -> (Arc<Types>, Revision) {
+        let revision = self.revision;
+        let (ast, ast_changed) = self.ast_at(name);
+        let (resolution, resolution_changed) = self.resolution_at(name);
+        let executions = &mut self.executions;
+        let file = self.files.get_mut(name).unwrap();
+        let inputs = ast_changed.max(resolution_changed);
+        file.types.get(revision, inputs, || {
+            Self::count(executions, Query::Types);
+            check(&ast.funcs.clone().unwrap_or_default(), &resolution)
+        })
+    }
+
+    ///
+    /// the problems in a source that would stop it from loading, or the warnings
+    /// about it if there are none, in the order an [Engine](crate::Engine) finds them;
+    /// type errors are not among them
+    ///
+    pub fn diagnostics(&mut self, name: &str) -> Arc<Vec<Diagnostic>> {
+        let revision = self.revision;
+        let (lexed, lexed_changed) = self.tokens_at(name);
+        let (ast, ast_changed) = self.ast_at(name);
+        let (resolution, resolution_changed) = self.resolution_at(name);
+        let executions = &mut self.executions;
+        let attrs = &self.attrs;
+        let file = self.files.get_mut(name).unwrap();
+        let inputs = lexed_changed.max(ast_changed).max(resolution_changed);
+        file.diagnostics
+            .get(revision, inputs, || {
+                Self::count(executions, Query::Diagnostics);
+                let errors = |errs: Vec<interface::Error>| {
+                    errs.into_iter()
+                        .map(|e| Diagnostic::from_error(name, e))
+                        .collect()
+                };
+                if !lexed.errs.is_empty() {
+                    return from_simple(name, &lexed.errs);
+                }
+                if !ast.errs.is_empty() {
+                    return from_simple(name, &ast.errs);
+                }
+                let funcs = ast.funcs.clone().unwrap_or_default();
+                let attrs = attrs.check(&funcs);
+                if !attrs.errors.is_empty() {
+                    return errors(attrs.errors);
+                }
+                if !resolution.errors.is_empty() {
+                    return errors(resolution.errors.clone());
+                }
+                let impure = check_memos(&funcs, &resolution);
+                if !impure.is_empty() {
+                    return errors(impure);
+                }
+                errors(attrs.warnings)
+                    .into_iter()
+                    .map(|warning: Diagnostic| Diagnostic {
+                        severity: Severity::Warning,
+                        ..warning
+                    })
+                    .collect()
+            })
+            .0
+    }
+
+    ///
+    /// lint warnings about a source, and errors for the rules that are denied;
+    /// only meaningful once it has no other errors
+    ///
+    pub fn lints(&mut self, name: &str) -> Arc<Vec<Diagnostic>> {
+        let revision = self.revision;
+        let (ast, ast_changed) = self.ast_at(name);
+        let (resolution, resolution_changed) = self.resolution_at(name);
+        let executions = &mut self.executions;
+        let config = &self.lint_config;
+        let file = self.files.get_mut(name).unwrap();
+        let inputs = ast_changed
+            .max(resolution_changed)
+            .max(self.lint_config_changed_at);
+        file.lints
+            .get(revision, inputs, || {
+                Self::count(executions, Query::Lints);
+                let funcs = ast.funcs.clone().unwrap_or_default();
+                lint(&funcs, &resolution, config)
+                    .iter()
+                    .map(|lint| to_diagnostic(name, lint))
+                    .collect()
+            })
+            .0
+    }
+}
+
+/// length of an element of a tree in chars
+fn char_len(el: &SyntaxElement) -> usize {
+    match el {
+        NodeOrToken::Node(node) => {
+            let mut len = 0;
+            node.text()
+                .for_each_chunk(|chunk| len += chunk.chars().count());
+            len
+        }
+        NodeOrToken::Token(tok) => tok.text().chars().count(),
+    }
+}
+
+///
+/// parse the items of a tree one at a time from their tokens, reusing what an item
+/// parsed to when it is the same as before, wherever it is now
+///
+fn parse_items(
+    tree: &GreenNode,
+    cache: &mut HashMap<GreenNode, Item>,
+    executions: &mut HashMap<Query, usize>,
+) -> Ast {
+    let mut items = HashMap::new();
+    let mut funcs = Vec::new();
+    let mut errs = Vec::new();
+    let mut offset = 0;
+    for el in SyntaxNode::new_root(tree.clone()).children_with_tokens() {
+        let end = offset + char_len(&el);
+        // everything else at the root is trivia
+        if let Some(node) = el.as_node() {
+            let key = node.green().into_owned();
+            match cache.remove(&key) {
+                Some(item) => {
+                    funcs.extend(
+                        item.iter()
+                            .map(|(name, func)| (name.clone(), shift_func(func, offset as isize))),
+                    );
+                    items.insert(key, item);
+                }
+                None => {
+                    Database::count(executions, Query::Item);
+                    let (tokens, clean) = lexed_tokens(node, offset);
+                    let (parsed, item_errs) = parse_tokens(&tokens, end);
+                    let parsed = parsed.into_iter().flatten().collect::<Vec<_>>();
+                    // errors keep the spans they were found at, so only items without them are kept
+                    if clean && item_errs.is_empty() {
+                        let item = parsed
+                            .iter()
+                            .map(|(name, func)| {
+                                (name.clone(), shift_func(func, -(offset as isize)))
+                            })
+                            .collect();
+                        items.insert(key, item);
+                    }
+                    funcs.extend(parsed);
+                    errs.extend(item_errs);
+                }
+            }
+        }
+        offset = end;
+    }
+    // items that are gone are forgotten
+    *cache = items;
+    let funcs = match collect_funcs(funcs) {
+        Ok(funcs) => Some(funcs),
+        Err(e) => {
+            errs.push(e);
+            None
+        }
+    };
+    Ast { funcs, errs }
+}
+
+/// a span moved by `by` chars
+fn shift(span: &Span, by: isize) -> Span {
+    span.start.wrapping_add_signed(by)..span.end.wrapping_add_signed(by)
+}
+
+fn shift_ty(ty: &Option<Spanned<Type>>, by: isize) -> Option<Spanned<Type>> {
+    ty.as_ref().map(|(ty, span)| (ty.clone(), shift(span, by)))
+}
+
+/// a function with its spans moved by `by` chars
+fn shift_func(func: &Func, by: isize) -> Func {
+    Func {
+        args: func.args.clone(),
+        body: shift_expr(&func.body, by),
+        name_span: shift(&func.name_span, by),
+        args_span: shift(&func.args_span, by),
+        arg_spans: func.arg_spans.iter().map(|span| shift(span, by)).collect(),
+        arg_types: func.arg_types.iter().map(|ty| shift_ty(ty, by)).collect(),
+        ret_type: shift_ty(&func.ret_type, by),
+        memo: func.memo,
+        attrs: func
+            .attrs
+            .iter()
+            .map(|attr| Attr {
+                name: (attr.name.0.clone(), shift(&attr.name.1, by)),
+                args: attr
+                    .args
+                    .iter()
+                    .map(|(tok, span)| (tok.clone(), shift(span, by)))
+                    .collect(),
+                span: shift(&attr.span, by),
+            })
+            .collect(),
+    }
+}
+
+fn shift_expr(expr: &Spanned<Expr>, by: isize) -> Spanned<Expr> {
+    let b = |e: &Spanned<Expr>| Box::new(shift_expr(e, by));
+    let e = match &expr.0 {
+        Expr::Var((name, span), ty, val, body) => Expr::Var(
+            (name.clone(), shift(span, by)),
+            shift_ty(ty, by),
+            b(val),
+            b(body),
+        ),
+        Expr::Then(x, y) => Expr::Then(b(x), b(y)),
+        Expr::Binary(x, op, y) => Expr::Binary(b(x), *op, b(y)),
+        Expr::Call(func, (args, span)) => Expr::Call(
+            b(func),
+            (
+                args.iter().map(|arg| shift_expr(arg, by)).collect(),
+                shift(span, by),
+            ),
+        ),
+        Expr::Return(x) => Expr::Return(b(x)),
+        Expr::If(cond, x, y) => Expr::If(b(cond), b(x), b(y)),
+        Expr::Error | Expr::Value(_) | Expr::Local(_) => expr.0.clone(),
+    };
+    (e, shift(&expr.1, by))
+}
+
+fn from_simple<T: Display + Hash + Eq + Clone>(name: &str, errs: &[Simple<T>]) -> Vec<Diagnostic> {
+    errs.iter()
+        .map(|e| Diagnostic::from_simple(name, e.clone()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use analysis::lint::{Level, LintConfig, Rule};
+    use parser::incremental::Edit;
+
+    use super::{Database, Query};
+
+    #[test]
+    fn computes_queries_again_only_when_inputs_change() {
+        let mut db = Database::new();
+        let counts = |db: &Database| {
+            [
+                Query::Tokens,
+                Query::Ast,
+                Query::Resolution,
+                Query::Types,
+                Query::Diagnostics,
+            ]
+            .map(|query| db.executions(query))
+        };
+        db.set_source("a.ipu", "fn inc(n) { n + 1 }\nfn main() { inc(1) }");
+        assert!(db.diagnostics("a.ipu").is_empty());
+        assert_eq!(db.types("a.ipu").funcs["inc"].to_string(), "fn(int) -> int");
+        assert_eq!(counts(&db), [1, 1, 1, 1, 1]);
+
+        // nothing changed
+        db.set_source("a.ipu", "fn inc(n) { n + 1 }\nfn main() { inc(1) }");
+        db.diagnostics("a.ipu");
+        db.types("a.ipu");
+        assert_eq!(counts(&db), [1, 1, 1, 1, 1]);
+
+        // a comment changes the tokens but not the tree, so nothing after it is computed again
+        db.set_source(
+            "a.ipu",
+            "fn inc(n) { n + 1 }\nfn main() { inc(1) } // one more",
+        );
+        db.types("a.ipu");
+        assert_eq!(counts(&db), [2, 2, 1, 1, 1]);
+        // diagnostics include those of the lexer
+        db.diagnostics("a.ipu");
+        assert_eq!(counts(&db), [2, 2, 1, 1, 2]);
+
+        db.set_source(
+            "a.ipu",
+            "fn inc(n) { n + 1 }\nfn main() { inc(x) } // one more",
+        );
+        assert_eq!(
+            db.diagnostics("a.ipu")[0].location(),
+            Some(("a.ipu", &(36..37)))
+        );
+        assert_eq!(counts(&db), [3, 3, 2, 1, 3]);
+        // types are only computed once asked for
+        db.types("a.ipu");
+        assert_eq!(counts(&db), [3, 3, 2, 2, 3]);
+    }
+
+    #[test]
+    fn parses_only_the_items_an_edit_changes() {
+        let src = "fn inc(n) { n + 1 }\n// süm\nfn add(a, b) { a + b }\nfn main() { inc(1) }\n";
+        // spans are in chars, and there is a wider one in the comment
+        let at = |s: &str| src[..src.find(s).unwrap()].chars().count();
+        let mut db = Database::new();
+        db.set_source("a.ipu", src);
+        assert!(db.diagnostics("a.ipu").is_empty());
+        assert_eq!(db.executions(Query::Item), 3);
+
+        let x = at("1)");
+        db.edit_source("a.ipu", &Edit::new(x..x + 1, "x"));
+        assert_eq!(
+            db.diagnostics("a.ipu")[0].location(),
+            Some(("a.ipu", &(x..x + 1)))
+        );
+        // only `main` is parsed again
+        assert_eq!(db.executions(Query::Item), 4);
+
+        db.edit_source("a.ipu", &Edit::new(at(" + b")..at(" }\nfn main"), ""));
+        assert_eq!(
+            db.source("a.ipu").as_deref(),
+            Some("fn inc(n) { n + 1 }\n// süm\nfn add(a, b) { a }\nfn main() { inc(x) }\n")
+        );
+        assert_eq!(
+            db.diagnostics("a.ipu")[0].location(),
+            Some(("a.ipu", &(x - 4..x - 3)))
+        );
+        // `main` moved, but it is the same, so what it parsed to is moved with it
+        assert_eq!(db.executions(Query::Item), 5);
+
+        // an item with a syntax error is parsed again wherever it moves, for its errors
+        let plus = at("a + b") + 1;
+        db.edit_source("a.ipu", &Edit::new(plus..plus, " +"));
+        let err = plus + 3;
+        assert_eq!(
+            db.diagnostics("a.ipu")[0].location(),
+            Some(("a.ipu", &(err..err + 1)))
+        );
+        db.edit_source("a.ipu", &Edit::new(0..0, "\n"));
+        assert_eq!(
+            db.diagnostics("a.ipu")[0].location(),
+            Some(("a.ipu", &(err + 1..err + 2)))
+        );
+        assert_eq!(db.executions(Query::Item), 7);
+
+        // the same as setting the whole source
+        let mut fresh = Database::new();
+        fresh.set_source("a.ipu", &db.source("a.ipu").unwrap());
+        assert_eq!(fresh.ast("a.ipu"), db.ast("a.ipu"));
+        assert_eq!(fresh.diagnostics("a.ipu"), db.diagnostics("a.ipu"));
+    }
+
+    #[test]
+    fn lints_with_the_config() {
+        let mut db = Database::new();
+        db.set_source("a.ipu", "fn main() { var x = 1; 2 }");
+        assert_eq!(db.lints("a.ipu")[0].message, "Unused variable 'x'");
+        db.set_lint_config(LintConfig::default());
+        db.lints("a.ipu");
+        assert_eq!(db.executions(Query::Lints), 1);
+        let mut config = LintConfig::default();
+        config.set(Rule::UnusedVariable, Level::Allow);
+        db.set_lint_config(config);
+        assert!(db.lints("a.ipu").is_empty());
+        assert_eq!(db.executions(Query::Ast), 1);
+    }
+
+    #[test]
+    fn keeps_sources_apart() {
+        let mut db = Database::new();
+        db.set_source("a.ipu", "fn main() { 1 }");
+        db.set_source("b.ipu", "#[unknown]\nfn main() { 2 }");
+        assert!(db.diagnostics("a.ipu").is_empty());
+        let warnings = db.diagnostics("b.ipu");
+        assert_eq!(warnings[0].location().unwrap().0, "b.ipu");
+        db.set_source("b.ipu", "fn main() {");
+        db.diagnostics("a.ipu");
+        assert_eq!(db.executions(Query::Diagnostics), 2);
+        assert!(!db.diagnostics("b.ipu").is_empty());
+    }
+}
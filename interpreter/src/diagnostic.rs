@@ -0,0 +1,179 @@
+use chumsky::{error::SimpleReason, prelude::Simple};
+use interface::{Error, Frame, Span};
+use std::{fmt::Display, hash::Hash};
+
+/// number of calls listed in the stack trace note of a runtime error
+const MAX_TRACE_NOTE: usize = 10;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// an annotated location in a source
+#[derive(Clone, Debug, PartialEq)]
+pub struct Label {
+    /// name of the source the span is in
+    pub source: String,
+    /// span relative to the start of that source
+    pub span: Span,
+    pub message: String,
+}
+
+/// an error or warning, located in the sources it was found in
+#[derive(Clone, Debug, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    /// annotated locations, the first of which is the primary one;
+    /// empty for problems that are not about any particular source
+    pub labels: Vec<Label>,
+    pub notes: Vec<String>,
+}
+
+impl Diagnostic {
+    /// an error that has no location
+    pub fn error(message: impl Into<String>) -> Self {
+        Self {
+            severity: Severity::Error,
+            message: message.into(),
+            labels: Vec::new(),
+            notes: Vec::new(),
+        }
+    }
+
+    pub fn with_label(mut self, source: &str, span: Span, message: impl Into<String>) -> Self {
+        self.labels.push(Label {
+            source: source.to_string(),
+            span,
+            message: message.into(),
+        });
+        self
+    }
+
+    pub fn with_note(mut self, note: impl Into<String>) -> Self {
+        self.notes.push(note.into());
+        self
+    }
+
+    /// the primary location
+    pub fn location(&self) -> Option<(&str, &Span)> {
+        self.labels
+            .first()
+            .map(|label| (label.source.as_str(), &label.span))
+    }
+
+    ///
+    /// a lexer or parser error in `source`
+    ///
+    pub fn from_simple<T: Display + Hash + Eq>(source: &str, e: Simple<T>) -> Self {
+        let found = e
+            .found()
+            .map(|found| found.to_string())
+            .unwrap_or_else(|| "end of file".to_string());
+        match e.reason() {
+            SimpleReason::Unclosed { span, delimiter } => {
+                Self::error(format!("Unclosed delimiter {}", delimiter))
+                    .with_label(
+                        source,
+                        e.span(),
+                        format!("Must be closed before this {}", found),
+                    )
+                    .with_label(
+                        source,
+                        span.clone(),
+                        format!("Unclosed delimiter {}", delimiter),
+                    )
+            }
+            SimpleReason::Unexpected => {
+                let mut expected = e
+                    .expected()
+                    .map(|expected| match expected {
+                        Some(expected) => expected.to_string(),
+                        None => "end of input".to_string(),
+                    })
+                    .collect::<Vec<_>>();
+                // the expected set is unordered
+                expected.sort();
+                Self::error(format!(
+                    "{}, expected {}",
+                    if e.found().is_some() {
+                        "Unexpected token in input"
+                    } else {
+                        "Unexpected end of input"
+                    },
+                    if expected.is_empty() {
+                        "something else".to_string()
+                    } else {
+                        expected.join(", ")
+                    }
+                ))
+                .with_label(
+                    source,
+                    e.span(),
+                    format!("Unexpected token {}", found),
+                )
+            }
+            SimpleReason::Custom(msg) => {
+                Self::error(msg.clone()).with_label(source, e.span(), msg.clone())
+            }
+        }
+    }
+
+    ///
+    /// a runtime error in `source`
+    ///
+    pub fn from_error(source: &str, e: Error) -> Self {
+        // recursive calls from the same call site share a label
+        let mut sites: Vec<(&Frame, usize)> = Vec::new();
+        for frame in &e.trace {
+            match sites
+                .iter_mut()
+                .find(|(f, _)| f.call_span == frame.call_span)
+            {
+                Some((_, n)) => *n += 1,
+                None => sites.push((frame, 1)),
+            }
+        }
+        let mut diag = Self::error(e.msg.clone()).with_label(source, e.span.clone(), &e.msg);
+        for (frame, n) in sites {
+            let msg = match n {
+                1 => format!("in call to {}", frame),
+                2 => format!("in call to {} (and 1 more call here)", frame),
+                n => format!("in call to {} (and {} more calls here)", frame, n - 1),
+            };
+            diag = diag.with_label(source, frame.call_span.clone(), msg);
+        }
+        if e.trace.is_empty() {
+            return diag;
+        }
+        let mut calls = e
+            .trace
+            .iter()
+            .take(MAX_TRACE_NOTE)
+            .map(|frame| frame.to_string())
+            .collect::<Vec<_>>();
+        if e.trace.len() > MAX_TRACE_NOTE {
+            calls.push(format!("{} more", e.trace.len() - MAX_TRACE_NOTE));
+        }
+        diag.with_note(format!(
+            "stack trace, innermost first: {}",
+            calls.join(" <- ")
+        ))
+    }
+}
+
+/// a lexer or parser error whose labels are not in any source until localized
+impl<T: Display + Hash + Eq> From<Simple<T>> for Diagnostic {
+    fn from(e: Simple<T>) -> Self {
+        Self::from_simple("", e)
+    }
+}
+
+/// a runtime error whose label is not in any source until localized
+impl From<Error> for Diagnostic {
+    fn from(e: Error) -> Self {
+        Self::from_error("", e)
+    }
+}
@@ -0,0 +1,413 @@
+//!
+//! API documentation of a source from the `///` comments before its functions,
+//! as Markdown or HTML, and doctests from the code blocks in them
+//!
+
+use analysis::resolver::DefKind;
+use interface::tokens::Token;
+use parser::printer::print_signature;
+use std::collections::HashMap;
+
+use crate::{
+    db::Database,
+    diagnostic::{Diagnostic, Severity},
+    engine::Engine,
+    test_runner::{TestOutcome, TestReport},
+};
+
+/// a documented function
+#[derive(Clone, Debug, PartialEq)]
+pub struct DocItem {
+    pub name: String,
+    /// attributes and head of the function, as it would be printed
+    pub signature: String,
+    /// the inferred type
+    pub ty: Option<String>,
+    /// lines of the doc comment, without the `///`
+    pub docs: Vec<String>,
+    /// line of the function name, counting from 1
+    pub line: usize,
+    /// other functions of the source it calls, in the order they are first called
+    pub calls: Vec<String>,
+}
+
+impl DocItem {
+    ///
+    /// the code blocks of the doc comment that are meant to be run,
+    /// which are those without a language or marked `ipu`
+    ///
+    pub fn examples(&self) -> Vec<String> {
+        blocks(&self.docs)
+            .into_iter()
+            .filter_map(|block| match block {
+                Block::Code(lang, code) if lang.is_empty() || lang == "ipu" => Some(code),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+///
+/// doc comments of the functions of a source, by function name; a `///` comment
+/// at the top level belongs to the next function, unless a function ends in between
+///
+fn doc_comments(tokens: &[(Token, interface::Span)]) -> HashMap<String, Vec<String>> {
+    let mut docs = HashMap::new();
+    let mut pending = Vec::new();
+    let mut depth = 0usize;
+    let mut after_fn = false;
+    for (tok, _) in tokens {
+        match tok {
+            Token::Comment(text) if depth == 0 && text.starts_with("///") => {
+                let line = &text[3..];
+                pending.push(line.strip_prefix(' ').unwrap_or(line).to_string());
+            }
+            Token::Fn if depth == 0 => after_fn = true,
+            Token::Ident(name) if after_fn => {
+                docs.insert(name.clone(), std::mem::take(&mut pending));
+                after_fn = false;
+            }
+            Token::Ctrl('{') => depth += 1,
+            Token::Ctrl('}') => {
+                depth = depth.saturating_sub(1);
+                if depth == 0 {
+                    pending.clear();
+                }
+            }
+            _ => {}
+        }
+    }
+    docs
+}
+
+///
+/// the functions of a source other than tests, in source order,
+/// unless it has errors that stop it from loading
+///
+pub fn doc_items(db: &mut Database, file: &str) -> Result<Vec<DocItem>, Vec<Diagnostic>> {
+    let errs = db
+        .diagnostics(file)
+        .iter()
+        .filter(|diag| diag.severity == Severity::Error)
+        .cloned()
+        .collect::<Vec<_>>();
+    if !errs.is_empty() {
+        return Err(errs);
+    }
+    let src = db.source(file).unwrap();
+    let lexed = db.tokens(file);
+    let ast = db.ast(file);
+    let resolution = db.resolution(file);
+    let types = db.types(file);
+    let mut docs = doc_comments(lexed.tokens.as_deref().unwrap_or_default());
+
+    let funcs = ast.funcs.as_ref().unwrap();
+    let mut funcs = funcs
+        .iter()
+        .filter(|(_, func)| !func.attrs.iter().any(|attr| attr.name.0 == "test"))
+        .collect::<Vec<_>>();
+    funcs.sort_by_key(|(_, func)| func.name_span.start);
+    let items = funcs
+        .into_iter()
+        .map(|(name, func)| {
+            let mut uses = resolution
+                .uses
+                .iter()
+                .filter(|(span, _)| func.body.1.start <= span.start && span.end <= func.body.1.end)
+                .filter(|(_, &def)| resolution.defs[def].kind == DefKind::Func)
+                .map(|(span, &def)| (span.start, &resolution.defs[def].name))
+                .collect::<Vec<_>>();
+            uses.sort();
+            let mut calls: Vec<String> = Vec::new();
+            for (_, callee) in uses {
+                if callee != name && !calls.contains(callee) {
+                    calls.push(callee.clone());
+                }
+            }
+            DocItem {
+                name: name.clone(),
+                signature: print_signature(name, func),
+                ty: types.funcs.get(name).map(ToString::to_string),
+                docs: docs.remove(name).unwrap_or_default(),
+                line: src
+                    .chars()
+                    .take(func.name_span.start)
+                    .filter(|c| *c == '\n')
+                    .count()
+                    + 1,
+                calls,
+            }
+        })
+        .collect();
+    Ok(items)
+}
+
+/// a paragraph or a fenced code block of a doc comment
+#[derive(Debug, PartialEq)]
+enum Block {
+    Text(String),
+    /// the language after the opening fence, and the code
+    Code(String, String),
+}
+
+fn blocks(lines: &[String]) -> Vec<Block> {
+    let mut blocks = Vec::new();
+    let mut text = Vec::new();
+    let mut code: Option<(String, Vec<&str>)> = None;
+    for line in lines {
+        let fence = line.trim_start().strip_prefix("```");
+        match (&mut code, fence) {
+            (Some((lang, lines)), Some(_)) => {
+                blocks.push(Block::Code(lang.clone(), lines.join("\n")));
+                code = None;
+            }
+            (Some((_, lines)), None) => lines.push(line),
+            (None, Some(lang)) => {
+                if !text.is_empty() {
+                    blocks.push(Block::Text(text.join("\n")));
+                    text.clear();
+                }
+                code = Some((lang.trim().to_string(), Vec::new()));
+            }
+            (None, None) if line.trim().is_empty() => {
+                if !text.is_empty() {
+                    blocks.push(Block::Text(text.join("\n")));
+                    text.clear();
+                }
+            }
+            (None, None) => text.push(line.as_str()),
+        }
+    }
+    // an unclosed block runs to the end of the comment
+    if let Some((lang, lines)) = code {
+        blocks.push(Block::Code(lang, lines.join("\n")));
+    }
+    if !text.is_empty() {
+        blocks.push(Block::Text(text.join("\n")));
+    }
+    blocks
+}
+
+///
+/// split text at inline code spans, calling `text` on the text between them
+/// and `code` on their contents
+///
+fn inline(s: &str, text: impl Fn(&str) -> String, code: impl Fn(&str) -> String) -> String {
+    let mut out = String::new();
+    let mut parts = s.split('`');
+    while let Some(part) = parts.next() {
+        out += &text(part);
+        match parts.next() {
+            Some(inner) => out += &code(inner),
+            None => break,
+        }
+    }
+    out
+}
+
+/// the item an inline code span such as `inc` or `inc()` names, if any
+fn linked<'a>(code: &'a str, items: &[DocItem]) -> Option<&'a str> {
+    let name = code.strip_suffix("()").unwrap_or(code);
+    items.iter().any(|item| item.name == name).then_some(name)
+}
+
+///
+/// Markdown documentation of the items of `file`
+///
+pub fn to_markdown(file: &str, items: &[DocItem]) -> String {
+    let mut out = format!("# {}\n\n", file);
+    for item in items {
+        out += &format!("- [`{}`](#{})\n", item.name, item.name);
+    }
+    for item in items {
+        out += &format!("\n## `{}`\n\n```\n{}\n```\n", item.name, item.signature);
+        if let Some(ty) = &item.ty {
+            out += &format!("\nType: `{}`\n", ty);
+        }
+        for block in blocks(&item.docs) {
+            out += &match block {
+                Block::Text(text) => {
+                    let text = inline(&text, str::to_string, |code| match linked(code, items) {
+                        Some(name) => format!("[`{}`](#{})", code, name),
+                        None => format!("`{}`", code),
+                    });
+                    format!("\n{}\n", text)
+                }
+                Block::Code(lang, code) => format!("\n```{}\n{}\n```\n", lang, code),
+            };
+        }
+        if !item.calls.is_empty() {
+            let calls = item
+                .calls
+                .iter()
+                .map(|name| format!("[`{}`](#{})", name, name))
+                .collect::<Vec<_>>();
+            out += &format!("\nCalls: {}\n", calls.join(", "));
+        }
+        out += &format!("\n[Source]({}#L{})\n", file, item.line);
+    }
+    out
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+///
+/// a standalone HTML page documenting the items of `file`
+///
+pub fn to_html(file: &str, items: &[DocItem]) -> String {
+    let link = |name: &str, text: &str| format!("<a href=\"#{}\"><code>{}</code></a>", name, text);
+    let mut out = format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n</head>\n<body>\n<h1>{}</h1>\n<ul>\n",
+        escape(file),
+        escape(file)
+    );
+    for item in items {
+        out += &format!("<li>{}</li>\n", link(&item.name, &item.name));
+    }
+    out += "</ul>\n";
+    for item in items {
+        out += &format!(
+            "<section id=\"{}\">\n<h2><code>{}</code></h2>\n<pre><code>{}</code></pre>\n",
+            item.name,
+            item.name,
+            escape(&item.signature)
+        );
+        if let Some(ty) = &item.ty {
+            out += &format!("<p>Type: <code>{}</code></p>\n", escape(ty));
+        }
+        for block in blocks(&item.docs) {
+            out += &match block {
+                Block::Text(text) => {
+                    let text = inline(&text, escape, |code| match linked(code, items) {
+                        Some(name) => link(name, &escape(code)),
+                        None => format!("<code>{}</code>", escape(code)),
+                    });
+                    format!("<p>{}</p>\n", text)
+                }
+                Block::Code(_, code) => format!("<pre><code>{}</code></pre>\n", escape(&code)),
+            };
+        }
+        if !item.calls.is_empty() {
+            let calls = item
+                .calls
+                .iter()
+                .map(|name| link(name, name))
+                .collect::<Vec<_>>();
+            out += &format!("<p>Calls: {}</p>\n", calls.join(", "));
+        }
+        out += &format!(
+            "<p><a href=\"{}#L{}\">Source</a></p>\n</section>\n",
+            escape(file),
+            item.line
+        );
+    }
+    out + "</body>\n</html>\n"
+}
+
+/// the source name of an example, which its diagnostics point into
+pub fn example_name(item: &str, index: usize) -> String {
+    format!("<doc of {} #{}>", item, index + 1)
+}
+
+///
+/// run each example of the items of a source in an engine of its own
+/// with the source loaded; an example passes unless evaluating it fails
+///
+pub fn run_doctests(
+    file: &str,
+    src: &str,
+    items: &[DocItem],
+    mut on_outcome: impl FnMut(&TestOutcome),
+) -> TestReport {
+    let mut report = TestReport::default();
+    for item in items {
+        for (i, code) in item.examples().iter().enumerate() {
+            let mut engine = Engine::new();
+            let result = engine
+                .load_source(file, src)
+                .and_then(|()| engine.enter(&example_name(&item.name, i), code))
+                .map(|_| ())
+                .map_err(|errs| errs.into_iter().next().unwrap());
+            let outcome = TestOutcome {
+                name: format!("{} (example {})", item.name, i + 1),
+                result,
+            };
+            on_outcome(&outcome);
+            report.outcomes.push(outcome);
+        }
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{doc_items, run_doctests, to_html, to_markdown};
+    use crate::db::Database;
+
+    const SRC: &str = "/// adds one to `n`
+///
+/// ```
+/// assert_eq(inc(1), 2)
+/// ```
+fn inc(n: int) { n + 1 }
+
+// not a doc comment
+/// twice `inc`, see `inc()`
+#[deprecated(\"use add\")]
+fn inc2(n) {
+    /// not documenting anything
+    inc(inc(n))
+}
+
+/// ```
+/// assert_eq(inc(1), 3)
+/// ```
+#[test]
+fn t() { 1 }
+";
+
+    #[test]
+    fn documents_functions() {
+        let mut db = Database::new();
+        db.set_source("lib.ipu", SRC);
+        let items = doc_items(&mut db, "lib.ipu").unwrap();
+        assert_eq!(items.len(), 2);
+        assert_eq!(
+            items[0].docs,
+            ["adds one to `n`", "", "```", "assert_eq(inc(1), 2)", "```"]
+        );
+        assert_eq!(items[0].ty.as_deref(), Some("fn(int) -> int"));
+        assert_eq!(items[0].examples(), ["assert_eq(inc(1), 2)"]);
+        assert_eq!(items[1].signature, "#[deprecated(\"use add\")]\nfn inc2(n)");
+        assert_eq!(items[1].docs, ["twice `inc`, see `inc()`"]);
+        assert_eq!(
+            (items[1].line, items[1].calls.clone()),
+            (11, vec!["inc".to_string()])
+        );
+
+        let md = to_markdown("lib.ipu", &items);
+        assert!(md.contains("\nadds one to `n`\n"));
+        assert!(md.contains("see [`inc()`](#inc)"));
+        assert!(md.contains("Calls: [`inc`](#inc)"));
+        assert!(md.contains("[Source](lib.ipu#L11)"));
+        let html = to_html("lib.ipu", &items);
+        assert!(
+            html.contains("<pre><code>#[deprecated(&quot;use add&quot;)]\nfn inc2(n)</code></pre>")
+        );
+        assert!(html.contains("see <a href=\"#inc\"><code>inc()</code></a>"));
+
+        let report = run_doctests("lib.ipu", SRC, &items, |_| {});
+        assert_eq!((report.passed(), report.failed()), (1, 0));
+        let src = SRC.replace("inc(1), 2", "inc(1), 3");
+        db.set_source("lib.ipu", &src);
+        let items = doc_items(&mut db, "lib.ipu").unwrap();
+        let report = run_doctests("lib.ipu", &src, &items, |_| {});
+        let err = report.outcomes[0].result.as_ref().unwrap_err();
+        assert_eq!(err.location().unwrap().0, "<doc of inc #1>");
+    }
+}
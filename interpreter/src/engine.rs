@@ -0,0 +1,702 @@
+use analysis::{
+    attrs::AttrRegistry,
+    purity::check_memos,
+    resolver::{Resolution, Resolver},
+    types::{check, check_expr, Scheme, Types},
+};
+use chumsky::Stream;
+use interface::{
+    nodes::{expr::Expr, func::Func, types::Type, value::Value},
+    tokens::Token,
+    Span, Spanned,
+};
+use parser::{
+    lexer::lex,
+    parsers::{parse, parse_expr, parse_repl, repl::ReplEntry},
+};
+use std::{cell::RefCell, collections::HashMap, rc::Rc, vec::IntoIter};
+
+use crate::{
+    builtins::Builtins,
+    diagnostic::{Diagnostic, Severity},
+    interpreter::Interpreter,
+    limits::Limits,
+    optimizer::{optimize, optimize_expr, Inlined, Optimized},
+    output::{Output, StdOutput},
+    source_map::SourceMap,
+    vm::{
+        bytecode::Program,
+        compiler::{compile, compile_expr},
+        Vm,
+    },
+};
+
+/// how scripts are executed
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Backend {
+    /// walk the AST
+    #[default]
+    Tree,
+    /// compile to bytecode and run it on a stack machine
+    Vm,
+}
+
+type TokenStream = Stream<'static, Token, Span, IntoIter<Spanned<Token>>>;
+
+///
+/// an interpreter for embedding scripts in a host program
+///
+/// ```
+/// use interpreter::{Engine, Value};
+///
+/// let mut engine = Engine::new();
+/// engine.load_source("fib.ipu", "fn fib(n) { if n < 2 { n } else { fib(n - 1) + fib(n - 2) } }").unwrap();
+/// assert_eq!(engine.call("fib", &[Value::I32(10)]), Ok(Value::I32(55)));
+/// assert_eq!(engine.eval_expr_str("fib(5) + 1"), Ok(Value::I32(6)));
+/// ```
+///
+pub struct Engine {
+    sources: SourceMap,
+    funcs: HashMap<String, Func>,
+    /// top-level bindings visible to expressions evaluated by the engine
+    globals: Vec<(String, Value)>,
+    builtins: Builtins,
+    attrs: AttrRegistry,
+    /// warnings about the functions loaded since they were last taken
+    warnings: Vec<Diagnostic>,
+    limits: Limits,
+    backend: Backend,
+    /// whether to run optimised functions
+    optimize: bool,
+    /// `funcs` after optimisation, until they change
+    optimized: Option<Optimized>,
+    /// the functions to run, compiled for the VM, until they change
+    program: Option<Program>,
+    /// the names in the functions to run resolved to their slots, until they change
+    resolution: Option<Resolution>,
+}
+
+impl Default for Engine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Engine {
+    /// an engine whose print functions write to stdout and stderr
+    pub fn new() -> Self {
+        Self::with_output(Rc::new(RefCell::new(StdOutput)))
+    }
+
+    /// an engine whose print functions write to `output`
+    pub fn with_output(output: Rc<RefCell<dyn Output>>) -> Self {
+        Self {
+            sources: SourceMap::default(),
+            funcs: HashMap::new(),
+            globals: Vec::new(),
+            builtins: Builtins::with_std(output),
+            attrs: AttrRegistry::with_std(),
+            warnings: Vec::new(),
+            limits: Limits::default(),
+            backend: Backend::default(),
+            optimize: false,
+            optimized: None,
+            program: None,
+            resolution: None,
+        }
+    }
+
+    /// native functions, for registering more of them
+    pub fn builtins_mut(&mut self) -> &mut Builtins {
+        &mut self.builtins
+    }
+
+    /// attributes functions may have, for registering more of them
+    pub fn attrs_mut(&mut self) -> &mut AttrRegistry {
+        &mut self.attrs
+    }
+
+    /// warnings about the functions loaded so far, such as unknown attributes
+    pub fn take_warnings(&mut self) -> Vec<Diagnostic> {
+        std::mem::take(&mut self.warnings)
+    }
+
+    /// limit the resources used by each call into the engine
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.limits = limits;
+    }
+
+    pub fn set_backend(&mut self, backend: Backend) {
+        self.backend = backend;
+    }
+
+    /// run optimised functions and expressions, see [Engine::optimized]
+    pub fn set_optimize(&mut self, optimize: bool) {
+        self.optimize = optimize;
+        self.program = None;
+        self.resolution = None;
+    }
+
+    /// every source loaded so far, for rendering diagnostics
+    pub fn sources(&self) -> &SourceMap {
+        &self.sources
+    }
+
+    pub fn funcs(&self) -> &HashMap<String, Func> {
+        &self.funcs
+    }
+
+    /// bind a name for expressions evaluated later, shadowing earlier bindings
+    pub fn set_global(&mut self, name: impl Into<String>, val: Value) {
+        self.globals.push((name.into(), val));
+    }
+
+    ///
+    /// lex a source, adding it to the source map;
+    /// the tokens' spans point into the shared span space
+    ///
+    fn tokens(&mut self, name: &str, src: &str) -> Result<TokenStream, Vec<Diagnostic>> {
+        let offset = self.sources.add(name, src);
+        let (tokens, lex_errs) = lex(src);
+        if !lex_errs.is_empty() {
+            // lexer spans are still relative to the source
+            return Err(lex_errs
+                .into_iter()
+                .map(|e| Diagnostic::from_simple(name, e))
+                .collect());
+        }
+        let tokens = tokens
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|t| !matches!(t.0, Token::Comment(_)))
+            .map(|(tok, span)| (tok, span.start + offset..span.end + offset))
+            .collect::<Vec<_>>();
+        let end = offset + src.chars().count();
+        Ok(Stream::from_iter(end..end + 1, tokens.into_iter()))
+    }
+
+    /// diagnostics for errors whose spans point into the shared span space
+    fn localize<E: Into<Diagnostic>>(&self, errs: impl IntoIterator<Item = E>) -> Vec<Diagnostic> {
+        errs.into_iter()
+            .map(|e| self.sources.localize(e.into()))
+            .collect()
+    }
+
+    ///
+    /// load the functions of a source file, replacing functions of the same name
+    ///
+    pub fn load_source(&mut self, name: &str, src: &str) -> Result<(), Vec<Diagnostic>> {
+        let (funcs, parse_errs) = parse(self.tokens(name, src)?);
+        if !parse_errs.is_empty() {
+            return Err(self.localize(parse_errs));
+        }
+        self.define(funcs.unwrap_or_default())
+    }
+
+    /// a resolver that knows the loaded functions and the builtins
+    fn resolver(&self) -> Resolver {
+        let mut resolver = Resolver::new();
+        self.builtins
+            .names()
+            .for_each(|name| resolver.builtin(name));
+        resolver.funcs(&self.funcs);
+        resolver
+    }
+
+    ///
+    /// add functions, replacing those of the same name,
+    /// unless they use names that are not defined, misuse attributes
+    /// or make `memo` functions impure
+    ///
+    fn define(&mut self, funcs: HashMap<String, Func>) -> Result<(), Vec<Diagnostic>> {
+        let attrs = self.attrs.check(&funcs);
+        if !attrs.errors.is_empty() {
+            return Err(self.localize(attrs.errors));
+        }
+        let warnings = self.localize(attrs.warnings);
+        let mut all = self.funcs.clone();
+        all.extend(funcs);
+        let mut resolver = self.resolver();
+        resolver.funcs(&all);
+        let resolution = resolver.resolve_funcs(&all);
+        if !resolution.errors.is_empty() {
+            return Err(self.localize(resolution.errors));
+        }
+        let impure = check_memos(&all, &resolution);
+        if !impure.is_empty() {
+            return Err(self.localize(impure));
+        }
+        self.funcs = all;
+        self.warnings
+            .extend(warnings.into_iter().map(|warning| Diagnostic {
+                severity: Severity::Warning,
+                ..warning
+            }));
+        self.optimized = None;
+        self.program = None;
+        self.resolution = None;
+        Ok(())
+    }
+
+    ///
+    /// the loaded functions with constants folded, dead code removed and trivial functions inlined
+    ///
+    pub fn optimized(&mut self) -> &Optimized {
+        if self.optimized.is_none() {
+            let resolution = self.resolver().resolve_funcs(&self.funcs);
+            self.optimized = Some(optimize(&self.funcs, &resolution));
+        }
+        self.optimized.as_ref().unwrap()
+    }
+
+    /// optimise the functions if enabled, unless they have not changed since
+    fn prepare(&mut self) {
+        if self.optimize {
+            self.optimized();
+        }
+    }
+
+    /// the functions to run, once prepared
+    fn runnable(&self) -> &HashMap<String, Func> {
+        match &self.optimized {
+            Some(optimized) if self.optimize => &optimized.funcs,
+            _ => &self.funcs,
+        }
+    }
+
+    /// the bodies of the calls inlined in the functions to run, once prepared
+    fn inlined(&self) -> Option<&Inlined> {
+        self.optimized
+            .as_ref()
+            .filter(|_| self.optimize)
+            .map(|optimized| &optimized.inlined)
+    }
+
+    /// resolve the names in the functions to run, unless they have not changed since
+    fn resolve(&mut self) {
+        if self.resolution.is_none() {
+            self.prepare();
+            self.resolution = Some(self.resolver().resolve_funcs(self.runnable()));
+        }
+    }
+
+    /// compile the functions for the VM, unless they have not changed since
+    fn compile(&mut self) {
+        if self.program.is_none() {
+            self.resolve();
+            let resolution = self.resolution.as_ref().unwrap();
+            self.program = Some(compile(self.runnable(), resolution, self.inlined()));
+        }
+    }
+
+    ///
+    /// infer the types of the loaded functions, reporting type errors
+    ///
+    pub fn check_types(&self) -> Result<Types, Vec<Diagnostic>> {
+        let resolution = self.resolver().resolve_funcs(&self.funcs);
+        let mut types = check(&self.funcs, &resolution);
+        if !types.errors.is_empty() {
+            return Err(self.localize(std::mem::take(&mut types.errors)));
+        }
+        Ok(types)
+    }
+
+    ///
+    /// call a function with already evaluated arguments
+    ///
+    pub fn call(&mut self, name: &str, args: &[Value]) -> Result<Value, Diagnostic> {
+        match self.backend {
+            Backend::Tree => self.resolve(),
+            Backend::Vm => self.compile(),
+        }
+        let funcs = self.runnable();
+        let func = match funcs.get(name) {
+            Some(func) => func,
+            None => {
+                let builtin = self
+                    .builtins
+                    .get(name)
+                    .ok_or_else(|| Diagnostic::error(format!("No such function '{}'", name)))?;
+                if !builtin.arity.accepts(args.len()) {
+                    return Err(arity_error(name, builtin.arity, args.len()));
+                }
+                return (builtin.func)(args).map_err(|e| Diagnostic::error(e.msg));
+            }
+        };
+        if func.args.len() != args.len() {
+            return Err(arity_error(name, func.args.len(), args.len()));
+        }
+        let res = match self.backend {
+            Backend::Tree => {
+                Interpreter::new(funcs, self.resolution.as_ref().unwrap(), &self.builtins)
+                    .with_limits(self.limits)
+                    .with_inlined(self.inlined())
+                    .call_func(func, args.to_vec())
+            }
+            Backend::Vm => {
+                let program = self.program.as_ref().unwrap();
+                let (_, chunk) = program.get(name).unwrap();
+                Vm::new(program, &self.builtins)
+                    .with_limits(self.limits)
+                    .run(chunk, args.to_vec())
+            }
+        };
+        res.map_err(|e| self.sources.localize(e.into()))
+    }
+
+    /// evaluate an expression with the globals in scope
+    fn eval(&mut self, expr: &Spanned<Expr>) -> Result<Value, Vec<Diagnostic>> {
+        let names = self
+            .globals
+            .iter()
+            .map(|(name, _)| name.clone())
+            .collect::<Vec<_>>();
+        let mut resolution = self.resolver().resolve_expr(expr, &names);
+        if !resolution.errors.is_empty() {
+            return Err(self.localize(resolution.errors));
+        }
+        let optimized;
+        let (expr, expr_inlined) = if self.optimize {
+            optimized = optimize_expr(expr, &self.funcs, &resolution);
+            resolution = self.resolver().resolve_expr(&optimized.0, &names);
+            (&optimized.0, Some(&optimized.1))
+        } else {
+            (expr, None)
+        };
+        self.resolve();
+        let res = match self.backend {
+            Backend::Tree => {
+                // the expression calls into the functions, so both tables are consulted
+                let inlined = self.inlined().map(|inlined| {
+                    let mut inlined = inlined.clone();
+                    inlined.extend(
+                        expr_inlined
+                            .into_iter()
+                            .flatten()
+                            .map(|(span, body)| (span.clone(), body.clone())),
+                    );
+                    inlined
+                });
+                let globals = self.globals.iter().map(|(_, val)| val.clone()).collect();
+                let funcs_resolution = self.resolution.as_ref().unwrap();
+                Interpreter::new(self.runnable(), funcs_resolution, &self.builtins)
+                    .with_limits(self.limits)
+                    .with_inlined(inlined.as_ref())
+                    .eval_top(expr, &resolution, globals)
+            }
+            Backend::Vm => {
+                self.compile();
+                let program = self.program.as_ref().unwrap();
+                let vals = self.globals.iter().map(|(_, val)| val.clone()).collect();
+                let chunk = compile_expr(program, &resolution, expr_inlined, names.len(), expr);
+                Vm::new(program, &self.builtins)
+                    .with_limits(self.limits)
+                    .run(&chunk, vals)
+            }
+        };
+        res.map_err(|e| vec![self.sources.localize(e.into())])
+    }
+
+    ///
+    /// evaluate a single expression, such as `fib(10) + 1`
+    ///
+    pub fn eval_expr_str(&mut self, src: &str) -> Result<Value, Vec<Diagnostic>> {
+        let (expr, parse_errs) = parse_expr(self.tokens("<expr>", src)?);
+        match expr {
+            Some(expr) if parse_errs.is_empty() => self.eval(&expr),
+            _ => Err(self.localize(parse_errs)),
+        }
+    }
+
+    ///
+    /// parse a REPL entry, keeping its source around for the functions it defines
+    ///
+    pub fn parse_entry(&mut self, name: &str, src: &str) -> Result<ReplEntry, Vec<Diagnostic>> {
+        let (entry, parse_errs) = parse_repl(self.tokens(name, src)?);
+        match entry {
+            Some(entry) if parse_errs.is_empty() => Ok(entry),
+            _ => Err(self.localize(parse_errs)),
+        }
+    }
+
+    ///
+    /// handle a REPL entry: define functions, bind a global or evaluate an expression
+    ///
+    pub fn enter(&mut self, name: &str, src: &str) -> Result<Option<Value>, Vec<Diagnostic>> {
+        match self.parse_entry(name, src)? {
+            ReplEntry::Funcs(funcs) => {
+                self.define(funcs)?;
+                Ok(None)
+            }
+            ReplEntry::Binding(name, ty, val) => {
+                let val = self.eval(&binding_expr(name.clone(), ty, val))?;
+                self.set_global(name.0, val);
+                Ok(None)
+            }
+            ReplEntry::Expr(expr) => self.eval(&expr).map(Some),
+        }
+    }
+
+    ///
+    /// infer the type of the value of a REPL entry without evaluating it
+    ///
+    pub fn type_of(&mut self, name: &str, src: &str) -> Result<Scheme, Vec<Diagnostic>> {
+        let expr = match self.parse_entry(name, src)? {
+            ReplEntry::Funcs(_) => {
+                return Err(vec![Diagnostic::error(
+                    "Only expressions and bindings have a type".to_string(),
+                )])
+            }
+            ReplEntry::Binding(name, ty, val) => binding_expr(name, ty, val),
+            ReplEntry::Expr(expr) => expr,
+        };
+        let names = self
+            .globals
+            .iter()
+            .map(|(name, _)| name.clone())
+            .collect::<Vec<_>>();
+        let resolution = self.resolver().resolve_expr(&expr, &names);
+        if !resolution.errors.is_empty() {
+            return Err(self.localize(resolution.errors));
+        }
+        // type errors in the functions are not the entry's
+        let funcs = check(&self.funcs, &self.resolver().resolve_funcs(&self.funcs)).funcs;
+        let globals = self
+            .globals
+            .iter()
+            .map(|(_, val)| val.clone())
+            .collect::<Vec<_>>();
+        check_expr(&expr, &resolution, &funcs, &globals).map_err(|errs| self.localize(errs))
+    }
+
+    ///
+    /// find the `main` function and check its signature,
+    /// which is either `main()` or `main(args)`
+    ///
+    pub fn main(&self) -> Result<&Func, Diagnostic> {
+        find_main("", &self.funcs).map_err(|e| self.sources.localize(e))
+    }
+
+    ///
+    /// run `main`, passing the program arguments as a list of strings if it takes them
+    ///
+    pub fn run_main(&mut self, args: Vec<String>) -> Result<Value, Diagnostic> {
+        let main = self.main()?;
+        let args = Value::List(args.into_iter().map(Value::Str).collect());
+        let args = if main.args.is_empty() {
+            vec![]
+        } else {
+            vec![args]
+        };
+        self.call("main", &args)
+    }
+}
+
+/// a binding as an expression producing the bound value, so that its annotation is checked
+fn binding_expr(
+    name: Spanned<String>,
+    ty: Option<Spanned<Type>>,
+    val: Spanned<Expr>,
+) -> Spanned<Expr> {
+    let span = val.1.clone();
+    let local = (Expr::Local(name.0.clone()), name.1.clone());
+    (Expr::Var(name, ty, Box::new(val), Box::new(local)), span)
+}
+
+///
+/// the `main` function of the functions of `source`, if its signature is right
+///
+pub fn find_main<'a>(
+    source: &str,
+    funcs: &'a HashMap<String, Func>,
+) -> Result<&'a Func, Diagnostic> {
+    let main = funcs
+        .get("main")
+        .ok_or_else(|| Diagnostic::error("No 'main' function found"))?;
+    if main.args.len() > 1 {
+        let msg = format!(
+            "'main' must take no arguments or a list of program arguments (found {} arguments)",
+            main.args.len()
+        );
+        return Err(Diagnostic::error(msg.clone()).with_label(source, main.args_span.clone(), msg));
+    }
+    Ok(main)
+}
+
+fn arity_error(name: &str, expected: impl std::fmt::Display, found: usize) -> Diagnostic {
+    Diagnostic::error(format!(
+        "'{}' called with wrong number of arguments (expected {}, found {})",
+        name, expected, found
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use interface::nodes::value::Value;
+    use std::{cell::RefCell, rc::Rc};
+
+    use super::{Backend, Engine};
+    use crate::{builtins::Arity, diagnostic::Severity, limits::Limits, output::CapturedOutput};
+
+    #[test]
+    fn calls_loaded_functions() {
+        let output = Rc::new(RefCell::new(CapturedOutput::default()));
+        let mut engine = Engine::with_output(output.clone());
+        engine
+            .load_source("a.ipu", "fn double(x) { x * 2 }")
+            .unwrap();
+        // undefined names are reported before anything runs
+        let errs = engine
+            .load_source("b.ipu", "fn main(args) { println(len(args)); double(y) }")
+            .unwrap_err();
+        assert_eq!(errs[0].message, "No such variable 'y' in scope");
+        assert_eq!(errs[0].location(), Some(("b.ipu", &(43..44))));
+        assert!(engine.main().is_err());
+        engine
+            .load_source(
+                "b.ipu",
+                "fn main(args) { println(len(args)); double(args) }",
+            )
+            .unwrap();
+        engine
+            .builtins_mut()
+            .register("answer", Arity::Exact(0), |_| Ok(Value::I32(42)));
+        assert_eq!(engine.call("double", &[Value::I32(4)]), Ok(Value::I32(8)));
+        assert_eq!(
+            engine.eval_expr_str("double(answer()) + 1"),
+            Ok(Value::I32(85))
+        );
+
+        let err = engine.run_main(vec!["a".to_string()]).unwrap_err();
+        assert_eq!(output.borrow().out, "1\n");
+        // errors point into the source they were found in
+        assert_eq!(err.message, "'[a]' is not a number");
+        assert_eq!(err.location(), Some(("a.ipu", &(15..16))));
+
+        // calls that led to the error are labelled in their own sources
+        let errs = engine.eval_expr_str("double(list())").unwrap_err();
+        let labels = errs[0]
+            .labels
+            .iter()
+            .map(|l| (l.source.as_str(), l.span.clone(), l.message.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            labels,
+            vec![
+                ("a.ipu", 15..16, "'[]' is not a number"),
+                ("<expr>", 0..14, "in call to double([])"),
+            ]
+        );
+
+        let err = engine.call("double", &[]).unwrap_err();
+        assert_eq!(err.location(), None);
+    }
+
+    #[test]
+    fn enforces_annotations() {
+        let mut engine = Engine::new();
+        engine
+            .load_source(
+                "a.ipu",
+                "fn first(xs: [int]) -> int { get(xs, 0) }\nfn main() { first(list(\"a\")) + 1 }",
+            )
+            .unwrap();
+        let list = |xs: &[Value]| Value::List(xs.to_vec());
+        assert_eq!(
+            engine.call("first", &[list(&[Value::I32(1)])]),
+            Ok(Value::I32(1))
+        );
+        let err = engine.call("first", &[Value::I32(1)]).unwrap_err();
+        assert_eq!(err.message, "'1' is not of type [int]");
+        assert_eq!(err.location(), Some(("a.ipu", &(13..18))));
+
+        // the call that passed the wrong value is labelled
+        let err = engine.run_main(Vec::new()).unwrap_err();
+        assert_eq!(err.message, "'[a]' is not of type [int]");
+        assert_eq!(err.labels[1].span, 54..70);
+
+        let errs = engine
+            .eval_expr_str("var x: str = first(list(1)); x")
+            .unwrap_err();
+        assert_eq!(errs[0].message, "'1' is not of type str");
+    }
+
+    #[test]
+    fn reports_diagnostics() {
+        let mut engine = Engine::new();
+        let errs = engine.load_source("a.ipu", "fn f() { 1 +  }").unwrap_err();
+        assert_eq!(errs[0].location(), Some(("a.ipu", &(14..15))));
+        let errs = engine.eval_expr_str("f()").unwrap_err();
+        assert_eq!(errs[0].message, "No such variable 'f' in scope");
+        assert_eq!(errs[0].location(), Some(("<expr>", &(0..1))));
+
+        assert_eq!(
+            engine.main().unwrap_err().message,
+            "No 'main' function found"
+        );
+        engine.load_source("b.ipu", "fn main(a, b) { a }").unwrap();
+        engine
+            .load_source("c.ipu", "fn f(x: int) -> int { x }\nfn g() { f(\"a\") }")
+            .unwrap();
+        let errs = engine.check_types().unwrap_err();
+        assert_eq!(errs[0].message, "Type mismatch: expected int, found str");
+        assert_eq!(errs[0].location(), Some(("c.ipu", &(37..40))));
+        assert_eq!(
+            engine.main().unwrap_err().location(),
+            Some(("b.ipu", &(7..13)))
+        );
+
+        engine
+            .load_source("d.ipu", "#[colour]\nfn h() { 1 }")
+            .unwrap();
+        let warnings = engine.take_warnings();
+        assert_eq!(warnings[0].severity, Severity::Warning);
+        assert_eq!(warnings[0].message, "Unknown attribute 'colour'");
+        assert_eq!(warnings[0].location(), Some(("d.ipu", &(2..8))));
+        assert!(engine.take_warnings().is_empty());
+        let errs = engine
+            .load_source("e.ipu", "#[memo(1)]\nfn h() { 1 }")
+            .unwrap_err();
+        assert_eq!(errs[0].message, "Attribute 'memo' takes no arguments");
+    }
+
+    #[test]
+    fn memoises_pure_functions() {
+        let src = "memo fn fib(n) { if n < 2 { n } else { fib(n - 1) + fib(n - 2) } }\nfn id(n) { n }\nmemo fn f(n) { id(n) }";
+        for backend in [Backend::Tree, Backend::Vm] {
+            let mut engine = Engine::new();
+            engine.set_backend(backend);
+            engine.load_source("a.ipu", src).unwrap();
+            let limits = Limits {
+                max_steps: Some(10_000),
+                ..Limits::default()
+            };
+            engine.set_limits(limits);
+            assert_eq!(
+                engine.call("fib", &[Value::I32(40)]),
+                Ok(Value::I32(102334155))
+            );
+            // without room in the cache, the calls take exponential time again
+            engine.set_limits(Limits {
+                max_memo_entries: 0,
+                ..limits
+            });
+            assert_eq!(
+                engine.call("fib", &[Value::I32(40)]).unwrap_err().message,
+                "Step limit of 10000 exceeded"
+            );
+        }
+
+        let mut engine = Engine::new();
+        engine.load_source("a.ipu", src).unwrap();
+        let errs = engine
+            .load_source("b.ipu", "fn id(n) { println(n); n }")
+            .unwrap_err();
+        assert_eq!(
+            errs[0].message,
+            "Memoised function 'f' must not call 'id', which calls 'println'"
+        );
+        assert_eq!(errs[0].location(), Some(("a.ipu", &(97..99))));
+        assert_eq!(engine.call("f", &[Value::I32(1)]), Ok(Value::I32(1)));
+    }
+}
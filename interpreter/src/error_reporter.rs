@@ -1,77 +1,99 @@
-use ariadne::{Color, Fmt, Label, Report, ReportKind, Source};
-use interface::tokens::Token;
+use ariadne::{Color, Config, Fmt, Label, Report, ReportKind};
+use std::io::{self, Write};
 
-use chumsky::prelude::Simple;
+use crate::{
+    diagnostic::{Diagnostic, Severity},
+    source_map::SourceMap,
+};
 
-pub fn report_errs(src: &String, lex_errs: Vec<Simple<char>>, parse_errs: Vec<Simple<Token>>) {
-    lex_errs
-        .into_iter()
-        .map(|e| e.map(|c| c.to_string()))
-        .chain(parse_errs.into_iter().map(|e| e.map(|tok| tok.to_string())))
-        .for_each(|e| {
-            let report = Report::build(ReportKind::Error, (), e.span().start);
+///
+/// print diagnostics to stderr
+///
+pub fn report(sources: &SourceMap, diags: &[Diagnostic]) {
+    for diag in diags {
+        write_diagnostic(sources, diag, true, io::stderr()).unwrap();
+    }
+}
+
+///
+/// render a diagnostic as it would be printed, optionally without colors
+///
+pub fn render(sources: &SourceMap, diag: &Diagnostic, color: bool) -> String {
+    let mut buf = Vec::new();
+    write_diagnostic(sources, diag, color, &mut buf).unwrap();
+    String::from_utf8(buf).unwrap()
+}
+
+fn write_diagnostic(
+    sources: &SourceMap,
+    diag: &Diagnostic,
+    color: bool,
+    mut w: impl Write,
+) -> io::Result<()> {
+    let (kind, label_color) = match diag.severity {
+        Severity::Error => (ReportKind::Error, Color::Red),
+        Severity::Warning => (ReportKind::Warning, Color::Yellow),
+    };
+    let (source, offset) = match diag.location() {
+        Some((source, span)) => (source.to_string(), span.start),
+        None => {
+            // there is no source to show, so only print the message
+            writeln!(w, "{}: {}", kind, diag.message)?;
+            for note in &diag.notes {
+                writeln!(w, "  = note: {}", note)?;
+            }
+            return Ok(());
+        }
+    };
+
+    let mut report = Report::build(kind, source, offset)
+        .with_config(Config::default().with_color(color))
+        .with_message(&diag.message);
+    for (i, label) in diag.labels.iter().enumerate() {
+        // the primary label stands out from the others
+        let label_color = if i == 0 { label_color } else { Color::Yellow };
+        let message = if color {
+            label.message.clone().fg(label_color).to_string()
+        } else {
+            label.message.clone()
+        };
+        let mut l = Label::new((label.source.clone(), label.span.clone())).with_message(message);
+        if color {
+            l = l.with_color(label_color);
+        }
+        report = report.with_label(l);
+    }
+    for note in &diag.notes {
+        report = report.with_note(note);
+    }
+    report.finish().write(
+        ariadne::sources(sources.files().map(|f| (f.name.clone(), f.src.clone()))),
+        w,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::render;
+    use crate::{diagnostic::Diagnostic, source_map::SourceMap};
 
-            let report = match e.reason() {
-                chumsky::error::SimpleReason::Unclosed { span, delimiter } => report
-                    .with_message(format!(
-                        "Unclosed delimiter {}",
-                        delimiter.fg(Color::Yellow)
-                    ))
-                    .with_label(
-                        Label::new(span.clone())
-                            .with_message(format!(
-                                "Unclosed delimiter {}",
-                                delimiter.fg(Color::Yellow)
-                            ))
-                            .with_color(Color::Yellow),
-                    )
-                    .with_label(
-                        Label::new(e.span())
-                            .with_message(format!(
-                                "Must be closed before this {}",
-                                e.found()
-                                    .unwrap_or(&"end of file".to_string())
-                                    .fg(Color::Red)
-                            ))
-                            .with_color(Color::Red),
-                    ),
-                chumsky::error::SimpleReason::Unexpected => report
-                    .with_message(format!(
-                        "{}, expected {}",
-                        if e.found().is_some() {
-                            "Unexpected token in input"
-                        } else {
-                            "Unexpected end of input"
-                        },
-                        if e.expected().len() == 0 {
-                            "something else".to_string()
-                        } else {
-                            e.expected()
-                                .map(|expected| match expected {
-                                    Some(expected) => expected.to_string(),
-                                    None => "end of input".to_string(),
-                                })
-                                .collect::<Vec<_>>()
-                                .join(", ")
-                        }
-                    ))
-                    .with_label(
-                        Label::new(e.span())
-                            .with_message(format!(
-                                "Unexpected token {}",
-                                e.found()
-                                    .unwrap_or(&"end of file".to_string())
-                                    .fg(Color::Red)
-                            ))
-                            .with_color(Color::Red),
-                    ),
-                chumsky::error::SimpleReason::Custom(msg) => report.with_message(msg).with_label(
-                    Label::new(e.span())
-                        .with_message(format!("{}", msg.fg(Color::Red)))
-                        .with_color(Color::Red),
-                ),
-            };
+    #[test]
+    fn renders_diagnostics() {
+        let mut sources = SourceMap::default();
+        sources.add("a.ipu", "fn main() { x }");
+        let diag = Diagnostic::error("No such variable 'x' in scope")
+            .with_label("a.ipu", 12..13, "not found")
+            .with_note("variables must be declared with var");
+        let s = render(&sources, &diag, false);
+        assert!(s.starts_with("Error: No such variable 'x' in scope"));
+        assert!(s.contains("a.ipu:1:13"));
+        assert!(s.contains("Note: variables must be declared with var"));
 
-            report.finish().print(Source::from(&src)).unwrap();
-        });
+        let s = render(
+            &sources,
+            &Diagnostic::error("No 'main' function found"),
+            false,
+        );
+        assert_eq!(s, "Error: No 'main' function found\n");
+    }
 }
@@ -0,0 +1,65 @@
+use chumsky::{prelude::Simple, Stream};
+use interface::{
+    nodes::{func::Func, value::Value},
+    tokens::Token,
+    Spanned,
+};
+use parser::{lexer::lex, parsers::parse};
+use std::{collections::HashMap, process::ExitCode};
+
+/// everything the lexer and parser produced for a source file
+pub struct Parsed {
+    /// all tokens, including comments
+    pub tokens: Option<Vec<Spanned<Token>>>,
+    pub funcs: Option<HashMap<String, Func>>,
+    pub lex_errs: Vec<Simple<char>>,
+    pub parse_errs: Vec<Simple<Token>>,
+}
+
+impl Parsed {
+    pub fn has_errors(&self) -> bool {
+        !self.lex_errs.is_empty() || !self.parse_errs.is_empty()
+    }
+}
+
+///
+/// lex and parse a source file
+///
+pub fn parse_src(src: &str) -> Parsed {
+    let (tokens, lex_errs) = lex(src);
+    let (funcs, parse_errs) = match &tokens {
+        Some(tokens) => parse_tokens(tokens, src.chars().count()),
+        None => (None, Vec::new()),
+    };
+    Parsed {
+        tokens,
+        funcs,
+        lex_errs,
+        parse_errs,
+    }
+}
+
+///
+/// parse the tokens of a source `len` chars long, skipping comments
+///
+pub fn parse_tokens(
+    tokens: &[Spanned<Token>],
+    len: usize,
+) -> (Option<HashMap<String, Func>>, Vec<Simple<Token>>) {
+    let tokens = tokens
+        .iter()
+        .filter(|t| !matches!(t.0, Token::Comment(_)))
+        .cloned()
+        .collect::<Vec<_>>();
+    parse(Stream::from_iter(len..len + 1, tokens.into_iter()))
+}
+
+/// the process exit code for the value returned by `main`
+pub fn exit_code(val: &Value) -> ExitCode {
+    match val {
+        // exit codes are truncated to 8 bits, as by the shell
+        Value::I32(x) => ExitCode::from(*x as u8),
+        Value::I64(x) => ExitCode::from(*x as u8),
+        _ => ExitCode::SUCCESS,
+    }
+}
@@ -1,163 +1,753 @@
+use analysis::resolver::{Def, Resolution};
 use interface::{
-    nodes::{binaryop::BinaryOp, expr::Expr, func::Func, value::Value},
-    Error, Spanned,
+    nodes::{binaryop::BinaryOp, expr::Expr, func::Func, types::Type, value::Value},
+    Error, Frame, Span, Spanned,
 };
 use std::collections::HashMap;
 
-pub fn eval_expr(
-    expr: &Spanned<Expr>,
-    funcs: &HashMap<String, Func>,
-    stack: &mut Vec<(String, Value)>,
-) -> Result<Value, Error> {
-    Ok(match &expr.0 {
-        Expr::Error => unreachable!(), // Error expressions only get created by parser errors, so cannot exist in a valid AST
-        Expr::Value(val) => val.clone(),
-        // Expr::List(items) => Value::List(
-        //     items
-        //         .iter()
-        //         .map(|item| eval_expr(item, funcs, stack))
-        //         .collect::<Result<_, _>>()?,
-        // ),
-        Expr::Local(name) => stack
-            .iter()
-            .rev()
-            .find(|(l, _)| l == name)
-            .map(|(_, v)| v.clone())
-            .or_else(|| {
-                Some(Value::Func(name.clone()))
-                    .filter(|_| funcs.contains_key(name) || name == "print")
-            })
-            .ok_or_else(|| Error {
-                span: expr.1.clone(),
-                msg: format!("No such variable '{}' in scope", name),
-            })?,
-        Expr::Var(local, val, body) => {
-            let val = eval_expr(val, funcs, stack)?;
-            stack.push((local.clone(), val));
-            let res = eval_expr(body, funcs, stack)?;
-            stack.pop();
-            res
-        }
-        Expr::Then(a, b) => {
-            eval_expr(a, funcs, stack)?;
-            eval_expr(b, funcs, stack)?
+use crate::{
+    builtins::Builtins,
+    limits::{heap_size, Limit, Limits},
+    memo::MemoCache,
+    optimizer::Inlined,
+};
+
+/// stack space left when evaluation moves to a new stack segment
+const RED_ZONE: usize = 128 * 1024;
+/// size of each new stack segment
+const STACK_SIZE: usize = 2 * 1024 * 1024;
+
+/// the result of evaluating an expression in tail position
+enum Tail<'a> {
+    Value(Value),
+    /// a call to a user function, made in place of the caller once it returns
+    Call {
+        name: String,
+        func: &'a Func,
+        args: Vec<Value>,
+        span: Span,
+    },
+}
+
+pub struct Interpreter<'a> {
+    funcs: &'a HashMap<String, Func>,
+    /// the names in the bodies of `funcs` resolved to their slots
+    res: &'a Resolution,
+    /// the resolution of the code being evaluated, which is `res` unless it is a top-level expression
+    scope: &'a Resolution,
+    builtins: &'a Builtins,
+    limits: Limits,
+    /// number of user function calls being evaluated
+    depth: usize,
+    /// number of expressions evaluated so far
+    steps: u64,
+    /// bytes allocated by values so far
+    heap: usize,
+    /// results of `memo` functions, which are borrowed for the whole run
+    memo: MemoCache<*const Func>,
+    /// the bodies of calls inlined by the optimiser, which are run in place of the calls
+    inlined: Option<&'a Inlined>,
+}
+
+impl<'a> Interpreter<'a> {
+    pub fn new(
+        funcs: &'a HashMap<String, Func>,
+        res: &'a Resolution,
+        builtins: &'a Builtins,
+    ) -> Self {
+        Self {
+            funcs,
+            res,
+            scope: res,
+            builtins,
+            limits: Limits::default(),
+            depth: 0,
+            steps: 0,
+            heap: 0,
+            memo: MemoCache::new(Limits::default().max_memo_entries),
+            inlined: None,
         }
-        // +
-        Expr::Binary(a, BinaryOp::Add, b) => Value::I32(
-            eval_expr(a, funcs, stack)?.num_i32(a.1.clone())?
-                + eval_expr(b, funcs, stack)?.num_i32(b.1.clone())?,
-        ),
-        // -
-        Expr::Binary(a, BinaryOp::Sub, b) => Value::I32(
-            eval_expr(a, funcs, stack)?.num_i32(a.1.clone())?
-                - eval_expr(b, funcs, stack)?.num_i32(b.1.clone())?,
-        ),
-        // *
-        Expr::Binary(a, BinaryOp::Mul, b) => Value::I32(
-            eval_expr(a, funcs, stack)?.num_i32(a.1.clone())?
-                * eval_expr(b, funcs, stack)?.num_i32(b.1.clone())?,
-        ),
-        // /
-        Expr::Binary(a, BinaryOp::Div, b) => Value::I32(
-            eval_expr(a, funcs, stack)?.num_i32(a.1.clone())?
-                / eval_expr(b, funcs, stack)?.num_i32(b.1.clone())?,
-        ),
-        // ==
-        Expr::Binary(a, BinaryOp::Eq, b) => {
-            Value::Bool(eval_expr(a, funcs, stack)? == eval_expr(b, funcs, stack)?)
+    }
+
+    pub fn with_inlined(mut self, inlined: Option<&'a Inlined>) -> Self {
+        self.inlined = inlined;
+        self
+    }
+
+    pub fn with_limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self.memo = MemoCache::new(limits.max_memo_entries);
+        self
+    }
+
+    /// an error for exceeding a limit while evaluating `span`
+    fn limit_err(span: &Span, limit: Limit) -> Error {
+        Error::new(span.clone(), limit)
+    }
+
+    /// account for a value allocated while evaluating `span`
+    fn alloc(&mut self, val: &Value, span: &Span) -> Result<(), Error> {
+        self.heap += heap_size(val);
+        match self.limits.max_heap {
+            Some(max) if self.heap > max => Err(Self::limit_err(span, Limit::Heap(max))),
+            _ => Ok(()),
         }
-        // !=
-        Expr::Binary(a, BinaryOp::Neq, b) => {
-            Value::Bool(eval_expr(a, funcs, stack)? != eval_expr(b, funcs, stack)?)
+    }
+
+    /// count an evaluated expression
+    fn step(&mut self, span: &Span) -> Result<(), Error> {
+        self.steps += 1;
+        match self.limits.max_steps {
+            Some(max) if self.steps > max => Err(Self::limit_err(span, Limit::Steps(max))),
+            _ => Ok(()),
         }
-        // >
-        Expr::Binary(a, BinaryOp::Gt, b) => Value::Bool(
-            eval_expr(a, funcs, stack)?.num_i32(a.1.clone())?
-                > eval_expr(b, funcs, stack)?.num_i32(b.1.clone())?,
-        ),
-        // >=
-        Expr::Binary(a, BinaryOp::Geq, b) => Value::Bool(
-            eval_expr(a, funcs, stack)?.num_i32(a.1.clone())?
-                >= eval_expr(b, funcs, stack)?.num_i32(b.1.clone())?,
-        ),
-        // <
-        Expr::Binary(a, BinaryOp::Lt, b) => Value::Bool(
-            eval_expr(a, funcs, stack)?.num_i32(a.1.clone())?
-                < eval_expr(b, funcs, stack)?.num_i32(b.1.clone())?,
-        ),
-        // <=
-        Expr::Binary(a, BinaryOp::Leq, b) => Value::Bool(
-            eval_expr(a, funcs, stack)?.num_i32(a.1.clone())?
-                <= eval_expr(b, funcs, stack)?.num_i32(b.1.clone())?,
-        ),
-        // &&
-        Expr::Binary(a, BinaryOp::And, b) => Value::Bool(
-            eval_expr(a, funcs, stack)?.bool(a.1.clone())?
-                && eval_expr(b, funcs, stack)?.bool(b.1.clone())?,
-        ),
-        // ||
-        Expr::Binary(a, BinaryOp::Or, b) => Value::Bool(
-            eval_expr(a, funcs, stack)?.bool(a.1.clone())?
-                || eval_expr(b, funcs, stack)?.bool(b.1.clone())?,
-        ),
-        // %
-        Expr::Binary(a, BinaryOp::Mod, b) => Value::I32(
-            eval_expr(a, funcs, stack)?.num_i32(a.1.clone())?
-                % eval_expr(b, funcs, stack)?.num_i32(b.1.clone())?,
-        ),
-        Expr::Call(func, (args, args_span)) => {
-            let f = eval_expr(func, funcs, stack)?;
-            match f {
-                Value::Func(name) => {
-                    if name == "print" {
-                        let evaled_args = args
-                            .iter()
-                            .map(|arg| eval_expr(arg, funcs, stack).unwrap())
-                            .collect::<Vec<Value>>();
-                        dbg!(evaled_args);
-                        return Ok(Value::Null);
+    }
+
+    ///
+    /// evaluate a top-level expression whose names were resolved in `res`,
+    /// with the values of the globals in their slots
+    ///
+    pub fn eval_top(
+        &mut self,
+        expr: &Spanned<Expr>,
+        res: &'a Resolution,
+        mut globals: Vec<Value>,
+    ) -> Result<Value, Error> {
+        let funcs_res = std::mem::replace(&mut self.scope, res);
+        let val = self.eval_expr(expr, &mut globals);
+        self.scope = funcs_res;
+        val
+    }
+
+    pub fn eval_expr(
+        &mut self,
+        expr: &Spanned<Expr>,
+        stack: &mut Vec<Value>,
+    ) -> Result<Value, Error> {
+        self.step(&expr.1)?;
+        // deep recursion continues on a stack allocated on the heap
+        // instead of overflowing the native one
+        stacker::maybe_grow(RED_ZONE, STACK_SIZE, || self.eval(expr, stack))
+    }
+
+    fn eval(&mut self, expr: &Spanned<Expr>, stack: &mut Vec<Value>) -> Result<Value, Error> {
+        Ok(match &expr.0 {
+            Expr::Error => unreachable!(), // Error expressions only get created by parser errors, so cannot exist in a valid AST
+            Expr::Value(val) => {
+                self.alloc(val, &expr.1)?;
+                val.clone()
+            }
+            // Expr::List(items) => Value::List(
+            //     items
+            //         .iter()
+            //         .map(|item| self.eval_expr(item, stack))
+            //         .collect::<Result<_, _>>()?,
+            // ),
+            // locals are bound in the order of their slots
+            Expr::Local(name) => match self.scope.def_of(&expr.1) {
+                Some(Def {
+                    slot: Some(slot), ..
+                }) => stack[*slot].clone(),
+                Some(def) => Value::Func(def.name.clone()),
+                // only when the errors of the resolution were ignored
+                None => {
+                    return Err(Error::new(
+                        expr.1.clone(),
+                        format!("No such variable '{}' in scope", name),
+                    ))
+                }
+            },
+            Expr::Var(_, ty, val, body) => {
+                let val = self.eval_expr(val, stack)?;
+                if let Some((ty, span)) = ty {
+                    val.check_type(ty, span.clone())?;
+                }
+                stack.push(val);
+                let res = self.eval_expr(body, stack)?;
+                stack.pop();
+                res
+            }
+            Expr::Then(a, b) => {
+                self.eval_expr(a, stack)?;
+                self.eval_expr(b, stack)?
+            }
+            // + - * / %
+            Expr::Binary(
+                a,
+                op
+                @ (BinaryOp::Add | BinaryOp::Sub | BinaryOp::Mul | BinaryOp::Div | BinaryOp::Mod),
+                b,
+            ) => self.arith(expr, a, *op, b, stack)?,
+            // ==
+            Expr::Binary(a, BinaryOp::Eq, b) => {
+                Value::Bool(self.eval_expr(a, stack)? == self.eval_expr(b, stack)?)
+            }
+            // !=
+            Expr::Binary(a, BinaryOp::Neq, b) => {
+                Value::Bool(self.eval_expr(a, stack)? != self.eval_expr(b, stack)?)
+            }
+            // >
+            Expr::Binary(a, BinaryOp::Gt, b) => Value::Bool(
+                self.eval_expr(a, stack)?.num_i32(a.1.clone())?
+                    > self.eval_expr(b, stack)?.num_i32(b.1.clone())?,
+            ),
+            // >=
+            Expr::Binary(a, BinaryOp::Geq, b) => Value::Bool(
+                self.eval_expr(a, stack)?.num_i32(a.1.clone())?
+                    >= self.eval_expr(b, stack)?.num_i32(b.1.clone())?,
+            ),
+            // <
+            Expr::Binary(a, BinaryOp::Lt, b) => Value::Bool(
+                self.eval_expr(a, stack)?.num_i32(a.1.clone())?
+                    < self.eval_expr(b, stack)?.num_i32(b.1.clone())?,
+            ),
+            // <=
+            Expr::Binary(a, BinaryOp::Leq, b) => Value::Bool(
+                self.eval_expr(a, stack)?.num_i32(a.1.clone())?
+                    <= self.eval_expr(b, stack)?.num_i32(b.1.clone())?,
+            ),
+            // &&
+            Expr::Binary(a, BinaryOp::And, b) => Value::Bool(
+                self.eval_expr(a, stack)?.bool(a.1.clone())?
+                    && self.eval_expr(b, stack)?.bool(b.1.clone())?,
+            ),
+            // ||
+            Expr::Binary(a, BinaryOp::Or, b) => Value::Bool(
+                self.eval_expr(a, stack)?.bool(a.1.clone())?
+                    || self.eval_expr(b, stack)?.bool(b.1.clone())?,
+            ),
+            Expr::Call(_, (args, _)) if self.inlined.is_some_and(|i| i.contains_key(&expr.1)) => {
+                self.inlined(expr, args, stack)?
+            }
+            Expr::Call(func, (args, args_span)) => {
+                let f = self.eval_expr(func, stack)?;
+                match self.callee(func, f, args, args_span, stack, Some(&expr.1))? {
+                    Ok((name, f, args)) => {
+                        self.depth += 1;
+                        let res = self.run_func(f, args, Some((name, expr.1.clone())));
+                        self.depth -= 1;
+                        res?
                     }
+                    Err(val) => val,
+                }
+            }
+            Expr::If(cond, a, b) => {
+                let c = self.eval_expr(cond, stack)?;
+                match c {
+                    Value::Bool(true) => self.eval_expr(a, stack)?,
+                    Value::Bool(false) => self.eval_expr(b, stack)?,
+                    c => {
+                        return Err(Error::new(
+                            cond.1.clone(),
+                            format!("Conditions must be booleans, found '{:?}'", c),
+                        ))
+                    }
+                }
+            }
+            Expr::Return(a) => self.eval_expr(a, stack)?,
+        })
+    }
+
+    ///
+    /// evaluate the body of a call inlined by the optimiser,
+    /// which counts towards the call depth and is traced like the call it replaced
+    ///
+    fn inlined(
+        &mut self,
+        expr: &Spanned<Expr>,
+        args: &[Spanned<Expr>],
+        stack: &mut Vec<Value>,
+    ) -> Result<Value, Error> {
+        let (name, body) = &self.inlined.unwrap()[&expr.1];
+        if let Some(max) = self.limits.max_call_depth {
+            if self.depth >= max {
+                return Err(Self::limit_err(&expr.1, Limit::CallDepth(max)));
+            }
+        }
+        let args = args
+            .iter()
+            .map(|arg| self.eval_expr(arg, stack))
+            .collect::<Result<Vec<_>, _>>()?;
+        self.depth += 1;
+        let res = self.eval_expr(body, stack);
+        self.depth -= 1;
+        res.map_err(|mut e| {
+            e.trace.push(Frame {
+                func: name.to_string(),
+                call_span: expr.1.clone(),
+                args,
+            });
+            e
+        })
+    }
 
-                    let f = &funcs[&name];
-                    let mut stack = if f.args.len() != args.len() {
-                        return Err(Error {
-                            span: args_span.clone(),
-                            msg: format!("'{}' called with wrong number of arguments (expected {}, found {})", name, f.args.len(), args.len()),
-                        });
-                    } else {
-                        f.args
-                            .iter()
-                            .zip(args.iter())
-                            .map(|(name, arg)| Ok((name.clone(), eval_expr(arg, funcs, stack)?)))
-                            .collect::<Result<_, _>>()?
-                    };
-                    eval_expr(&f.body, funcs, &mut stack)?
+    ///
+    /// evaluate an expression whose value is the result of the function it is in,
+    /// leaving a call to a user function for the caller to make
+    ///
+    fn eval_tail(
+        &mut self,
+        expr: &Spanned<Expr>,
+        stack: &mut Vec<Value>,
+    ) -> Result<Tail<'a>, Error> {
+        match &expr.0 {
+            Expr::Var(_, ty, val, body) => {
+                self.step(&expr.1)?;
+                let val = self.eval_expr(val, stack)?;
+                if let Some((ty, span)) = ty {
+                    val.check_type(ty, span.clone())?;
                 }
-                f => {
-                    return Err(Error {
-                        span: func.1.clone(),
-                        msg: format!("'{:?}' is not callable", f),
-                    })
+                stack.push(val);
+                let res = self.eval_tail(body, stack)?;
+                stack.pop();
+                Ok(res)
+            }
+            Expr::Then(a, b) => {
+                self.step(&expr.1)?;
+                self.eval_expr(a, stack)?;
+                self.eval_tail(b, stack)
+            }
+            Expr::If(cond, a, b) => {
+                self.step(&expr.1)?;
+                match self.eval_expr(cond, stack)? {
+                    Value::Bool(true) => self.eval_tail(a, stack),
+                    Value::Bool(false) => self.eval_tail(b, stack),
+                    c => Err(Error::new(
+                        cond.1.clone(),
+                        format!("Conditions must be booleans, found '{:?}'", c),
+                    )),
                 }
             }
+            Expr::Return(a) => {
+                self.step(&expr.1)?;
+                self.eval_tail(a, stack)
+            }
+            Expr::Call(func, (args, args_span)) => {
+                self.step(&expr.1)?;
+                let f = self.eval_expr(func, stack)?;
+                // a tail call replaces the caller, so it does not count towards the depth
+                Ok(match self.callee(func, f, args, args_span, stack, None)? {
+                    Ok((name, func, args)) => Tail::Call {
+                        name,
+                        func,
+                        args,
+                        span: expr.1.clone(),
+                    },
+                    Err(val) => Tail::Value(val),
+                })
+            }
+            _ => self.eval_expr(expr, stack).map(Tail::Value),
+        }
+    }
+
+    ///
+    /// evaluate the arguments of a call to a user function,
+    /// or make the call if `f` is a native function;
+    /// the call depth is checked against the limit at `call_span`, unless it is a tail call
+    ///
+    #[allow(clippy::type_complexity)]
+    fn callee(
+        &mut self,
+        func: &Spanned<Expr>,
+        f: Value,
+        args: &[Spanned<Expr>],
+        args_span: &Span,
+        stack: &mut Vec<Value>,
+        call_span: Option<&Span>,
+    ) -> Result<Result<(String, &'a Func, Vec<Value>), Value>, Error> {
+        let name = match f {
+            Value::Func(name) => name,
+            f => {
+                return Err(Error::new(
+                    func.1.clone(),
+                    format!("'{:?}' is not callable", f),
+                ))
+            }
+        };
+        let funcs = self.funcs;
+        let f = match funcs.get(&name) {
+            Some(f) => f,
+            None => return self.call_native(&name, args, args_span, stack).map(Err),
+        };
+        if let (Some(max), Some(span)) = (self.limits.max_call_depth, call_span) {
+            if self.depth >= max {
+                return Err(Self::limit_err(span, Limit::CallDepth(max)));
+            }
         }
-        Expr::If(cond, a, b) => {
-            let c = eval_expr(cond, funcs, stack)?;
-            match c {
-                Value::Bool(true) => eval_expr(a, funcs, stack)?,
-                Value::Bool(false) => eval_expr(b, funcs, stack)?,
-                c => {
-                    return Err(Error {
-                        span: cond.1.clone(),
-                        msg: format!("Conditions must be booleans, found '{:?}'", c),
+        if f.args.len() != args.len() {
+            return Err(Error::new(
+                args_span.clone(),
+                format!(
+                    "'{}' called with wrong number of arguments (expected {}, found {})",
+                    name,
+                    f.args.len(),
+                    args.len()
+                ),
+            ));
+        }
+        let args = args
+            .iter()
+            .map(|arg| self.eval_expr(arg, stack))
+            .collect::<Result<_, _>>()?;
+        Ok(Ok((name, f, args)))
+    }
+
+    ///
+    /// call a user function from the host with already evaluated arguments
+    ///
+    pub fn call_func(&mut self, f: &'a Func, args: Vec<Value>) -> Result<Value, Error> {
+        self.run_func(f, args, None)
+    }
+
+    ///
+    /// evaluate the body of a user function, enforcing its type annotations,
+    /// caching the results of `memo` functions
+    /// and making calls in tail position without growing the native stack;
+    /// errors are traced to `frame`, the name and call site of the function
+    ///
+    fn run_func(
+        &mut self,
+        mut f: &'a Func,
+        mut args: Vec<Value>,
+        mut frame: Option<(String, Span)>,
+    ) -> Result<Value, Error> {
+        // return types of functions that made tail calls, each once, checked when the last callee returns
+        let mut checks: Vec<&'a Spanned<Type>> = Vec::new();
+        // calls of `memo` functions whose result is the result of this call
+        let mut memos: Vec<(*const Func, Vec<Value>)> = Vec::new();
+        // a function called from a top-level expression has the names of the functions
+        let scope = std::mem::replace(&mut self.scope, self.res);
+        loop {
+            let cached = if f.memo {
+                let cached = self.memo.get(&(f as *const _), &args).cloned();
+                if cached.is_none() {
+                    memos.push((f, args.clone()));
+                }
+                cached
+            } else {
+                None
+            };
+            let mut stack = args;
+            let res = match cached {
+                Some(val) => Ok(Tail::Value(val)),
+                None => f
+                    .arg_types
+                    .iter()
+                    .zip(&stack)
+                    .try_for_each(|(ty, val)| match ty {
+                        Some((ty, span)) => val.check_type(ty, span.clone()),
+                        None => Ok(()),
                     })
+                    .and_then(|()| self.eval_tail(&f.body, &mut stack)),
+            };
+            let res = match res {
+                Ok(Tail::Call {
+                    name,
+                    func,
+                    args: next,
+                    span,
+                }) => {
+                    if let Some(ty) = &f.ret_type {
+                        // recursion, mutual or not, would check the same annotations over and over
+                        if !checks.iter().any(|check| std::ptr::eq(*check, ty)) {
+                            checks.push(ty);
+                        }
+                    }
+                    f = func;
+                    args = next;
+                    if let Some(frame) = &mut frame {
+                        *frame = (name, span);
+                    }
+                    continue;
                 }
-            }
+                Ok(Tail::Value(val)) => f
+                    .ret_type
+                    .iter()
+                    .chain(checks.iter().rev().copied())
+                    .try_for_each(|(ty, span)| val.check_type(ty, span.clone()))
+                    .map(|()| {
+                        // only results that passed the checks are cached
+                        for (func, args) in memos.drain(..) {
+                            self.memo.insert(func, args, val.clone());
+                        }
+                        val
+                    }),
+                Err(e) => Err(e),
+            };
+            self.scope = scope;
+            return res.map_err(|mut e| {
+                if let Some((name, call_span)) = frame {
+                    // the arguments are still at the bottom of the callee's stack
+                    stack.truncate(f.args.len());
+                    e.trace.push(Frame {
+                        func: name,
+                        call_span,
+                        args: stack,
+                    });
+                }
+                e
+            });
         }
-        Expr::Return(a) => {
-            let val = eval_expr(a, funcs, stack)?;
-            println!("{}", val);
-            val
+    }
+
+    /// integer arithmetic, which fails instead of panicking
+    fn arith(
+        &mut self,
+        expr: &Spanned<Expr>,
+        a: &Spanned<Expr>,
+        op: BinaryOp,
+        b: &Spanned<Expr>,
+        stack: &mut Vec<Value>,
+    ) -> Result<Value, Error> {
+        let x = self.eval_expr(a, stack)?.num_i32(a.1.clone())?;
+        let y = self.eval_expr(b, stack)?.num_i32(b.1.clone())?;
+        if matches!(op, BinaryOp::Div | BinaryOp::Mod) && y == 0 {
+            return Err(Error::new(b.1.clone(), "Division by zero"));
         }
-    })
+        let res = match op {
+            BinaryOp::Add => x.checked_add(y),
+            BinaryOp::Sub => x.checked_sub(y),
+            BinaryOp::Mul => x.checked_mul(y),
+            BinaryOp::Div => x.checked_div(y),
+            BinaryOp::Mod => x.checked_rem(y),
+            _ => unreachable!(),
+        };
+        res.map(Value::I32)
+            .ok_or_else(|| Error::new(expr.1.clone(), format!("Integer overflow in '{}'", op)))
+    }
+
+    /// call a function registered in [Builtins]
+    fn call_native(
+        &mut self,
+        name: &str,
+        args: &[Spanned<Expr>],
+        args_span: &Span,
+        stack: &mut Vec<Value>,
+    ) -> Result<Value, Error> {
+        let builtins = self.builtins;
+        let builtin = builtins
+            .get(name)
+            .ok_or_else(|| Error::new(args_span.clone(), format!("No such function '{}'", name)))?;
+        if !builtin.arity.accepts(args.len()) {
+            return Err(Error::new(
+                args_span.clone(),
+                format!(
+                    "'{}' called with wrong number of arguments (expected {}, found {})",
+                    name,
+                    builtin.arity,
+                    args.len()
+                ),
+            ));
+        }
+        let args = args
+            .iter()
+            .map(|arg| self.eval_expr(arg, stack))
+            .collect::<Result<Vec<_>, _>>()?;
+        let val = (builtin.func)(&args).map_err(|e| Error {
+            span: args_span.clone(),
+            ..e
+        })?;
+        self.alloc(&val, args_span)?;
+        Ok(val)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use analysis::resolver::{Resolution, Resolver};
+    use interface::nodes::{func::Func, value::Value};
+    use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+    use super::Interpreter;
+    use crate::{
+        builtins::{native_err, Arity, Builtins},
+        frontend::parse_src,
+        limits::Limits,
+        output::CapturedOutput,
+    };
+
+    /// the names in `funcs` resolved, ignoring names that are not defined
+    fn resolve(funcs: &HashMap<String, Func>, builtins: &Builtins) -> Resolution {
+        let mut resolver = Resolver::new();
+        builtins.names().for_each(|name| resolver.builtin(name));
+        resolver.funcs(funcs);
+        resolver.resolve_funcs(funcs)
+    }
+
+    #[test]
+    fn prints_to_output() {
+        let src =
+            r#"fn main() { print("fib {} = ", 10); println(55); eprint(1, 2); println(print(y)) }"#;
+        let funcs = parse_src(src).funcs.unwrap();
+        let output = Rc::new(RefCell::new(CapturedOutput::default()));
+        let builtins = Builtins::with_std(output.clone());
+        let res = resolve(&funcs, &builtins);
+        let mut interpreter = Interpreter::new(&funcs, &res, &builtins);
+        let err = interpreter
+            .eval_expr(&funcs["main"].body, &mut Vec::new())
+            .unwrap_err();
+        // errors in arguments are reported instead of panicking
+        assert_eq!(err.msg, "No such variable 'y' in scope");
+        assert_eq!(output.borrow().out, "fib 10 = 55\n");
+        assert_eq!(output.borrow().err, "1 2");
+    }
+
+    #[test]
+    fn calls_native_functions() {
+        let src = "fn main() { sum(1, 2, 3) * twice(2) }\nfn fail() { twice(0) }";
+        let funcs = parse_src(src).funcs.unwrap();
+        let mut builtins = Builtins::new();
+        builtins.register("sum", Arity::Variadic, |args| {
+            Ok(Value::I32(
+                args.iter().map(|v| v.clone().num_i32(0..0).unwrap()).sum(),
+            ))
+        });
+        builtins.register("twice", Arity::Exact(1), |args| match args[0] {
+            Value::I32(0) => Err(native_err("zero")),
+            Value::I32(x) => Ok(Value::I32(x * 2)),
+            _ => Err(native_err("not a number")),
+        });
+        let res = resolve(&funcs, &builtins);
+        let mut interpreter = Interpreter::new(&funcs, &res, &builtins);
+        assert_eq!(
+            interpreter.eval_expr(&funcs["main"].body, &mut Vec::new()),
+            Ok(Value::I32(24))
+        );
+        let err = interpreter
+            .eval_expr(&funcs["fail"].body, &mut Vec::new())
+            .unwrap_err();
+        // native errors point at the call's arguments
+        assert_eq!((err.span, err.msg.as_str()), (55..58, "zero"));
+    }
+
+    #[test]
+    fn caches_only_checked_results() {
+        let src = "memo fn m(n) { g(n) }\nfn g(n) -> int { str(n) }";
+        let funcs = parse_src(src).funcs.unwrap();
+        let builtins = Builtins::with_std(Rc::new(RefCell::new(CapturedOutput::default())));
+        let res = resolve(&funcs, &builtins);
+        let mut interpreter = Interpreter::new(&funcs, &res, &builtins);
+        for _ in 0..2 {
+            let err = interpreter
+                .call_func(&funcs["m"], vec![Value::I32(1)])
+                .unwrap_err();
+            assert_eq!(err.msg, "'1' is not of type int");
+        }
+    }
+
+    #[test]
+    fn enforces_limits() {
+        let src = "fn main() { main() + 1 }
+fn f(n) { if n == 0 { 0 } else { 1 + f(n - 1) } }
+fn s(n) { if n == 0 { list() } else { list(str(n), s(n - 1)) } }
+fn e() { 1 / (2 - 2) }";
+        let funcs = parse_src(src).funcs.unwrap();
+        let builtins = Builtins::with_std(Rc::new(RefCell::new(CapturedOutput::default())));
+        let res = resolve(&funcs, &builtins);
+        let call = |limits: Limits, name: &str, n: i32| {
+            let mut stack = vec![Value::I32(n); funcs[name].args.len()];
+            Interpreter::new(&funcs, &res, &builtins)
+                .with_limits(limits)
+                .eval_expr(&funcs[name].body, &mut stack)
+        };
+
+        // runaway recursion fails at the call that went too deep
+        let err = call(Limits::default(), "main", 0).unwrap_err();
+        assert_eq!(
+            (err.span, err.msg.as_str()),
+            (12..18, "Call depth limit of 1000 exceeded")
+        );
+        assert_eq!(call(Limits::default(), "f", 900), Ok(Value::I32(900)));
+
+        let limits = Limits {
+            max_steps: Some(1000),
+            ..Limits::default()
+        };
+        assert_eq!(call(limits, "f", 10), Ok(Value::I32(10)));
+        assert_eq!(
+            call(limits, "f", 900).unwrap_err().msg,
+            "Step limit of 1000 exceeded"
+        );
+
+        let limits = Limits {
+            max_heap: Some(1000),
+            ..Limits::default()
+        };
+        assert!(call(limits, "s", 5).is_ok());
+        assert_eq!(
+            call(limits, "s", 100).unwrap_err().msg,
+            "Heap limit of 1000 bytes exceeded"
+        );
+
+        let err = call(Limits::default(), "e", 0).unwrap_err();
+        assert_eq!((err.span, err.msg.as_str()), (154..159, "Division by zero"));
+    }
+
+    #[test]
+    fn reports_overflow() {
+        let funcs = parse_src("fn main() { 2147483647 + 1 }").funcs.unwrap();
+        let builtins = Builtins::new();
+        let res = resolve(&funcs, &builtins);
+        let err = Interpreter::new(&funcs, &res, &builtins)
+            .eval_expr(&funcs["main"].body, &mut Vec::new())
+            .unwrap_err();
+        assert_eq!(
+            (err.span, err.msg.as_str()),
+            (12..26, "Integer overflow in '+'")
+        );
+    }
+
+    #[test]
+    fn makes_tail_calls_in_constant_space() {
+        let src = "fn count(n, acc) { if n == 0 { acc } else { count(n - 1, acc + 1) } }
+fn f(n) { if n == 0 { x } else { var m = n - 1; f(m) } }
+fn main() { f(2) }";
+        let funcs = parse_src(src).funcs.unwrap();
+        let builtins = Builtins::new();
+        let res = resolve(&funcs, &builtins);
+        let limits = Limits {
+            max_call_depth: Some(10),
+            ..Limits::default()
+        };
+        let mut interpreter = Interpreter::new(&funcs, &res, &builtins).with_limits(limits);
+        assert_eq!(
+            interpreter.call_func(&funcs["count"], vec![Value::I32(100_000), Value::I32(0)]),
+            Ok(Value::I32(100_000))
+        );
+
+        // frames replaced by tail calls are not in the trace
+        let err = interpreter
+            .eval_expr(&funcs["main"].body, &mut Vec::new())
+            .unwrap_err();
+        let trace = err
+            .trace
+            .iter()
+            .map(|frame| (frame.to_string(), frame.call_span.clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(trace, vec![("f(0)".to_string(), 118..122)]);
+    }
+
+    #[test]
+    fn records_stack_traces() {
+        let src = "fn f(n) { if n == 0 { x } else { f(n - 1) + 1 } }\nfn main() { f(2) }";
+        let funcs = parse_src(src).funcs.unwrap();
+        let builtins = Builtins::new();
+        let res = resolve(&funcs, &builtins);
+        let err = Interpreter::new(&funcs, &res, &builtins)
+            .eval_expr(&funcs["main"].body, &mut Vec::new())
+            .unwrap_err();
+        let trace = err
+            .trace
+            .iter()
+            .map(|frame| (frame.to_string(), frame.call_span.clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            trace,
+            vec![
+                ("f(0)".to_string(), 33..41),
+                ("f(1)".to_string(), 33..41),
+                ("f(2)".to_string(), 62..66),
+            ]
+        );
+    }
 }
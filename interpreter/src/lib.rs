@@ -0,0 +1,25 @@
+//!
+//! interpreter for the toy language, for embedding scripts in Rust programs
+//!
+
+pub mod builtins;
+pub mod db;
+pub mod diagnostic;
+pub mod doc;
+pub mod engine;
+pub mod error_reporter;
+pub mod frontend;
+pub mod interpreter;
+pub mod limits;
+pub mod lints;
+pub mod memo;
+pub mod optimizer;
+pub mod output;
+pub mod source_map;
+pub mod test_runner;
+pub mod vm;
+
+pub use diagnostic::Diagnostic;
+pub use engine::Engine;
+pub use interface::nodes::value::Value;
+pub use limits::Limits;
@@ -0,0 +1,64 @@
+use interface::nodes::value::Value;
+use std::mem::size_of;
+
+/// call depth allowed unless configured otherwise
+pub const DEFAULT_MAX_CALL_DEPTH: usize = 1000;
+
+/// results of `memo` functions kept unless configured otherwise
+pub const DEFAULT_MAX_MEMO_ENTRIES: usize = 10_000;
+
+///
+/// bounds on the resources a script may use; `None` means unbounded
+///
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Limits {
+    /// nesting of function calls, which bounds the memory used by runaway recursion
+    pub max_call_depth: Option<usize>,
+    /// number of expressions evaluated, counted the same way by both backends,
+    /// which keeps long-running scripts from hanging
+    pub max_steps: Option<u64>,
+    /// bytes allocated by strings and lists over the whole run
+    pub max_heap: Option<usize>,
+    /// results of `memo` functions cached during a call, after which the oldest are forgotten
+    pub max_memo_entries: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            max_call_depth: Some(DEFAULT_MAX_CALL_DEPTH),
+            max_steps: None,
+            max_heap: None,
+            max_memo_entries: DEFAULT_MAX_MEMO_ENTRIES,
+        }
+    }
+}
+
+/// which limit a script hit
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Limit {
+    CallDepth(usize),
+    Steps(u64),
+    Heap(usize),
+}
+
+impl std::fmt::Display for Limit {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Limit::CallDepth(n) => write!(f, "Call depth limit of {} exceeded", n),
+            Limit::Steps(n) => write!(f, "Step limit of {} exceeded", n),
+            Limit::Heap(n) => write!(f, "Heap limit of {} bytes exceeded", n),
+        }
+    }
+}
+
+///
+/// bytes allocated for the contents of a value
+///
+pub fn heap_size(val: &Value) -> usize {
+    match val {
+        Value::Str(s) => s.len(),
+        Value::List(xs) => xs.len() * size_of::<Value>() + xs.iter().map(heap_size).sum::<usize>(),
+        _ => 0,
+    }
+}
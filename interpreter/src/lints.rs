@@ -0,0 +1,99 @@
+//!
+//! lint warnings as diagnostics, and the config file that sets the level of each rule
+//!
+
+use analysis::lint::{Level, Lint, LintConfig, Rule};
+use std::{fs, path::Path};
+
+use crate::diagnostic::{Diagnostic, Severity};
+
+/// the config file, which applies to the programs in its directory and below
+pub const CONFIG_FILE: &str = "ipu.toml";
+
+///
+/// the levels set in the `[lint]` table of a config file, such as
+/// `unused_variable = "allow"`
+///
+pub fn parse_config(text: &str) -> Result<LintConfig, String> {
+    let table = text
+        .parse::<toml::Table>()
+        .map_err(|e| e.message().to_string())?;
+    let mut config = LintConfig::default();
+    let lints = match table.get("lint") {
+        Some(toml::Value::Table(lints)) => lints,
+        Some(_) => return Err("'lint' must be a table".to_string()),
+        None => return Ok(config),
+    };
+    for (name, level) in lints {
+        let rule = Rule::from_name(name).ok_or_else(|| format!("Unknown lint '{}'", name))?;
+        let level = level.as_str().and_then(Level::from_name).ok_or_else(|| {
+            format!(
+                "Level of '{}' must be \"allow\", \"warn\" or \"deny\"",
+                name
+            )
+        })?;
+        config.set(rule, level);
+    }
+    Ok(config)
+}
+
+///
+/// the config of the nearest config file in the directory of `file` or above it,
+/// or the default one if there is none
+///
+pub fn find_config(file: &Path) -> Result<LintConfig, Diagnostic> {
+    let dir = file.parent().unwrap_or(Path::new(""));
+    let dir = dir.canonicalize().unwrap_or_else(|_| dir.to_path_buf());
+    let path = match dir
+        .ancestors()
+        .map(|dir| dir.join(CONFIG_FILE))
+        .find(|path| path.is_file())
+    {
+        Some(path) => path,
+        None => return Ok(LintConfig::default()),
+    };
+    let invalid = |msg: String| Diagnostic::error(format!("Invalid {}: {}", path.display(), msg));
+    let text = fs::read_to_string(&path).map_err(|e| invalid(e.to_string()))?;
+    parse_config(&text).map_err(invalid)
+}
+
+/// a lint in `source`, as a warning or, if its rule is denied, an error
+pub fn to_diagnostic(source: &str, lint: &Lint) -> Diagnostic {
+    let severity = match lint.level {
+        Level::Deny => Severity::Error,
+        _ => Severity::Warning,
+    };
+    Diagnostic {
+        severity,
+        ..Diagnostic::error(lint.msg.clone())
+            .with_label(source, lint.span.clone(), lint.msg.clone())
+            .with_note(format!(
+                "to allow it, add `#[allow({})]` to the function or `{} = \"allow\"` to {}",
+                lint.rule.name(),
+                lint.rule.name(),
+                CONFIG_FILE
+            ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use analysis::lint::{Level, Rule};
+
+    use super::parse_config;
+
+    #[test]
+    fn parses_config() {
+        let config =
+            parse_config("[lint]\nunused_variable = \"allow\"\nshadowed_binding = \"deny\"\n")
+                .unwrap();
+        assert_eq!(config.level(Rule::UnusedVariable), Level::Allow);
+        assert_eq!(config.level(Rule::ShadowedBinding), Level::Deny);
+        assert_eq!(config.level(Rule::UnusedFunction), Level::Warn);
+        assert_eq!(
+            parse_config("[lint]\nunused = \"allow\""),
+            Err("Unknown lint 'unused'".to_string())
+        );
+        assert!(parse_config("[lint]\nunused_variable = 1").is_err());
+    }
+}
@@ -1,48 +1,490 @@
-use std::{env, fs};
-
-use chumsky::{prelude::Simple, Stream};
-use error_reporter::report_errs;
-use interface::tokens::Token;
-use parser::{lexer::lex, parsers::parse};
-
-use crate::interpreter::eval_expr;
-
-mod error_reporter;
-mod interpreter;
-
-fn main() {
-    let src = fs::read_to_string(env::args().nth(1).expect("Expected file argument"))
-        .expect("Failed to read file");
-    let (tokens, mut lex_errs) = lex(&src);
-
-    let parse_errs = if let Some(tokens) = tokens {
-        let tokens = tokens
-            .into_iter()
-            .filter(|t| !matches!(t.0, Token::Comment(_)))
-            .collect::<Vec<_>>();
-        tokens.iter().for_each(|t| println!("{} {:?}", t.0, t.1));
-
-        let len = src.chars().count();
-        let token_stream = Stream::from_iter(len..len + 1, tokens.into_iter());
-        let (ast, parse_errs) = parse(token_stream);
-
-        println!("{:#?}", ast);
-
-        if let Some(funcs) = ast.filter(|_| lex_errs.len() + parse_errs.len() == 0) {
-            if let Some(main) = funcs.get("main") {
-                assert_eq!(main.args.len(), 0);
-                match eval_expr(&main.body, &funcs, &mut Vec::new()) {
-                    Ok(val) => println!("Return value: {}", val),
-                    Err(e) => lex_errs.push(Simple::custom(e.span, e.msg)),
-                }
-            } else {
-                panic!("No main function!");
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
+
+use chumsky::prelude::Simple;
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use interface::{nodes::func::Func, tokens::Token};
+use interpreter::{
+    db::Database,
+    diagnostic::Severity,
+    doc::{doc_items, example_name, run_doctests, to_html, to_markdown},
+    engine::{find_main, Backend},
+    error_reporter::report,
+    frontend::{exit_code, parse_src},
+    limits::{DEFAULT_MAX_CALL_DEPTH, DEFAULT_MAX_MEMO_ENTRIES},
+    lints::find_config,
+    source_map::SourceMap,
+    test_runner::{run_tests, TestOutcome, TestReport},
+    Diagnostic, Engine, Limits,
+};
+use parser::cst::parse_cst;
+
+mod repl;
+
+#[derive(Parser)]
+#[command(name = "ipu", about = "interpreter for the toy language")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Run a program, exiting with the integer returned by `main`
+    Run {
+        file: PathBuf,
+        #[command(flatten)]
+        limits: LimitArgs,
+        /// How to execute the program
+        #[arg(long, value_enum, default_value_t = BackendArg::Tree)]
+        backend: BackendArg,
+        /// Check types before running the program
+        #[arg(long)]
+        typecheck: bool,
+        /// Fold constants, remove dead code and inline trivial functions before running
+        #[arg(long)]
+        optimize: bool,
+        /// Arguments passed to `main(args)` as a list of strings
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+        args: Vec<String>,
+    },
+    /// Run the functions marked `#[test]`, exiting with failure if any fails
+    Test {
+        file: PathBuf,
+        /// Only run tests whose names contain this
+        filter: Option<String>,
+        #[command(flatten)]
+        limits: LimitArgs,
+        /// How to execute the tests
+        #[arg(long, value_enum, default_value_t = BackendArg::Tree)]
+        backend: BackendArg,
+    },
+    /// Report diagnostics and lint warnings without running the program,
+    /// with the lint levels set in the nearest `ipu.toml`
+    Check {
+        file: PathBuf,
+        /// Also check types
+        #[arg(long)]
+        typecheck: bool,
+    },
+    /// Dump the tokens of a program
+    Lex {
+        file: PathBuf,
+        #[arg(long, value_enum, default_value_t = Format::Text)]
+        format: Format,
+    },
+    /// Dump the AST of a program
+    Parse {
+        file: PathBuf,
+        #[arg(long, value_enum, default_value_t = Format::Text)]
+        format: Format,
+        /// Dump the AST after optimising it
+        #[arg(long)]
+        optimize: bool,
+        /// Dump the lossless syntax tree instead, whitespace and comments included
+        #[arg(long, conflicts_with = "optimize")]
+        cst: bool,
+    },
+    /// Pretty-print a program, keeping its comments
+    Fmt {
+        file: PathBuf,
+        /// Exit with failure if the program is not formatted, instead of printing it
+        #[arg(long)]
+        check: bool,
+        /// Rewrite the file in place instead of printing it
+        #[arg(long, conflicts_with = "check")]
+        write: bool,
+    },
+    /// Generate documentation from the `///` comments before functions
+    Doc {
+        file: PathBuf,
+        #[arg(long, value_enum, default_value_t = DocFormat::Html)]
+        format: DocFormat,
+        /// Run the code blocks in the comments as tests instead
+        #[arg(long)]
+        test: bool,
+    },
+    /// Start an interactive session
+    Repl {
+        /// How to execute entries
+        #[arg(long, value_enum, default_value_t = BackendArg::Tree)]
+        backend: BackendArg,
+    },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum BackendArg {
+    /// Walk the syntax tree
+    Tree,
+    /// Compile to bytecode for a stack machine
+    Vm,
+}
+
+impl From<BackendArg> for Backend {
+    fn from(arg: BackendArg) -> Self {
+        match arg {
+            BackendArg::Tree => Backend::Tree,
+            BackendArg::Vm => Backend::Vm,
+        }
+    }
+}
+
+/// resource limits for running untrusted programs
+#[derive(Args)]
+struct LimitArgs {
+    /// Maximum nesting of function calls
+    #[arg(long, default_value_t = DEFAULT_MAX_CALL_DEPTH)]
+    max_call_depth: usize,
+    /// Maximum number of expressions evaluated
+    #[arg(long)]
+    max_steps: Option<u64>,
+    /// Maximum number of bytes allocated by strings and lists
+    #[arg(long)]
+    max_heap: Option<usize>,
+    /// Maximum number of results of `memo` functions to cache
+    #[arg(long, default_value_t = DEFAULT_MAX_MEMO_ENTRIES)]
+    max_memo_entries: usize,
+}
+
+impl From<LimitArgs> for Limits {
+    fn from(args: LimitArgs) -> Self {
+        Self {
+            max_call_depth: Some(args.max_call_depth),
+            max_steps: args.max_steps,
+            max_heap: args.max_heap,
+            max_memo_entries: args.max_memo_entries,
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Format {
+    Text,
+    Json,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum DocFormat {
+    Html,
+    Markdown,
+}
+
+fn main() -> ExitCode {
+    match Cli::parse().command {
+        Command::Run {
+            file,
+            limits,
+            backend,
+            typecheck,
+            optimize,
+            args,
+        } => with_src(&file, |name, src| {
+            let backend = backend.into();
+            run(name, src, limits.into(), backend, typecheck, optimize, args)
+        }),
+        Command::Test {
+            file,
+            filter,
+            limits,
+            backend,
+        } => with_src(&file, |name, src| {
+            test(name, src, limits.into(), backend.into(), filter.as_deref())
+        }),
+        Command::Check { file, typecheck } => {
+            with_src(&file, |name, src| check(name, src, typecheck))
+        }
+        Command::Lex { file, format } => {
+            with_src(&file, |name, src| dump_tokens(name, src, format))
+        }
+        Command::Parse {
+            file, cst: true, ..
+        } => with_src(&file, |_, src| {
+            print!("{:#?}", parse_cst(src));
+            ExitCode::SUCCESS
+        }),
+        Command::Parse {
+            file,
+            format,
+            optimize,
+            ..
+        } => with_src(&file, |name, src| dump_ast(name, src, format, optimize)),
+        Command::Fmt { file, check, write } => {
+            with_src(&file, |name, src| fmt(&file, name, src, check, write))
+        }
+        Command::Doc { file, format, test } => {
+            with_src(&file, |name, src| doc(name, src, format, test))
+        }
+        Command::Repl { backend } => match repl::repl(backend.into()) {
+            Ok(()) => ExitCode::SUCCESS,
+            Err(e) => {
+                eprintln!("{}", e);
+                ExitCode::FAILURE
             }
+        },
+    }
+}
+
+/// read a source file and hand its name and contents to a command
+fn with_src(file: &Path, command: impl FnOnce(&str, &str) -> ExitCode) -> ExitCode {
+    match fs::read_to_string(file) {
+        Ok(src) => command(&file.display().to_string(), &src),
+        Err(e) => {
+            eprintln!("Failed to read {}: {}", file.display(), e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(
+    name: &str,
+    src: &str,
+    limits: Limits,
+    backend: Backend,
+    typecheck: bool,
+    optimize: bool,
+    args: Vec<String>,
+) -> ExitCode {
+    let mut engine = Engine::new();
+    engine.set_limits(limits);
+    engine.set_backend(backend);
+    engine.set_optimize(optimize);
+    let loaded = engine.load_source(name, src).and_then(|()| {
+        if typecheck {
+            engine.check_types().map(|_| ())
+        } else {
+            Ok(())
+        }
+    });
+    if let Err(errs) = loaded {
+        report(engine.sources(), &errs);
+        return ExitCode::FAILURE;
+    }
+    let warnings = engine.take_warnings();
+    report(engine.sources(), &warnings);
+    match engine.run_main(args) {
+        Ok(val) => exit_code(&val),
+        Err(e) => {
+            report(engine.sources(), &[e]);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn test(name: &str, src: &str, limits: Limits, backend: Backend, filter: Option<&str>) -> ExitCode {
+    let mut engine = Engine::new();
+    engine.set_limits(limits);
+    engine.set_backend(backend);
+    if let Err(errs) = engine.load_source(name, src) {
+        report(engine.sources(), &errs);
+        return ExitCode::FAILURE;
+    }
+    let warnings = engine.take_warnings();
+    report(engine.sources(), &warnings);
+
+    let results = run_tests(&mut engine, filter, print_outcome);
+    finish_tests(engine.sources(), &results)
+}
+
+fn print_outcome(outcome: &TestOutcome) {
+    let status = if outcome.result.is_ok() {
+        "ok"
+    } else {
+        "FAILED"
+    };
+    println!("test {} ... {}", outcome.name, status);
+}
+
+/// report failed tests and sum up, failing if any failed
+fn finish_tests(sources: &SourceMap, results: &TestReport) -> ExitCode {
+    let failures = results
+        .outcomes
+        .iter()
+        .filter_map(|outcome| outcome.result.clone().err())
+        .collect::<Vec<_>>();
+    report(sources, &failures);
+    println!(
+        "test result: {}. {} passed; {} failed; {} filtered out",
+        if failures.is_empty() { "ok" } else { "FAILED" },
+        results.passed(),
+        results.failed(),
+        results.filtered_out
+    );
+    if failures.is_empty() {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
+
+///
+/// print the documentation of a program, or run the examples in it
+///
+fn doc(name: &str, src: &str, format: DocFormat, test: bool) -> ExitCode {
+    let mut db = Database::new();
+    db.set_source(name, src);
+    let mut sources = SourceMap::default();
+    sources.add(name, src);
+    let items = match doc_items(&mut db, name) {
+        Ok(items) => items,
+        Err(errs) => {
+            report(&sources, &errs);
+            return ExitCode::FAILURE;
+        }
+    };
+    if !test {
+        match format {
+            DocFormat::Html => print!("{}", to_html(name, &items)),
+            DocFormat::Markdown => print!("{}", to_markdown(name, &items)),
+        }
+        return ExitCode::SUCCESS;
+    }
+    for item in &items {
+        for (i, code) in item.examples().iter().enumerate() {
+            sources.add(example_name(&item.name, i), code.as_str());
         }
+    }
+    let results = run_doctests(name, src, &items, print_outcome);
+    finish_tests(&sources, &results)
+}
 
-        parse_errs
+fn check(name: &str, src: &str, typecheck: bool) -> ExitCode {
+    let mut sources = SourceMap::default();
+    sources.add(name, src);
+    let mut db = Database::new();
+    match find_config(Path::new(name)) {
+        Ok(config) => db.set_lint_config(config),
+        Err(e) => {
+            report(&sources, &[e]);
+            return ExitCode::FAILURE;
+        }
+    }
+    db.set_source(name, src);
+    let mut diags = db.diagnostics(name).to_vec();
+    if diags.iter().all(|diag| diag.severity == Severity::Warning) {
+        diags.extend(db.lints(name).iter().cloned());
+        if typecheck {
+            let types = db.types(name);
+            diags.extend(
+                types
+                    .errors
+                    .iter()
+                    .map(|e| Diagnostic::from_error(name, e.clone())),
+            );
+        }
+        diags.extend(find_main(name, db.ast(name).funcs.as_ref().unwrap()).err());
+    }
+    report(&sources, &diags);
+    if diags.iter().any(|diag| diag.severity == Severity::Error) {
+        ExitCode::FAILURE
     } else {
-        Vec::new()
+        ExitCode::SUCCESS
+    }
+}
+
+fn dump_tokens(name: &str, src: &str, format: Format) -> ExitCode {
+    let parsed = parse_src(src);
+    let tokens = parsed.tokens.unwrap_or_default();
+    match format {
+        Format::Text => tokens.iter().for_each(|t| println!("{} {:?}", t.0, t.1)),
+        Format::Json => println!("{}", serde_json::to_string_pretty(&tokens).unwrap()),
+    }
+    finish(name, src, parsed.lex_errs, Vec::new())
+}
+
+fn dump_ast(name: &str, src: &str, format: Format, optimize: bool) -> ExitCode {
+    if optimize {
+        return dump_optimized_ast(name, src, format);
+    }
+    let parsed = parse_src(src);
+    print_funcs(parsed.funcs.iter().flatten(), format);
+    finish(name, src, parsed.lex_errs, parsed.parse_errs)
+}
+
+/// dump the AST of a program that loads without errors, after optimising it
+fn dump_optimized_ast(name: &str, src: &str, format: Format) -> ExitCode {
+    let mut engine = Engine::new();
+    if let Err(errs) = engine.load_source(name, src) {
+        report(engine.sources(), &errs);
+        return ExitCode::FAILURE;
+    }
+    print_funcs(&engine.optimized().expanded(), format);
+    ExitCode::SUCCESS
+}
+
+fn print_funcs<'a>(funcs: impl IntoIterator<Item = (&'a String, &'a Func)>, format: Format) {
+    // sort functions by name so that dumps are stable
+    let funcs = funcs.into_iter().collect::<BTreeMap<_, _>>();
+    match format {
+        Format::Text => println!("{:#?}", funcs),
+        Format::Json => println!("{}", serde_json::to_string_pretty(&funcs).unwrap()),
+    }
+}
+
+/// print a program formatted, or check that it is or rewrite it in place
+fn fmt(file: &Path, name: &str, src: &str, check: bool, write: bool) -> ExitCode {
+    let parsed = parse_src(src);
+    let formatted = match (&parsed.tokens, &parsed.funcs) {
+        (Some(tokens), Some(funcs)) if !parsed.has_errors() => {
+            parser::printer::format_funcs(src, tokens, funcs)
+        }
+        _ => return finish(name, src, parsed.lex_errs, parsed.parse_errs),
     };
-    report_errs(&src, lex_errs, parse_errs);
+    if check {
+        if formatted == src {
+            return ExitCode::SUCCESS;
+        }
+        // the first line that differs is usually enough to tell what is wrong
+        let (line, (old, new)) = src
+            .lines()
+            .chain(std::iter::repeat(""))
+            .zip(formatted.lines().chain(std::iter::repeat("")))
+            .enumerate()
+            .find(|(_, (old, new))| old != new)
+            .unwrap_or((0, ("", "")));
+        println!("Diff in {} at line {}:\n-{}\n+{}", name, line + 1, old, new);
+        ExitCode::FAILURE
+    } else if write {
+        if formatted == src {
+            return ExitCode::SUCCESS;
+        }
+        match fs::write(file, formatted) {
+            Ok(()) => ExitCode::SUCCESS,
+            Err(e) => {
+                eprintln!("Failed to write {}: {}", file.display(), e);
+                ExitCode::FAILURE
+            }
+        }
+    } else {
+        print!("{}", formatted);
+        ExitCode::SUCCESS
+    }
+}
+
+/// report errors, failing if there are any
+fn finish(
+    name: &str,
+    src: &str,
+    lex_errs: Vec<Simple<char>>,
+    parse_errs: Vec<Simple<Token>>,
+) -> ExitCode {
+    if lex_errs.is_empty() && parse_errs.is_empty() {
+        return ExitCode::SUCCESS;
+    }
+    let mut sources = SourceMap::default();
+    sources.add(name, src);
+    let errs = lex_errs
+        .into_iter()
+        .map(|e| Diagnostic::from_simple(name, e))
+        .chain(
+            parse_errs
+                .into_iter()
+                .map(|e| Diagnostic::from_simple(name, e)),
+        )
+        .collect::<Vec<_>>();
+    report(&sources, &errs);
+    ExitCode::FAILURE
 }
@@ -0,0 +1,46 @@
+use interface::nodes::value::Value;
+use std::{
+    collections::{HashMap, VecDeque},
+    hash::Hash,
+};
+
+///
+/// results of `memo` functions keyed on their argument values,
+/// forgetting the oldest results once it holds `capacity` of them
+///
+pub struct MemoCache<K> {
+    capacity: usize,
+    results: HashMap<K, HashMap<Vec<Value>, Value>>,
+    /// keys in the order they were cached
+    order: VecDeque<(K, Vec<Value>)>,
+}
+
+impl<K: Clone + Eq + Hash> MemoCache<K> {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            results: HashMap::new(),
+            order: VecDeque::new(),
+        }
+    }
+
+    pub fn get(&self, func: &K, args: &[Value]) -> Option<&Value> {
+        self.results.get(func)?.get(args)
+    }
+
+    pub fn insert(&mut self, func: K, args: Vec<Value>, val: Value) {
+        if self.capacity == 0 {
+            return;
+        }
+        let results = self.results.entry(func.clone()).or_default();
+        if results.insert(args.clone(), val).is_none() {
+            self.order.push_back((func, args));
+        }
+        while self.order.len() > self.capacity {
+            let (func, args) = self.order.pop_front().unwrap();
+            if let Some(results) = self.results.get_mut(&func) {
+                results.remove(&args);
+            }
+        }
+    }
+}
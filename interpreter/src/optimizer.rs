@@ -0,0 +1,400 @@
+use analysis::resolver::{DefKind, Resolution};
+use interface::{
+    nodes::{binaryop::BinaryOp, expr::Expr, func::Func, value::Value},
+    Span, Spanned,
+};
+use std::collections::HashMap;
+
+///
+/// the callees and bodies of inlined calls, by the span of the call
+///
+/// the calls stay in the tree, so that it remains the language the analyses know;
+/// the interpreter and the VM run a body in place of its call,
+/// which still counts as a call towards the call depth and in stack traces
+///
+pub type Inlined = HashMap<Span, (String, Spanned<Expr>)>;
+
+/// functions after optimisation
+#[derive(Clone, Debug, Default)]
+pub struct Optimized {
+    pub funcs: HashMap<String, Func>,
+    pub inlined: Inlined,
+}
+
+impl Optimized {
+    /// the functions with inlined calls replaced by their bodies, as they run
+    pub fn expanded(&self) -> HashMap<String, Func> {
+        self.funcs
+            .iter()
+            .map(|(name, func)| {
+                let body = expand(&func.body, &self.inlined);
+                (
+                    name.clone(),
+                    Func {
+                        body,
+                        ..func.clone()
+                    },
+                )
+            })
+            .collect()
+    }
+}
+
+///
+/// optimise the bodies of functions whose names were resolved in `res`
+///
+/// every node keeps the span of the source it came from,
+/// so that runtime errors still point at the original program
+///
+pub fn optimize(funcs: &HashMap<String, Func>, res: &Resolution) -> Optimized {
+    let mut optimizer = Optimizer::new(funcs, res);
+    let funcs = funcs
+        .iter()
+        .map(|(name, func)| {
+            let body = optimizer.expr_at(&func.body, true);
+            (
+                name.clone(),
+                Func {
+                    body,
+                    ..func.clone()
+                },
+            )
+        })
+        .collect();
+    Optimized {
+        funcs,
+        inlined: optimizer.inlined,
+    }
+}
+
+///
+/// optimise an expression that calls into `funcs`, with the bodies of the calls it inlined
+///
+pub fn optimize_expr(
+    expr: &Spanned<Expr>,
+    funcs: &HashMap<String, Func>,
+    res: &Resolution,
+) -> (Spanned<Expr>, Inlined) {
+    let mut optimizer = Optimizer::new(funcs, res);
+    let expr = optimizer.expr(expr);
+    (expr, optimizer.inlined)
+}
+
+struct Optimizer<'a> {
+    funcs: &'a HashMap<String, Func>,
+    res: &'a Resolution,
+    inlined: Inlined,
+}
+
+impl<'a> Optimizer<'a> {
+    fn new(funcs: &'a HashMap<String, Func>, res: &'a Resolution) -> Self {
+        Self {
+            funcs,
+            res,
+            inlined: Inlined::new(),
+        }
+    }
+
+    fn expr(&mut self, expr: &Spanned<Expr>) -> Spanned<Expr> {
+        self.expr_at(expr, false)
+    }
+
+    /// optimise an expression, which is the result of the function if it is in `tail` position
+    fn expr_at(&mut self, expr: &Spanned<Expr>, tail: bool) -> Spanned<Expr> {
+        let span = expr.1.clone();
+        match &expr.0 {
+            Expr::Var(name, ty, val, body) => (
+                Expr::Var(
+                    name.clone(),
+                    ty.clone(),
+                    Box::new(self.expr(val)),
+                    Box::new(self.expr_at(body, tail)),
+                ),
+                span,
+            ),
+            // a prefix without side effects is dead
+            Expr::Then(a, b) => {
+                let (a, b) = (self.expr(a), self.expr_at(b, tail));
+                if is_pure(&a) {
+                    b
+                } else {
+                    (Expr::Then(Box::new(a), Box::new(b)), span)
+                }
+            }
+            Expr::Binary(a, op, b) => fold(self.expr(a), *op, self.expr(b), span),
+            Expr::If(cond, a, b) => match self.expr(cond) {
+                (Expr::Value(Value::Bool(true)), _) => self.expr_at(a, tail),
+                (Expr::Value(Value::Bool(false)), _) => self.expr_at(b, tail),
+                cond => (
+                    Expr::If(
+                        Box::new(cond),
+                        Box::new(self.expr_at(a, tail)),
+                        Box::new(self.expr_at(b, tail)),
+                    ),
+                    span,
+                ),
+            },
+            Expr::Call(func, (args, args_span)) => {
+                let func = self.expr(func);
+                let args = args.iter().map(|arg| self.expr(arg)).collect::<Vec<_>>();
+                // a tail call replaces its caller, which an inlined call would not
+                if let Some((name, callee)) = self.inlinable(&func, &args).filter(|_| !tail) {
+                    let body = self.expr(&substitute(callee, &callee.body, &args));
+                    self.inlined.insert(span.clone(), (name.clone(), body));
+                }
+                (Expr::Call(Box::new(func), (args, args_span.clone())), span)
+            }
+            Expr::Return(val) => (Expr::Return(Box::new(self.expr_at(val, tail))), span),
+            Expr::Error | Expr::Value(_) | Expr::Local(_) => expr.clone(),
+        }
+    }
+
+    ///
+    /// the name and function a call can be replaced with,
+    /// if it is trivial and its arguments are pure
+    ///
+    fn inlinable(
+        &self,
+        func: &Spanned<Expr>,
+        args: &[Spanned<Expr>],
+    ) -> Option<(&'a String, &'a Func)> {
+        let name = match &func.0 {
+            Expr::Local(name) => name,
+            _ => return None,
+        };
+        // a local of the same name may shadow the function
+        if self.res.def_of(&func.1)?.kind != DefKind::Func {
+            return None;
+        }
+        let (name, callee) = self.funcs.get_key_value(name)?;
+        let simple_args = args
+            .iter()
+            .all(|arg| matches!(arg.0, Expr::Value(_) | Expr::Local(_)));
+        (callee.args.len() == args.len() && simple_args && is_trivial(callee, &callee.body))
+            .then_some((name, callee))
+    }
+}
+
+///
+/// whether a function body only combines its arguments and constants,
+/// so that it can be inlined without checks or captured names
+///
+fn is_trivial(func: &Func, body: &Spanned<Expr>) -> bool {
+    let annotated = func.arg_types.iter().any(Option::is_some) || func.ret_type.is_some();
+    !annotated
+        && match &body.0 {
+            Expr::Value(_) => true,
+            Expr::Local(name) => func.args.contains(name),
+            Expr::Binary(a, _, b) => is_trivial(func, a) && is_trivial(func, b),
+            _ => false,
+        }
+}
+
+/// replace the arguments of a trivial function body with the arguments of a call
+fn substitute(func: &Func, body: &Spanned<Expr>, args: &[Spanned<Expr>]) -> Spanned<Expr> {
+    match &body.0 {
+        Expr::Local(name) => match func.args.iter().rposition(|arg| arg == name) {
+            Some(i) => args[i].clone(),
+            None => body.clone(),
+        },
+        Expr::Binary(a, op, b) => (
+            Expr::Binary(
+                Box::new(substitute(func, a, args)),
+                *op,
+                Box::new(substitute(func, b, args)),
+            ),
+            body.1.clone(),
+        ),
+        _ => body.clone(),
+    }
+}
+
+/// replace the inlined calls in an expression with their bodies
+fn expand(expr: &Spanned<Expr>, inlined: &Inlined) -> Spanned<Expr> {
+    let b = |e: &Spanned<Expr>| Box::new(expand(e, inlined));
+    let span = expr.1.clone();
+    match &expr.0 {
+        Expr::Var(name, ty, val, body) => {
+            (Expr::Var(name.clone(), ty.clone(), b(val), b(body)), span)
+        }
+        Expr::Then(a, c) => (Expr::Then(b(a), b(c)), span),
+        Expr::Binary(a, op, c) => (Expr::Binary(b(a), *op, b(c)), span),
+        Expr::Call(..) if inlined.contains_key(&span) => inlined[&span].1.clone(),
+        Expr::Call(func, (args, args_span)) => (
+            Expr::Call(
+                b(func),
+                (
+                    args.iter().map(|arg| expand(arg, inlined)).collect(),
+                    args_span.clone(),
+                ),
+            ),
+            span,
+        ),
+        Expr::Return(a) => (Expr::Return(b(a)), span),
+        Expr::If(cond, a, c) => (Expr::If(b(cond), b(a), b(c)), span),
+        Expr::Error | Expr::Value(_) | Expr::Local(_) => expr.clone(),
+    }
+}
+
+/// whether evaluating an expression can neither fail nor have side effects
+fn is_pure(expr: &Spanned<Expr>) -> bool {
+    match &expr.0 {
+        Expr::Value(_) | Expr::Local(_) => true,
+        Expr::Binary(a, BinaryOp::Eq | BinaryOp::Neq, b) => is_pure(a) && is_pure(b),
+        _ => false,
+    }
+}
+
+/// fold a binary expression of constants, unless evaluating it would fail
+fn fold(a: Spanned<Expr>, op: BinaryOp, b: Spanned<Expr>, span: Span) -> Spanned<Expr> {
+    match (&a.0, op, &b.0) {
+        (Expr::Value(x), _, Expr::Value(y)) => {
+            if let Some(val) = fold_values(x, op, y) {
+                return (Expr::Value(val), span);
+            }
+        }
+        // the right operand is never evaluated
+        (Expr::Value(Value::Bool(false)), BinaryOp::And, _) => {
+            return (Expr::Value(Value::Bool(false)), span)
+        }
+        (Expr::Value(Value::Bool(true)), BinaryOp::Or, _) => {
+            return (Expr::Value(Value::Bool(true)), span)
+        }
+        _ => {}
+    }
+    (Expr::Binary(Box::new(a), op, Box::new(b)), span)
+}
+
+fn fold_values(x: &Value, op: BinaryOp, y: &Value) -> Option<Value> {
+    use Value::{Bool, I32};
+    match (x, op, y) {
+        (_, BinaryOp::Eq, _) => Some(Bool(x == y)),
+        (_, BinaryOp::Neq, _) => Some(Bool(x != y)),
+        (I32(x), BinaryOp::Add, I32(y)) => x.checked_add(*y).map(I32),
+        (I32(x), BinaryOp::Sub, I32(y)) => x.checked_sub(*y).map(I32),
+        (I32(x), BinaryOp::Mul, I32(y)) => x.checked_mul(*y).map(I32),
+        // `None` for division by zero as well as overflow
+        (I32(x), BinaryOp::Div, I32(y)) => x.checked_div(*y).map(I32),
+        (I32(x), BinaryOp::Mod, I32(y)) => x.checked_rem(*y).map(I32),
+        (I32(x), BinaryOp::Gt, I32(y)) => Some(Bool(x > y)),
+        (I32(x), BinaryOp::Geq, I32(y)) => Some(Bool(x >= y)),
+        (I32(x), BinaryOp::Lt, I32(y)) => Some(Bool(x < y)),
+        (I32(x), BinaryOp::Leq, I32(y)) => Some(Bool(x <= y)),
+        (Bool(x), BinaryOp::And, Bool(y)) => Some(Bool(*x && *y)),
+        (Bool(x), BinaryOp::Or, Bool(y)) => Some(Bool(*x || *y)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use parser::printer::print_expr;
+    use std::{cell::RefCell, rc::Rc};
+
+    use super::optimize;
+    use crate::{
+        builtins::Builtins, engine::Backend, frontend::parse_src, limits::Limits,
+        output::StdOutput, Engine, Value,
+    };
+    use analysis::resolver::Resolver;
+
+    fn optimized_main(src: &str) -> String {
+        let funcs = parse_src(src).funcs.unwrap();
+        let mut resolver = Resolver::new();
+        Builtins::with_std(Rc::new(RefCell::new(StdOutput)))
+            .names()
+            .for_each(|name| resolver.builtin(name));
+        resolver.funcs(&funcs);
+        let res = resolver.resolve_funcs(&funcs);
+        print_expr(&optimize(&funcs, &res).expanded()["main"].body)
+    }
+
+    #[test]
+    fn optimizes_expressions() {
+        assert_eq!(optimized_main("fn main() { 1 + 2 * 3 }"), "7;");
+        assert_eq!(
+            optimized_main("fn main() { if 1 < 2 && 2 == 2 { 5; \"a\" } else { print(1) } }"),
+            "\"a\";"
+        );
+        assert_eq!(
+            optimized_main(
+                "fn double(x) { x * 2 }\nfn main() { var y = 3; 4; double(y) + double(2) }"
+            ),
+            "var y = 3;\ny * 2 + 4;"
+        );
+        // calls that may fail or have side effects are kept
+        assert_eq!(
+            optimized_main("fn main() { print(1); 1 / 0 + 2147483647 * 2 }"),
+            "print(1);\n1 / 0 + 2147483647 * 2;"
+        );
+        // annotated functions check their arguments, and `double` here is a local
+        assert_eq!(
+            optimized_main(
+                "fn f(x: int) { x }\nfn double(x) { x * 2 }\nfn main() { var double = f; double(f(1)) }"
+            ),
+            "var double = f;\ndouble(f(1));"
+        );
+    }
+
+    #[test]
+    fn keeps_spans_of_runtime_errors() {
+        let src = "fn inc(x) { x + 1 }\nfn main() { if 0 < 1 { 0 + inc(\"a\") } else { 0 } }";
+        let mut engine = Engine::new();
+        engine.set_optimize(true);
+        engine.load_source("a.ipu", src).unwrap();
+        let err = engine.call("main", &[]).unwrap_err();
+        // the value that is not a number comes from the call site
+        assert_eq!(
+            (err.message.as_str(), err.labels[0].span.clone()),
+            ("'a' is not a number", 51..54)
+        );
+        assert_eq!(engine.eval_expr_str("inc(1) * 2"), Ok(Value::I32(4)));
+        engine.set_backend(Backend::Vm);
+        assert_eq!(engine.call("main", &[]).unwrap_err().labels[0].span, 51..54);
+        assert_eq!(engine.eval_expr_str("inc(1) * 2"), Ok(Value::I32(4)));
+    }
+
+    #[test]
+    fn inlined_calls_count_as_calls() {
+        let src =
+            "fn inc(x) { x + 1 }\nfn f(n, x) { if n == 0 { 0 + inc(x) } else { 0 + f(n - 1, x) } }";
+        for backend in [Backend::Tree, Backend::Vm] {
+            let run = |optimize: bool, limits: Limits, x: Value| {
+                let mut engine = Engine::new();
+                engine.set_backend(backend);
+                engine.set_optimize(optimize);
+                engine.set_limits(limits);
+                engine.load_source("a.ipu", src).unwrap();
+                if optimize {
+                    let optimized = engine.optimized();
+                    assert_eq!(optimized.inlined.len(), 1);
+                    let body = print_expr(&optimized.expanded()["f"].body);
+                    assert!(!body.contains("inc("), "{}", body);
+                }
+                engine.call("f", &[Value::I32(2), x])
+            };
+            // the limit trips at the same call, whether it was inlined or not
+            for depth in 1..6 {
+                let limits = Limits {
+                    max_call_depth: Some(depth),
+                    ..Limits::default()
+                };
+                assert_eq!(
+                    run(true, limits, Value::I32(1)),
+                    run(false, limits, Value::I32(1))
+                );
+            }
+            // and the stack trace still shows the inlined call
+            let a = Value::Str("a".to_string());
+            let err = run(true, Limits::default(), a.clone()).unwrap_err();
+            let unoptimized = run(false, Limits::default(), a).unwrap_err();
+            assert_eq!(err.notes, unoptimized.notes);
+            assert_eq!(
+                err.notes[0],
+                "stack trace, innermost first: inc(a) <- f(0, a) <- f(1, a)"
+            );
+            // only the value that is not a number is found at the call site instead
+            assert_eq!(err.labels[1..], unoptimized.labels[1..]);
+        }
+    }
+}
@@ -0,0 +1,43 @@
+use std::io::{self, Write};
+
+/// where `print` and friends write to
+pub trait Output {
+    /// write to standard output
+    fn out(&mut self, s: &str) -> io::Result<()>;
+    /// write to standard error
+    fn err(&mut self, s: &str) -> io::Result<()>;
+}
+
+/// writes to the process's stdout and stderr
+pub struct StdOutput;
+
+impl Output for StdOutput {
+    fn out(&mut self, s: &str) -> io::Result<()> {
+        let mut stdout = io::stdout();
+        stdout.write_all(s.as_bytes())?;
+        stdout.flush()
+    }
+
+    fn err(&mut self, s: &str) -> io::Result<()> {
+        io::stderr().write_all(s.as_bytes())
+    }
+}
+
+/// keeps everything written, so it can be inspected afterwards
+#[derive(Debug, Default)]
+pub struct CapturedOutput {
+    pub out: String,
+    pub err: String,
+}
+
+impl Output for CapturedOutput {
+    fn out(&mut self, s: &str) -> io::Result<()> {
+        self.out.push_str(s);
+        Ok(())
+    }
+
+    fn err(&mut self, s: &str) -> io::Result<()> {
+        self.err.push_str(s);
+        Ok(())
+    }
+}
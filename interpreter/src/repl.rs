@@ -0,0 +1,237 @@
+use std::{cell::RefCell, env, path::PathBuf, rc::Rc};
+
+use interface::{nodes::value::Value, tokens::Token};
+use interpreter::{
+    diagnostic::Diagnostic,
+    engine::Backend,
+    error_reporter::report,
+    output::{Output, StdOutput},
+    source_map::SourceMap,
+    Engine,
+};
+use parser::{lexer::lex, parsers::repl::ReplEntry};
+use rustyline::{error::ReadlineError, DefaultEditor};
+
+const HELP: &str = "\
+Enter `fn` definitions, `var name = value;` bindings or expressions.
+  :ast <input>     show the AST of an entry
+  :tokens <input>  show the tokens of an entry
+  :type <expr>     show the type of an expression without evaluating it
+  :help            show this message
+  :quit            leave the REPL";
+
+/// state kept across REPL entries
+pub struct Session {
+    engine: Engine,
+    /// number of entries so far, for naming them in diagnostics
+    entries: usize,
+}
+
+impl Session {
+    pub fn new(output: Rc<RefCell<dyn Output>>) -> Self {
+        Self {
+            engine: Engine::with_output(output),
+            entries: 0,
+        }
+    }
+
+    /// a unique name for the next entry
+    fn next_name(&mut self) -> String {
+        self.entries += 1;
+        format!("<repl:{}>", self.entries)
+    }
+
+    /// parse an entry, reporting errors
+    fn parse(&mut self, input: &str) -> Option<ReplEntry> {
+        let name = self.next_name();
+        self.engine
+            .parse_entry(&name, input)
+            .map_err(|errs| report(self.engine.sources(), &errs))
+            .ok()
+    }
+
+    ///
+    /// handle an entry, returning the value of an expression
+    ///
+    pub fn enter(&mut self, input: &str) -> Option<Value> {
+        let name = self.next_name();
+        let res = self.engine.enter(&name, input);
+        let warnings = self.engine.take_warnings();
+        report(self.engine.sources(), &warnings);
+        res.map_err(|errs| report(self.engine.sources(), &errs))
+            .ok()
+            .flatten()
+    }
+
+    /// infer the type of an entry, reporting errors
+    pub fn type_of(&mut self, input: &str) -> Option<String> {
+        let name = self.next_name();
+        self.engine
+            .type_of(&name, input)
+            .map(|scheme| scheme.to_string())
+            .map_err(|errs| report(self.engine.sources(), &errs))
+            .ok()
+    }
+
+    /// handle a `:command`, returning `false` to leave the REPL
+    fn command(&mut self, input: &str) -> bool {
+        let (cmd, rest) = input.split_once(char::is_whitespace).unwrap_or((input, ""));
+        match cmd {
+            ":ast" => {
+                if let Some(entry) = self.parse(rest) {
+                    println!("{:#?}", entry);
+                }
+            }
+            ":tokens" => {
+                let (tokens, lex_errs) = lex(rest);
+                tokens
+                    .unwrap_or_default()
+                    .iter()
+                    .for_each(|t| println!("{} {:?}", t.0, t.1));
+                let mut sources = SourceMap::default();
+                sources.add("<repl>", rest);
+                let errs = lex_errs
+                    .into_iter()
+                    .map(|e| Diagnostic::from_simple("<repl>", e))
+                    .collect::<Vec<_>>();
+                report(&sources, &errs);
+            }
+            ":type" => {
+                if let Some(ty) = self.type_of(rest) {
+                    println!("{}", ty);
+                }
+            }
+            ":help" => println!("{}", HELP),
+            ":quit" => return false,
+            _ => eprintln!("Unknown command '{}', try :help", cmd),
+        }
+        true
+    }
+}
+
+/// whether an entry has unclosed delimiters and continues on the next line
+pub fn is_incomplete(input: &str) -> bool {
+    let (tokens, _) = lex(input);
+    let depth = tokens
+        .unwrap_or_default()
+        .iter()
+        .fold(0i32, |depth, (tok, _)| match tok {
+            Token::Ctrl('{') | Token::Ctrl('(') => depth + 1,
+            Token::Ctrl('}') | Token::Ctrl(')') => depth - 1,
+            _ => depth,
+        });
+    depth > 0
+}
+
+fn history_path() -> Option<PathBuf> {
+    env::var_os("HOME").map(|home| PathBuf::from(home).join(".ipu_history"))
+}
+
+///
+/// run the interactive REPL until EOF or `:quit`
+///
+pub fn repl(backend: Backend) -> rustyline::Result<()> {
+    let mut rl = DefaultEditor::new()?;
+    if let Some(path) = history_path() {
+        // there is no history on the first run
+        let _ = rl.load_history(&path);
+    }
+    println!("Type :help for help");
+
+    let mut session = Session::new(Rc::new(RefCell::new(StdOutput)));
+    session.engine.set_backend(backend);
+    let mut buf = String::new();
+    loop {
+        let prompt = if buf.is_empty() { ">> " } else { ".. " };
+        match rl.readline(prompt) {
+            Ok(line) => {
+                buf.push_str(&line);
+                buf.push('\n');
+                if is_incomplete(&buf) {
+                    continue;
+                }
+                let input = std::mem::take(&mut buf);
+                if input.trim().is_empty() {
+                    continue;
+                }
+                rl.add_history_entry(input.trim_end())?;
+                if input.trim_start().starts_with(':') {
+                    if !session.command(input.trim()) {
+                        break;
+                    }
+                } else if let Some(val) = session.enter(&input) {
+                    if val != Value::Null {
+                        println!("{}", val);
+                    }
+                }
+            }
+            // Ctrl-C drops the entry being typed
+            Err(ReadlineError::Interrupted) => buf.clear(),
+            Err(ReadlineError::Eof) => break,
+            Err(e) => return Err(e),
+        }
+    }
+
+    if let Some(path) = history_path() {
+        rl.save_history(&path)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use interface::nodes::value::Value;
+    use std::{cell::RefCell, rc::Rc};
+
+    use super::{is_incomplete, Session};
+    use interpreter::output::CapturedOutput;
+
+    #[test]
+    fn keeps_definitions() {
+        let output = Rc::new(RefCell::new(CapturedOutput::default()));
+        let mut session = Session::new(output.clone());
+        assert_eq!(session.enter("fn double(x) { x * 2 }"), None);
+        assert_eq!(session.enter("var a = 3;"), None);
+        assert_eq!(session.enter("double(a) + 1"), Some(Value::I32(7)));
+        assert_eq!(session.enter("var a = 1; a"), Some(Value::I32(1)));
+        assert_eq!(session.enter("a"), Some(Value::I32(3)));
+        assert_eq!(session.enter("b"), None);
+        assert_eq!(session.enter("println(a)"), Some(Value::Null));
+        assert_eq!(output.borrow().out, "3\n");
+    }
+
+    #[test]
+    fn infers_types_without_evaluating() {
+        let output = Rc::new(RefCell::new(CapturedOutput::default()));
+        let mut session = Session::new(output.clone());
+        assert_eq!(session.enter("fn double(x) { x * 2 }"), None);
+        assert_eq!(session.enter("var a: int = 3;"), None);
+        assert_eq!(session.enter("var s: str = 3;"), None);
+        assert_eq!(session.type_of("double(a)"), Some("int".to_string()));
+        assert_eq!(
+            session.type_of("println(a); list(a)"),
+            Some("[int]".to_string())
+        );
+        assert_eq!(
+            session.type_of("double"),
+            Some("fn(int) -> int".to_string())
+        );
+        assert_eq!(
+            session.type_of("var b: int = a; b"),
+            Some("int".to_string())
+        );
+        assert_eq!(session.type_of("double(\"x\")"), None);
+        assert_eq!(session.type_of("fn f() { 1 }"), None);
+        // nothing was printed or defined
+        assert_eq!(output.borrow().out, "");
+        assert_eq!(session.enter("s"), None);
+        assert_eq!(session.enter("f()"), None);
+    }
+
+    #[test]
+    fn detects_incomplete_input() {
+        assert!(is_incomplete("fn f() {\n"));
+        assert!(is_incomplete("f(1,\n"));
+        assert!(!is_incomplete("fn f() {\n 1\n}\n"));
+    }
+}
@@ -0,0 +1,94 @@
+use interface::Span;
+
+use crate::diagnostic::Diagnostic;
+
+#[derive(Debug)]
+pub struct SourceFile {
+    pub name: String,
+    pub src: String,
+    /// offset of the first char of the file in the shared span space
+    pub offset: usize,
+}
+
+impl SourceFile {
+    /// whether a span in the shared span space points into this file
+    pub fn contains(&self, span: &Span) -> bool {
+        // the end of file is part of the file, for "unexpected end of input"
+        span.start >= self.offset && span.start <= self.offset + self.src.chars().count()
+    }
+}
+
+///
+/// source files laid out one after another in a single span space,
+/// so that spans of code loaded from different files never overlap
+///
+#[derive(Debug, Default)]
+pub struct SourceMap {
+    files: Vec<SourceFile>,
+    len: usize,
+}
+
+impl SourceMap {
+    ///
+    /// add a file, returning its offset
+    ///
+    pub fn add(&mut self, name: impl Into<String>, src: impl Into<String>) -> usize {
+        let src = src.into();
+        let offset = self.len;
+        // leave a gap so that the end of a file is not the start of the next one
+        self.len += src.chars().count() + 1;
+        self.files.push(SourceFile {
+            name: name.into(),
+            src,
+            offset,
+        });
+        offset
+    }
+
+    /// the most recently added file with this name
+    pub fn get(&self, name: &str) -> Option<&SourceFile> {
+        self.files.iter().rev().find(|f| f.name == name)
+    }
+
+    pub fn files(&self) -> impl Iterator<Item = &SourceFile> {
+        self.files.iter()
+    }
+
+    /// the file a span points into, and the span relative to that file
+    pub fn locate(&self, span: &Span) -> Option<(&SourceFile, Span)> {
+        self.files
+            .iter()
+            .rev()
+            .find(|f| f.contains(span))
+            .map(|f| (f, span.start - f.offset..span.end - f.offset))
+    }
+
+    ///
+    /// move labels of a diagnostic built from shared-space spans into the files they point into
+    ///
+    pub fn localize(&self, mut diag: Diagnostic) -> Diagnostic {
+        for label in &mut diag.labels {
+            if let Some((file, span)) = self.locate(&label.span) {
+                label.source = file.name.clone();
+                label.span = span;
+            }
+        }
+        diag
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SourceMap;
+
+    #[test]
+    fn locates_spans() {
+        let mut sources = SourceMap::default();
+        assert_eq!(sources.add("a", "fn a() { 1 }"), 0);
+        assert_eq!(sources.add("b", "fn b() { 2 }"), 13);
+        let (file, span) = sources.locate(&(16..17)).unwrap();
+        assert_eq!((file.name.as_str(), span), ("b", 3..4));
+        let (file, span) = sources.locate(&(12..12)).unwrap();
+        assert_eq!((file.name.as_str(), span), ("a", 12..12));
+    }
+}
@@ -0,0 +1,108 @@
+use crate::{diagnostic::Diagnostic, engine::Engine};
+
+/// how a test function went
+#[derive(Debug, PartialEq)]
+pub struct TestOutcome {
+    pub name: String,
+    /// the error that made the test fail
+    pub result: Result<(), Diagnostic>,
+}
+
+/// results of running the tests of the loaded functions
+#[derive(Debug, Default)]
+pub struct TestReport {
+    pub outcomes: Vec<TestOutcome>,
+    /// tests that did not match the filter
+    pub filtered_out: usize,
+}
+
+impl TestReport {
+    pub fn passed(&self) -> usize {
+        self.outcomes.iter().filter(|o| o.result.is_ok()).count()
+    }
+
+    pub fn failed(&self) -> usize {
+        self.outcomes.len() - self.passed()
+    }
+}
+
+///
+/// names of the functions marked `#[test]`, in the order they were loaded
+///
+pub fn test_names(engine: &Engine) -> Vec<String> {
+    let mut tests = engine
+        .funcs()
+        .iter()
+        .filter(|(_, func)| func.attrs.iter().any(|attr| attr.name.0 == "test"))
+        .collect::<Vec<_>>();
+    tests.sort_by_key(|(_, func)| func.name_span.start);
+    tests.into_iter().map(|(name, _)| name.clone()).collect()
+}
+
+///
+/// run each test whose name contains `filter` in a call of its own,
+/// so that one test cannot affect another
+///
+pub fn run_tests(
+    engine: &mut Engine,
+    filter: Option<&str>,
+    mut on_outcome: impl FnMut(&TestOutcome),
+) -> TestReport {
+    let mut report = TestReport::default();
+    for name in test_names(engine) {
+        if filter.is_some_and(|filter| !name.contains(filter)) {
+            report.filtered_out += 1;
+            continue;
+        }
+        let func = &engine.funcs()[&name];
+        let result = if func.args.is_empty() {
+            engine.call(&name, &[]).map(|_| ())
+        } else {
+            let msg = format!("Test '{}' must not take arguments", name);
+            Err(engine
+                .sources()
+                .localize(Diagnostic::error(msg.clone()).with_label(
+                    "",
+                    func.args_span.clone(),
+                    msg,
+                )))
+        };
+        let outcome = TestOutcome { name, result };
+        on_outcome(&outcome);
+        report.outcomes.push(outcome);
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::run_tests;
+    use crate::Engine;
+
+    #[test]
+    fn runs_tests() {
+        let src = "fn add(a, b) { a + b }
+#[test]
+fn adds() { assert_eq(add(1, 2), 3) }
+#[test]
+fn fails() { assert(add(1, 1) == 2); assert_eq(add(1, 2), 4) }
+#[test]
+fn takes_args(x) { x }
+fn helper() { assert(1 == 2) }";
+        let mut engine = Engine::new();
+        engine.load_source("t.ipu", src).unwrap();
+
+        let mut seen = Vec::new();
+        let report = run_tests(&mut engine, None, |outcome| seen.push(outcome.name.clone()));
+        assert_eq!(seen, ["adds", "fails", "takes_args"]);
+        assert_eq!((report.passed(), report.failed()), (1, 2));
+        let err = report.outcomes[1].result.as_ref().unwrap_err();
+        assert_eq!(err.message, "Assertion failed: '3' is not equal to '4'");
+        assert_eq!(err.location(), Some(("t.ipu", &(123..137))));
+        let err = report.outcomes[2].result.as_ref().unwrap_err();
+        assert_eq!(err.location(), Some(("t.ipu", &(161..164))));
+
+        let report = run_tests(&mut engine, Some("add"), |_| {});
+        assert_eq!((report.passed(), report.filtered_out), (1, 2));
+    }
+}
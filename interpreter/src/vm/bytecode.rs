@@ -0,0 +1,119 @@
+use interface::{
+    nodes::{binaryop::BinaryOp, types::Type, value::Value},
+    Span, Spanned,
+};
+use std::collections::HashMap;
+
+/// a single VM instruction; operands index into the tables of its [Chunk]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Instr {
+    /// count an evaluated expression, failing if there are more than the step limit
+    Step,
+    /// push a constant
+    Const(u32),
+    /// push the value of a local slot
+    Local(u32),
+    /// pop a value into a local slot
+    StoreLocal(u32),
+    /// push the function of this name, failing if there is none
+    Func(u32),
+    Pop,
+    /// fail unless the value on top is a number
+    CheckNum,
+    /// fail unless the value on top is a bool
+    CheckBool,
+    /// fail unless the value on top has an annotated type
+    CheckType(u32),
+    /// pop two numbers and push the result; the operand is the span of the right-hand side
+    Arith(BinaryOp, u32),
+    /// pop two numbers and compare them; the right-hand side is checked
+    Compare(BinaryOp),
+    Eq,
+    Neq,
+    Jump(u32),
+    /// jump if the bool on top is false, leaving it on the stack
+    JumpIfFalse(u32),
+    /// jump if the bool on top is true, leaving it on the stack
+    JumpIfTrue(u32),
+    /// pop a condition, jumping if it is false
+    Branch(u32),
+    /// fail if calling a function would exceed the call depth limit
+    CheckDepth,
+    /// call a user function with its arguments on the stack
+    CallFunc(u32),
+    /// call a user function in place of the current one
+    TailCallFunc(u32),
+    /// check that the value below the arguments to come can be called with this many;
+    /// the operands are the spans of the callee and of the arguments.
+    /// The call depth is not checked for tail calls
+    PrepareCall {
+        argc: u32,
+        func_span: u32,
+        args_span: u32,
+        tail: bool,
+    },
+    /// call the value below the arguments on the stack
+    Call {
+        argc: u32,
+        args_span: u32,
+    },
+    /// call the value below the arguments on the stack in place of the current function,
+    /// if it is a user function
+    TailCall {
+        argc: u32,
+        args_span: u32,
+    },
+    /// enter the body of a call inlined by the optimiser,
+    /// popping the arguments of the call for stack traces
+    Inline {
+        name: u32,
+        argc: u32,
+    },
+    /// leave the body of an inlined call
+    EndInline,
+    /// return the cached result of a `memo` function called with the same arguments,
+    /// or have the result cached on return
+    Recall,
+    /// fail with a message
+    Fail(u32),
+    Return,
+}
+
+///
+/// compiled code of a function or an expression
+///
+#[derive(Debug, Default)]
+pub struct Chunk {
+    pub name: String,
+    /// number of arguments, which occupy the first local slots
+    pub arity: usize,
+    /// number of local slots, including the arguments
+    pub locals: usize,
+    pub code: Vec<Instr>,
+    /// span of the expression each instruction was compiled from
+    pub code_spans: Vec<Span>,
+    pub consts: Vec<Value>,
+    /// function names and error messages
+    pub names: Vec<String>,
+    /// spans referred to by instruction operands
+    pub spans: Vec<Span>,
+    /// annotated types referred to by instruction operands
+    pub types: Vec<Type>,
+    /// annotated return type, checked by the caller after tail calls
+    pub ret_type: Option<Spanned<Type>>,
+}
+
+///
+/// compiled functions of a program
+///
+#[derive(Debug, Default)]
+pub struct Program {
+    pub funcs: Vec<Chunk>,
+    pub func_index: HashMap<String, usize>,
+}
+
+impl Program {
+    pub fn get(&self, name: &str) -> Option<(usize, &Chunk)> {
+        self.func_index.get(name).map(|&i| (i, &self.funcs[i]))
+    }
+}
@@ -0,0 +1,349 @@
+use analysis::resolver::{DefKind, Resolution};
+use interface::{
+    nodes::{binaryop::BinaryOp, expr::Expr, func::Func, types::Type},
+    Span, Spanned,
+};
+use std::collections::HashMap;
+
+use super::bytecode::{Chunk, Instr, Program};
+use crate::optimizer::Inlined;
+
+/// index and arity of each user function
+type Signatures = HashMap<String, (u32, usize)>;
+
+struct Compiler<'a> {
+    sigs: &'a Signatures,
+    /// local slots and callees of the names in the code
+    res: &'a Resolution,
+    /// the bodies of calls inlined by the optimiser, which are compiled in place of the calls
+    inlined: Option<&'a Inlined>,
+    chunk: Chunk,
+}
+
+///
+/// compile every function of a program, with the names in their bodies resolved by `res`
+///
+pub fn compile(
+    funcs: &HashMap<String, Func>,
+    res: &Resolution,
+    inlined: Option<&Inlined>,
+) -> Program {
+    // sort by name so that function indices do not depend on hash order
+    let mut names = funcs.keys().cloned().collect::<Vec<_>>();
+    names.sort();
+    let sigs = names
+        .iter()
+        .enumerate()
+        .map(|(i, name)| (name.clone(), (i as u32, funcs[name].args.len())))
+        .collect::<Signatures>();
+    Program {
+        funcs: names
+            .iter()
+            .map(|name| compile_func(&sigs, res, inlined, name, &funcs[name]))
+            .collect(),
+        func_index: names
+            .into_iter()
+            .enumerate()
+            .map(|(i, name)| (name, i))
+            .collect(),
+    }
+}
+
+///
+/// compile an expression resolved by `res`, with the first `locals` slots holding the globals
+///
+pub fn compile_expr(
+    program: &Program,
+    res: &Resolution,
+    inlined: Option<&Inlined>,
+    locals: usize,
+    expr: &Spanned<Expr>,
+) -> Chunk {
+    let sigs = program
+        .func_index
+        .iter()
+        .map(|(name, &i)| (name.clone(), (i as u32, program.funcs[i].arity)))
+        .collect::<Signatures>();
+    compile_chunk(&sigs, res, inlined, "<expr>", locals, expr)
+}
+
+/// compile a function, checking its arguments and result against their annotations
+/// and caching its result if it is `memo`
+fn compile_func(
+    sigs: &Signatures,
+    res: &Resolution,
+    inlined: Option<&Inlined>,
+    name: &str,
+    func: &Func,
+) -> Chunk {
+    let mut compiler = Compiler::new(sigs, res, inlined, name, func.args.len());
+    if func.memo {
+        compiler.emit(Instr::Recall, &func.name_span);
+    }
+    for (slot, ty) in func.arg_types.iter().enumerate() {
+        if let Some(ty) = ty {
+            compiler.emit(Instr::Local(slot as u32), &ty.1);
+            compiler.check_type(ty);
+            compiler.emit(Instr::Pop, &ty.1);
+        }
+    }
+    compiler.expr_at(&func.body, true);
+    if let Some(ty) = &func.ret_type {
+        compiler.check_type(ty);
+    }
+    compiler.chunk.ret_type = func.ret_type.clone();
+    compiler.emit(Instr::Return, &func.body.1);
+    compiler.chunk
+}
+
+fn compile_chunk(
+    sigs: &Signatures,
+    res: &Resolution,
+    inlined: Option<&Inlined>,
+    name: &str,
+    arity: usize,
+    body: &Spanned<Expr>,
+) -> Chunk {
+    let mut compiler = Compiler::new(sigs, res, inlined, name, arity);
+    compiler.expr(body);
+    compiler.emit(Instr::Return, &body.1);
+    compiler.chunk
+}
+
+impl<'a> Compiler<'a> {
+    fn new(
+        sigs: &'a Signatures,
+        res: &'a Resolution,
+        inlined: Option<&'a Inlined>,
+        name: &str,
+        arity: usize,
+    ) -> Self {
+        Self {
+            sigs,
+            res,
+            inlined,
+            chunk: Chunk {
+                name: name.to_string(),
+                arity,
+                locals: arity,
+                ..Chunk::default()
+            },
+        }
+    }
+
+    fn emit(&mut self, instr: Instr, span: &Span) -> usize {
+        self.chunk.code.push(instr);
+        self.chunk.code_spans.push(span.clone());
+        self.chunk.code.len() - 1
+    }
+
+    /// point a jump emitted earlier at the next instruction
+    fn patch(&mut self, at: usize) {
+        let target = self.chunk.code.len() as u32;
+        match &mut self.chunk.code[at] {
+            Instr::Jump(t) | Instr::JumpIfFalse(t) | Instr::JumpIfTrue(t) | Instr::Branch(t) => {
+                *t = target
+            }
+            instr => unreachable!("{:?} is not a jump", instr),
+        }
+    }
+
+    fn span(&mut self, span: &Span) -> u32 {
+        self.chunk.spans.push(span.clone());
+        self.chunk.spans.len() as u32 - 1
+    }
+
+    fn check_type(&mut self, (ty, span): &Spanned<Type>) {
+        self.chunk.types.push(ty.clone());
+        let i = self.chunk.types.len() as u32 - 1;
+        self.emit(Instr::CheckType(i), span);
+    }
+
+    fn name(&mut self, name: String) -> u32 {
+        self.chunk.names.push(name);
+        self.chunk.names.len() as u32 - 1
+    }
+
+    /// the local slot a use of a name refers to
+    fn local(&self, span: &Span) -> Option<u32> {
+        self.res
+            .def_of(span)
+            .and_then(|def| def.slot)
+            .map(|slot| slot as u32)
+    }
+
+    fn expr(&mut self, expr: &Spanned<Expr>) {
+        self.expr_at(expr, false)
+    }
+
+    /// compile an expression, which is the result of the function if it is in `tail` position
+    fn expr_at(&mut self, expr: &Spanned<Expr>, tail: bool) {
+        let span = &expr.1;
+        // steps are counted per expression, as the tree-walking interpreter does
+        self.emit(Instr::Step, span);
+        match &expr.0 {
+            Expr::Error => unreachable!(), // Error expressions only get created by parser errors, so cannot exist in a valid AST
+            Expr::Value(val) => {
+                self.chunk.consts.push(val.clone());
+                let i = self.chunk.consts.len() as u32 - 1;
+                self.emit(Instr::Const(i), span);
+            }
+            Expr::Local(name) => match self.local(span) {
+                Some(slot) => {
+                    self.emit(Instr::Local(slot), span);
+                }
+                // functions are looked up when the code runs
+                None => {
+                    let name = self.name(name.clone());
+                    self.emit(Instr::Func(name), span);
+                }
+            },
+            Expr::Var(local, ty, val, body) => {
+                self.expr(val);
+                if let Some(ty) = ty {
+                    self.check_type(ty);
+                }
+                let slot = self
+                    .res
+                    .binding(&local.1)
+                    .and_then(|def| def.slot)
+                    .expect("every `var` is bound by the resolver");
+                self.chunk.locals = self.chunk.locals.max(slot + 1);
+                self.emit(Instr::StoreLocal(slot as u32), span);
+                self.expr_at(body, tail);
+            }
+            Expr::Then(a, b) => {
+                self.expr(a);
+                self.emit(Instr::Pop, span);
+                self.expr_at(b, tail);
+            }
+            Expr::Binary(a, op @ (BinaryOp::And | BinaryOp::Or), b) => {
+                // the right-hand side is only evaluated if it decides the result
+                self.expr(a);
+                self.emit(Instr::CheckBool, &a.1);
+                let jump = match op {
+                    BinaryOp::And => Instr::JumpIfFalse(0),
+                    _ => Instr::JumpIfTrue(0),
+                };
+                let jump = self.emit(jump, span);
+                self.emit(Instr::Pop, span);
+                self.expr(b);
+                self.emit(Instr::CheckBool, &b.1);
+                self.patch(jump);
+            }
+            Expr::Binary(a, op @ (BinaryOp::Eq | BinaryOp::Neq), b) => {
+                self.expr(a);
+                self.expr(b);
+                let instr = match op {
+                    BinaryOp::Eq => Instr::Eq,
+                    _ => Instr::Neq,
+                };
+                self.emit(instr, span);
+            }
+            Expr::Binary(
+                a,
+                op @ (BinaryOp::Gt | BinaryOp::Geq | BinaryOp::Lt | BinaryOp::Leq),
+                b,
+            ) => {
+                self.expr(a);
+                self.emit(Instr::CheckNum, &a.1);
+                self.expr(b);
+                self.emit(Instr::Compare(*op), &b.1);
+            }
+            Expr::Binary(a, op, b) => {
+                self.expr(a);
+                self.emit(Instr::CheckNum, &a.1);
+                self.expr(b);
+                let rhs = self.span(&b.1);
+                self.emit(Instr::Arith(*op, rhs), span);
+            }
+            // the optimiser only inlines calls that are not in tail position
+            Expr::Call(_, (args, _)) if self.inlined.is_some_and(|i| i.contains_key(span)) => {
+                let (name, body) = &self.inlined.unwrap()[span];
+                self.emit(Instr::CheckDepth, span);
+                args.iter().for_each(|arg| self.expr(arg));
+                let name = self.name(name.clone());
+                let argc = args.len() as u32;
+                self.emit(Instr::Inline { name, argc }, span);
+                self.expr(body);
+                self.emit(Instr::EndInline, span);
+            }
+            Expr::Call(func, (args, args_span)) => self.call(expr, func, args, args_span, tail),
+            Expr::If(cond, a, b) => {
+                self.expr(cond);
+                let branch = self.emit(Instr::Branch(0), &cond.1);
+                self.expr_at(a, tail);
+                let jump = self.emit(Instr::Jump(0), span);
+                self.patch(branch);
+                self.expr_at(b, tail);
+                self.patch(jump);
+            }
+            Expr::Return(a) => self.expr_at(a, tail),
+        }
+    }
+
+    fn call(
+        &mut self,
+        expr: &Spanned<Expr>,
+        func: &Spanned<Expr>,
+        args: &[Spanned<Expr>],
+        args_span: &Span,
+        tail: bool,
+    ) {
+        let span = &expr.1;
+        // calls to user functions by name are linked here
+        let direct = match (&func.0, self.res.def_of(&func.1)) {
+            (Expr::Local(name), Some(def)) if def.kind == DefKind::Func => {
+                self.sigs.get(name).map(|&sig| (name, sig))
+            }
+            _ => None,
+        };
+        if let Some((name, (index, arity))) = direct {
+            // the callee is not evaluated, but still counts as a step
+            self.emit(Instr::Step, &func.1);
+            // a tail call replaces the caller, so it does not count towards the depth
+            if !tail {
+                self.emit(Instr::CheckDepth, span);
+            }
+            if arity != args.len() {
+                let msg = self.name(format!(
+                    "'{}' called with wrong number of arguments (expected {}, found {})",
+                    name,
+                    arity,
+                    args.len()
+                ));
+                self.emit(Instr::Fail(msg), args_span);
+                return;
+            }
+            args.iter().for_each(|arg| self.expr(arg));
+            let call = if tail {
+                Instr::TailCallFunc(index)
+            } else {
+                Instr::CallFunc(index)
+            };
+            self.emit(call, span);
+            return;
+        }
+
+        self.expr(func);
+        let func_span = self.span(&func.1);
+        let args_span = self.span(args_span);
+        let argc = args.len() as u32;
+        self.emit(
+            Instr::PrepareCall {
+                argc,
+                func_span,
+                args_span,
+                tail,
+            },
+            span,
+        );
+        args.iter().for_each(|arg| self.expr(arg));
+        let call = if tail {
+            Instr::TailCall { argc, args_span }
+        } else {
+            Instr::Call { argc, args_span }
+        };
+        self.emit(call, span);
+    }
+}
@@ -0,0 +1,543 @@
+use interface::{
+    nodes::{binaryop::BinaryOp, types::Type, value::Value},
+    Error, Frame, Span, Spanned,
+};
+
+use crate::{
+    builtins::Builtins,
+    limits::{heap_size, Limit, Limits},
+    memo::MemoCache,
+};
+
+use self::bytecode::{Chunk, Instr, Program};
+
+pub mod bytecode;
+pub mod compiler;
+
+/// a call being executed
+struct CallFrame<'c> {
+    chunk: &'c Chunk,
+    ip: usize,
+    /// index of the first local slot of the call
+    base: usize,
+    /// span of the call expression, for stack traces
+    call_span: Span,
+    /// return types of the functions this frame replaced with tail calls, each once, checked on return
+    checks: Vec<&'c Spanned<Type>>,
+    /// calls of `memo` functions made by this frame, whose results are cached on return
+    memos: Vec<(*const Chunk, Vec<Value>)>,
+}
+
+///
+/// a stack machine running compiled programs,
+/// with the same semantics and errors as the tree-walking [crate::interpreter::Interpreter]
+///
+pub struct Vm<'a> {
+    program: &'a Program,
+    builtins: &'a Builtins,
+    limits: Limits,
+    stack: Vec<Value>,
+    locals: Vec<Value>,
+    /// number of expressions evaluated so far
+    steps: u64,
+    /// bytes allocated by values so far
+    heap: usize,
+    /// results of `memo` functions, whose chunks are borrowed for the whole run
+    memo: MemoCache<*const Chunk>,
+    /// the call inlined by the optimiser whose body is being executed, if any;
+    /// inlined bodies make no calls, so it is always the innermost
+    inlined: Option<Frame>,
+}
+
+fn arity_error(span: &Span, name: &str, expected: impl std::fmt::Display, found: usize) -> Error {
+    Error::new(
+        span.clone(),
+        format!(
+            "'{}' called with wrong number of arguments (expected {}, found {})",
+            name, expected, found
+        ),
+    )
+}
+
+impl<'a> Vm<'a> {
+    pub fn new(program: &'a Program, builtins: &'a Builtins) -> Self {
+        Self {
+            program,
+            builtins,
+            limits: Limits::default(),
+            stack: Vec::new(),
+            locals: Vec::new(),
+            steps: 0,
+            heap: 0,
+            memo: MemoCache::new(Limits::default().max_memo_entries),
+            inlined: None,
+        }
+    }
+
+    pub fn with_limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self.memo = MemoCache::new(limits.max_memo_entries);
+        self
+    }
+
+    /// account for a value allocated while executing `span`
+    fn alloc(&mut self, val: &Value, span: &Span) -> Result<(), Error> {
+        self.heap += heap_size(val);
+        match self.limits.max_heap {
+            Some(max) if self.heap > max => Err(Error::new(span.clone(), Limit::Heap(max))),
+            _ => Ok(()),
+        }
+    }
+
+    fn pop(&mut self) -> Value {
+        self.stack.pop().expect("operand stack underflow")
+    }
+
+    fn pop_num(&mut self, span: &Span) -> Result<i32, Error> {
+        self.pop().num_i32(span.clone())
+    }
+
+    ///
+    /// run a chunk, such as the body of a function or an expression,
+    /// with its first local slots set to `args`
+    ///
+    pub fn run(&mut self, chunk: &Chunk, args: Vec<Value>) -> Result<Value, Error> {
+        self.stack.clear();
+        self.locals.clear();
+        self.locals.extend(args);
+        self.locals.resize(chunk.locals, Value::Null);
+        let mut frames = vec![CallFrame {
+            chunk,
+            ip: 0,
+            base: 0,
+            call_span: 0..0,
+            checks: Vec::new(),
+            memos: Vec::new(),
+        }];
+        self.execute(&mut frames).map_err(|mut e| {
+            e.trace.extend(self.inlined.take());
+            // the entry chunk was not called from a script, so it has no frame
+            for frame in frames.iter().skip(1).rev() {
+                let args = &self.locals[frame.base..frame.base + frame.chunk.arity];
+                e.trace.push(Frame {
+                    func: frame.chunk.name.clone(),
+                    call_span: frame.call_span.clone(),
+                    args: args.to_vec(),
+                });
+            }
+            e
+        })
+    }
+
+    /// push a frame for a call to a user function whose arguments are on the stack
+    fn enter<'c>(&mut self, frames: &mut Vec<CallFrame<'c>>, chunk: &'c Chunk, call_span: &Span)
+    where
+        'a: 'c,
+    {
+        let base = self.locals.len();
+        let args = self.stack.len() - chunk.arity;
+        self.locals.extend(self.stack.drain(args..));
+        self.locals.resize(base + chunk.locals, Value::Null);
+        frames.push(CallFrame {
+            chunk,
+            ip: 0,
+            base,
+            call_span: call_span.clone(),
+            checks: Vec::new(),
+            memos: Vec::new(),
+        });
+    }
+
+    /// replace the current frame with a call to a user function whose arguments are on the stack
+    fn tail_call<'c>(&mut self, frames: &mut [CallFrame<'c>], chunk: &'c Chunk, call_span: &Span)
+    where
+        'a: 'c,
+    {
+        let frame = frames.last_mut().unwrap();
+        if let Some(ty) = &frame.chunk.ret_type {
+            // recursion, mutual or not, would check the same annotations over and over
+            if !frame.checks.iter().any(|check| std::ptr::eq(*check, ty)) {
+                frame.checks.push(ty);
+            }
+        }
+        let args = self.stack.len() - chunk.arity;
+        self.locals.truncate(frame.base);
+        self.locals.extend(self.stack.drain(args..));
+        self.locals.resize(frame.base + chunk.locals, Value::Null);
+        frame.chunk = chunk;
+        frame.ip = 0;
+        frame.call_span = call_span.clone();
+    }
+
+    fn check_depth(&self, frames: &[CallFrame], span: &Span) -> Result<(), Error> {
+        match self.limits.max_call_depth {
+            Some(max) if frames.len() > max => Err(Error::new(span.clone(), Limit::CallDepth(max))),
+            _ => Ok(()),
+        }
+    }
+
+    fn execute<'c>(&mut self, frames: &mut Vec<CallFrame<'c>>) -> Result<Value, Error>
+    where
+        'a: 'c,
+    {
+        loop {
+            let frame = frames.last_mut().unwrap();
+            let chunk = frame.chunk;
+            let ip = frame.ip;
+            let base = frame.base;
+            frame.ip += 1;
+            let span = &chunk.code_spans[ip];
+
+            match chunk.code[ip] {
+                Instr::Step => {
+                    self.steps += 1;
+                    if let Some(max) = self.limits.max_steps {
+                        if self.steps > max {
+                            return Err(Error::new(span.clone(), Limit::Steps(max)));
+                        }
+                    }
+                }
+                Instr::Const(i) => {
+                    let val = &chunk.consts[i as usize];
+                    self.alloc(val, span)?;
+                    self.stack.push(val.clone());
+                }
+                Instr::Local(slot) => {
+                    let val = self.locals[base + slot as usize].clone();
+                    self.stack.push(val);
+                }
+                Instr::StoreLocal(slot) => {
+                    let val = self.pop();
+                    self.locals[base + slot as usize] = val;
+                }
+                Instr::Func(name) => {
+                    let name = &chunk.names[name as usize];
+                    if self.program.get(name).is_none() && !self.builtins.contains(name) {
+                        return Err(Error::new(
+                            span.clone(),
+                            format!("No such variable '{}' in scope", name),
+                        ));
+                    }
+                    self.stack.push(Value::Func(name.clone()));
+                }
+                Instr::Pop => {
+                    self.pop();
+                }
+                // popping a value of the wrong type reports it
+                Instr::CheckNum => {
+                    if !matches!(self.stack.last(), Some(Value::I32(_))) {
+                        self.pop_num(span)?;
+                    }
+                }
+                Instr::CheckBool => {
+                    if !matches!(self.stack.last(), Some(Value::Bool(_))) {
+                        self.pop().bool(span.clone())?;
+                    }
+                }
+                Instr::CheckType(ty) => {
+                    let ty = &chunk.types[ty as usize];
+                    self.stack.last().unwrap().check_type(ty, span.clone())?;
+                }
+                Instr::Arith(op, rhs) => {
+                    let rhs = &chunk.spans[rhs as usize];
+                    let y = self.pop_num(rhs)?;
+                    let x = self.pop_num(span)?;
+                    if matches!(op, BinaryOp::Div | BinaryOp::Mod) && y == 0 {
+                        return Err(Error::new(rhs.clone(), "Division by zero"));
+                    }
+                    let res = match op {
+                        BinaryOp::Add => x.checked_add(y),
+                        BinaryOp::Sub => x.checked_sub(y),
+                        BinaryOp::Mul => x.checked_mul(y),
+                        BinaryOp::Div => x.checked_div(y),
+                        BinaryOp::Mod => x.checked_rem(y),
+                        _ => unreachable!(),
+                    };
+                    let res = res.ok_or_else(|| {
+                        Error::new(span.clone(), format!("Integer overflow in '{}'", op))
+                    })?;
+                    self.stack.push(Value::I32(res));
+                }
+                Instr::Compare(op) => {
+                    let y = self.pop_num(span)?;
+                    let x = self.pop_num(span)?;
+                    let res = match op {
+                        BinaryOp::Gt => x > y,
+                        BinaryOp::Geq => x >= y,
+                        BinaryOp::Lt => x < y,
+                        BinaryOp::Leq => x <= y,
+                        _ => unreachable!(),
+                    };
+                    self.stack.push(Value::Bool(res));
+                }
+                Instr::Eq => {
+                    let y = self.pop();
+                    let x = self.pop();
+                    self.stack.push(Value::Bool(x == y));
+                }
+                Instr::Neq => {
+                    let y = self.pop();
+                    let x = self.pop();
+                    self.stack.push(Value::Bool(x != y));
+                }
+                Instr::Jump(target) => frames.last_mut().unwrap().ip = target as usize,
+                Instr::JumpIfFalse(target) => {
+                    if self.stack.last() == Some(&Value::Bool(false)) {
+                        frames.last_mut().unwrap().ip = target as usize;
+                    }
+                }
+                Instr::JumpIfTrue(target) => {
+                    if self.stack.last() == Some(&Value::Bool(true)) {
+                        frames.last_mut().unwrap().ip = target as usize;
+                    }
+                }
+                Instr::Branch(target) => match self.pop() {
+                    Value::Bool(true) => {}
+                    Value::Bool(false) => frames.last_mut().unwrap().ip = target as usize,
+                    c => {
+                        return Err(Error::new(
+                            span.clone(),
+                            format!("Conditions must be booleans, found '{:?}'", c),
+                        ))
+                    }
+                },
+                Instr::CheckDepth => self.check_depth(frames, span)?,
+                Instr::CallFunc(index) => {
+                    let program = self.program;
+                    self.enter(frames, &program.funcs[index as usize], span);
+                }
+                Instr::TailCallFunc(index) => {
+                    let program = self.program;
+                    self.tail_call(frames, &program.funcs[index as usize], span);
+                }
+                Instr::PrepareCall {
+                    argc,
+                    func_span,
+                    args_span,
+                    tail,
+                } => {
+                    let argc = argc as usize;
+                    let args_span = &chunk.spans[args_span as usize];
+                    let name = match self.stack.last() {
+                        Some(Value::Func(name)) => name,
+                        f => {
+                            return Err(Error::new(
+                                chunk.spans[func_span as usize].clone(),
+                                format!("'{:?}' is not callable", f.unwrap()),
+                            ))
+                        }
+                    };
+                    if let Some((_, func)) = self.program.get(name) {
+                        if !tail {
+                            self.check_depth(frames, span)?;
+                        }
+                        if func.arity != argc {
+                            return Err(arity_error(args_span, name, func.arity, argc));
+                        }
+                    } else {
+                        let builtin = self.builtins.get(name).ok_or_else(|| {
+                            Error::new(args_span.clone(), format!("No such function '{}'", name))
+                        })?;
+                        if !builtin.arity.accepts(argc) {
+                            return Err(arity_error(args_span, name, builtin.arity, argc));
+                        }
+                    }
+                }
+                Instr::Call { argc, args_span } | Instr::TailCall { argc, args_span } => {
+                    let args = self.stack.len() - argc as usize;
+                    let name = match &self.stack[args - 1] {
+                        Value::Func(name) => name.clone(),
+                        _ => unreachable!(), // checked by PrepareCall
+                    };
+                    let program = self.program;
+                    if let Some((_, func)) = program.get(&name) {
+                        if let Instr::TailCall { .. } = chunk.code[ip] {
+                            self.tail_call(frames, func, span);
+                            // the callee that was replaced, if any, stays below the frame
+                            self.pop();
+                        } else {
+                            self.enter(frames, func, span);
+                            // the callee stays below the callee's frame until it returns
+                        }
+                        continue;
+                    }
+                    let args_span = &chunk.spans[args_span as usize];
+                    let args = self.stack.split_off(args);
+                    self.pop();
+                    let builtin = self.builtins.get(&name).unwrap();
+                    let val = (builtin.func)(&args).map_err(|e| Error {
+                        span: args_span.clone(),
+                        ..e
+                    })?;
+                    self.alloc(&val, args_span)?;
+                    self.stack.push(val);
+                }
+                Instr::Recall => {
+                    let args = &self.locals[base..base + chunk.arity];
+                    let frame = frames.last_mut().unwrap();
+                    match self.memo.get(&(chunk as *const _), args) {
+                        Some(val) => {
+                            self.stack.push(val.clone());
+                            // the last instruction returns
+                            frame.ip = chunk.code.len() - 1;
+                        }
+                        None => frame.memos.push((chunk, args.to_vec())),
+                    }
+                }
+                Instr::Fail(msg) => {
+                    return Err(Error::new(span.clone(), &chunk.names[msg as usize]))
+                }
+                Instr::Inline { name, argc } => {
+                    let args = self.stack.split_off(self.stack.len() - argc as usize);
+                    self.inlined = Some(Frame {
+                        func: chunk.names[name as usize].clone(),
+                        call_span: span.clone(),
+                        args,
+                    });
+                }
+                Instr::EndInline => self.inlined = None,
+                Instr::Return => {
+                    let val = self.pop();
+                    for (ty, span) in frames.last().unwrap().checks.iter().rev() {
+                        val.check_type(ty, span.clone())?;
+                    }
+                    let frame = frames.pop().unwrap();
+                    // only results that passed the checks are cached
+                    for (chunk, args) in frame.memos {
+                        self.memo.insert(chunk, args, val.clone());
+                    }
+                    self.locals.truncate(frame.base);
+                    if frames.is_empty() {
+                        return Ok(val);
+                    }
+                    // drop the callee of dynamic calls
+                    if let Some(Instr::Call { .. }) =
+                        frames.last().map(|caller| caller.chunk.code[caller.ip - 1])
+                    {
+                        self.pop();
+                    }
+                    self.stack.push(val);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use interface::nodes::value::Value;
+    use std::{cell::RefCell, rc::Rc};
+
+    use analysis::resolver::Resolver;
+
+    use super::{compiler::compile, Vm};
+    use crate::{
+        builtins::Builtins,
+        diagnostic::Diagnostic,
+        engine::{Backend, Engine},
+        frontend::parse_src,
+        limits::Limits,
+        output::CapturedOutput,
+    };
+
+    /// run `main` and capture everything it printed
+    fn run(src: &str, backend: Backend, limits: Limits) -> (Result<Value, Diagnostic>, String) {
+        let output = Rc::new(RefCell::new(CapturedOutput::default()));
+        let mut engine = Engine::with_output(output.clone());
+        engine.set_backend(backend);
+        engine.set_limits(limits);
+        let res = engine
+            .load_source("test.ipu", src)
+            .map_err(|mut errs| errs.remove(0))
+            .and_then(|_| engine.run_main(Vec::new()));
+        let output = output.borrow();
+        (res, format!("{}{}", output.out, output.err))
+    }
+
+    const PROGRAMS: &[&str] = &[
+        include_str!("../../../test_codes/fib.ipu"),
+        include_str!("../../../test_codes/memo_fib.ipu"),
+        "fn main() { var x = 1; var y = x + 2; var x = y * 3; x - y % 2 }",
+        "fn main() { println(1 < 2, 2 <= 2, 3 > 4, 4 >= 5, 1 == 1, \"a\" != \"a\") }",
+        "fn t() { print(\"t\"); 1 == 1 }\nfn f() { print(\"f\"); 1 == 2 }\nfn main() { println(f() && t(), t() || f(), t() && f()) }",
+        "fn apply(f, x) { f(x) }\nfn double(x) { x * 2 }\nfn main() { var g = double; apply(g, 3) + apply(len, \"abc\") }",
+        "fn main() { if 1 < 2 { println(\"yes\") }; if 2 < 1 { 1 } else { 2 } }",
+        "fn main() { var xs = list(1, \"a\", list()); println(\"{} has {} items\", xs, len(xs)); get(xs, 1) }",
+        "fn main() { return 1 + 2 }",
+        "fn main() { x }",
+        "fn main() { var f = 1; f(2) }",
+        "fn f(a) { a }\nfn main() { f(1, 2) }",
+        "fn main() { var f = len; f() }",
+        "fn main() { 1 / (3 - 3) }",
+        "fn main() { 2147483647 * 2 }",
+        "fn main() { if 1 { 2 } }",
+        "fn main() { \"a\" + 1 }",
+        "fn main() { 1 + \"a\" }",
+        "fn main() { 1 < list() }",
+        "fn main() { get(list(), 3) }",
+        "fn f(n) { if n == 0 { 1 / n } else { n * f(n - 1) } }\nfn main() { f(3) }",
+        "fn main() { 1 + main() }",
+        "fn main() { 1 && 2 }",
+        "fn main() { var a = \"a\"; println(a); str(a) }",
+        "fn f(x: int, ys: [str]) -> [str] { ys }\nfn main() { var n: int = len(f(1, list(\"a\"))); f(n, list(1)) }",
+        "fn f(x: int) -> bool { x }\nfn main() { f(1) }",
+        "fn main() { var s: str = 1; s }",
+        "fn count(n, acc) { if n == 0 { acc } else { count(n - 1, acc + 1) } }\nfn main() { count(100000, 0) }",
+        "fn even(n) { if n == 0 { 1 == 1 } else { odd(n - 1) } }\nfn odd(n) { if n == 0 { 1 == 2 } else { var f = even; f(n - 1) } }\nfn main() { println(even(10001)); odd(4) }",
+        "fn f(n) -> int { if n == 0 { \"done\" } else { g(n - 1) } }\nfn g(n) { f(n) }\nfn main() { f(3) }",
+        "fn f(n) -> int { if n == 0 { \"done\" } else { g(n - 1) } }\nfn g(n) -> int { f(n) }\nfn main() { f(100000) }",
+        "fn f(n) { if n == 0 { x(n) } else { f(n - 1) } }\nfn x(n) { 1 / n }\nfn main() { 1 + f(3) }",
+    ];
+
+    #[test]
+    fn backends_agree() {
+        for src in PROGRAMS {
+            assert_eq!(
+                run(src, Backend::Vm, Limits::default()),
+                run(src, Backend::Tree, Limits::default()),
+                "{}",
+                src
+            );
+        }
+    }
+
+    #[test]
+    fn backends_agree_on_limits() {
+        let src = "fn f(n) { if n == 0 { list() } else { list(\"item\", f(n - 1)) } }\nfn main() { f(50) }";
+        for limits in [
+            Limits {
+                max_call_depth: Some(10),
+                ..Limits::default()
+            },
+            Limits {
+                max_heap: Some(500),
+                ..Limits::default()
+            },
+            Limits {
+                max_steps: Some(300),
+                ..Limits::default()
+            },
+        ] {
+            let (res, _) = run(src, Backend::Vm, limits);
+            assert!(res.is_err());
+            assert_eq!(res, run(src, Backend::Tree, limits).0);
+        }
+    }
+
+    #[test]
+    fn caches_only_checked_results() {
+        let src = "memo fn m(n) { g(n) }\nfn g(n) -> int { str(n) }";
+        let funcs = parse_src(src).funcs.unwrap();
+        let builtins = Builtins::with_std(Rc::new(RefCell::new(CapturedOutput::default())));
+        let mut resolver = Resolver::new();
+        builtins.names().for_each(|name| resolver.builtin(name));
+        resolver.funcs(&funcs);
+        let program = compile(&funcs, &resolver.resolve_funcs(&funcs), None);
+        let (_, chunk) = program.get("m").unwrap();
+        let mut vm = Vm::new(&program, &builtins);
+        for _ in 0..2 {
+            let err = vm.run(chunk, vec![Value::I32(1)]).unwrap_err();
+            assert_eq!(err.msg, "'1' is not of type int");
+        }
+    }
+}
@@ -0,0 +1,120 @@
+//!
+//! golden-file tests: every program in `test_codes/` is lexed, parsed and run,
+//! and what each stage produced is compared against the `.expected` file next to it.
+//!
+//! Run with `BLESS=1` to write the `.expected` files instead.
+//!
+
+use interpreter::{
+    error_reporter::render, frontend::parse_src, output::CapturedOutput, Diagnostic, Engine,
+};
+use std::{
+    cell::RefCell,
+    collections::BTreeMap,
+    env, fs,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+/// what each stage produced for a program, as it is written to its `.expected` file
+fn golden(name: &str, src: &str) -> String {
+    let parsed = parse_src(src);
+    let tokens = parsed
+        .tokens
+        .iter()
+        .flatten()
+        .map(|(tok, span)| format!("{} {:?}\n", tok, span))
+        .collect::<String>();
+    // sort functions by name so that dumps are stable
+    let funcs = parsed.funcs.iter().flatten().collect::<BTreeMap<_, _>>();
+    let ast = format!("{:#?}\n", funcs);
+
+    let output = Rc::new(RefCell::new(CapturedOutput::default()));
+    let mut engine = Engine::with_output(output.clone());
+    let (result, diags) = match engine.load_source(name, src) {
+        Ok(()) => match engine.run_main(Vec::new()) {
+            Ok(val) => (format!("{}\n", val), Vec::new()),
+            Err(e) => ("(failed)\n".to_string(), vec![e]),
+        },
+        Err(errs) => ("(not run)\n".to_string(), errs),
+    };
+    let diags = engine
+        .take_warnings()
+        .iter()
+        .chain(&diags)
+        .map(|diag: &Diagnostic| render(engine.sources(), diag, false))
+        .collect::<String>();
+    let stdout = output.borrow().out.clone();
+
+    [
+        ("tokens", tokens),
+        ("ast", ast),
+        ("stdout", stdout),
+        ("result", result),
+        ("diagnostics", diags),
+    ]
+    .iter()
+    .map(|(section, text)| format!("== {} ==\n{}", section, text))
+    .collect()
+}
+
+/// programs in a directory, in a stable order
+fn programs(dir: &Path) -> Vec<PathBuf> {
+    let mut paths = fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "ipu"))
+        .collect::<Vec<_>>();
+    paths.sort();
+    paths
+}
+
+///
+/// compare every program in `dir` against its `.expected` file,
+/// or write the files if `bless` is set
+///
+fn check_dir(dir: &Path, bless: bool) {
+    let mut mismatches = Vec::new();
+    for path in programs(dir) {
+        let name = path.file_name().unwrap().to_string_lossy().to_string();
+        let src = fs::read_to_string(&path).unwrap();
+        let actual = golden(&name, &src);
+        let expected_path = path.with_extension("expected");
+        if bless {
+            fs::write(&expected_path, actual).unwrap();
+            continue;
+        }
+        match fs::read_to_string(&expected_path) {
+            Ok(expected) if expected == actual => {}
+            Ok(expected) => {
+                // the first line that differs is usually enough to tell what changed
+                let (line, (want, got)) = expected
+                    .lines()
+                    .chain(std::iter::repeat(""))
+                    .zip(actual.lines().chain(std::iter::repeat("")))
+                    .enumerate()
+                    .find(|(_, (want, got))| want != got)
+                    .unwrap();
+                mismatches.push(format!(
+                    "{}:{}: expected `{}`, found `{}`",
+                    expected_path.display(),
+                    line + 1,
+                    want,
+                    got
+                ));
+            }
+            Err(_) => mismatches.push(format!("{} is missing", expected_path.display())),
+        }
+    }
+    assert!(
+        mismatches.is_empty(),
+        "golden files differ, rerun with BLESS=1 to update them:\n{}",
+        mismatches.join("\n")
+    );
+}
+
+#[test]
+fn test_codes() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("../test_codes");
+    check_dir(&dir, env::var_os("BLESS").is_some());
+}
@@ -1,28 +1,26 @@
-use chumsky::prelude::Simple;
-use itertools::Itertools;
-use tower_lsp::lsp_types::{Diagnostic, Position, Range};
+use interpreter::diagnostic::{self, Severity};
+use tower_lsp::lsp_types::{Diagnostic, DiagnosticSeverity, Position, Range};
 
-pub fn to_diagnostics(line: usize, err: &Simple<char>) -> Diagnostic {
-    let span = err.span();
-    let expected = err.expected().filter_map(|s| *s).join(", ");
-    let found = err.found().unwrap();
+use super::navigation::to_range;
+
+pub fn to_diagnostic(src: &str, diag: &diagnostic::Diagnostic) -> Diagnostic {
+    // problems that are not about any place in particular go at the start
+    let range = diag.location().map_or_else(
+        || Range::new(Position::new(0, 0), Position::new(0, 0)),
+        |(_, span)| to_range(src, span),
+    );
+    let severity = match diag.severity {
+        Severity::Error => DiagnosticSeverity::Error,
+        Severity::Warning => DiagnosticSeverity::Warning,
+    };
 
     Diagnostic {
-        range: Range {
-            start: Position {
-                line: line as u32,
-                character: span.start as u32,
-            },
-            end: Position {
-                line: line as u32,
-                character: span.end as u32,
-            },
-        },
-        severity: None,
+        range,
+        severity: Some(severity),
         code: None,
         code_description: None,
         source: Some("source".to_string()),
-        message: format!("expected {:?} but {:?}", expected, found).to_string(),
+        message: diag.message.clone(),
         related_information: None,
         tags: None,
         data: None,
@@ -1,15 +1,18 @@
-use parser::lexer::lex;
-use tower_lsp::lsp_types::{Diagnostic, Position, Range, SemanticToken};
+use interpreter::{db::Database, diagnostic::Severity};
+use tower_lsp::lsp_types::{Diagnostic, Range, SemanticToken};
 
 use crate::{
-    analyzer::{diagnostics::to_diagnostics, semantic_tokens::to_token_type},
+    analyzer::{diagnostics::to_diagnostic, navigation::to_range, semantic_tokens::to_token_type},
     globals::TOKEN_TYPES,
 };
 
 use self::semantic_tokens::to_semantic_tokens;
 
 mod diagnostics;
+pub mod navigation;
 mod semantic_tokens;
+pub mod sync;
+pub mod type_hints;
 
 #[derive(Debug, Clone)]
 pub struct RangedTokenType {
@@ -19,46 +22,39 @@ pub struct RangedTokenType {
     pub range: Range,
 }
 
-/// convert source to tokens and diagnostics
-/// TODO: ASTを作っていないので全然semanticじゃない
-/// TODO: line毎にlexしている
-pub fn analyze_src(src: String) -> (Vec<SemanticToken>, Vec<Diagnostic>) {
+///
+/// semantic tokens and diagnostics of a source, which must be in the database;
+/// lints and type errors are only reported once there are no other errors
+///
+pub fn analyze_src(db: &mut Database, file: &str) -> (Vec<SemanticToken>, Vec<Diagnostic>) {
     let map = &mut TOKEN_TYPES.lock().unwrap();
-    let mut diagnostics: Vec<Diagnostic> = vec![];
-    let mut ranged_types: Vec<RangedTokenType> = vec![];
-    src.lines().into_iter().enumerate().for_each(|(i, line)| {
-        let (tokens, errs) = lex(format!("{}\n", line).as_str());
-        log::debug!("{:?}", errs);
-        let diag_errs = errs
-            .iter()
-            .map(|err| to_diagnostics(i, err))
-            .collect::<Vec<_>>();
-        let types = tokens
-            .unwrap_or(vec![])
-            .into_iter()
-            .filter_map(|(tok, pos)| {
-                if let Some(key) = to_token_type(&tok) {
-                    Some(RangedTokenType {
-                        range: Range {
-                            start: Position {
-                                line: i as u32,
-                                character: pos.start as u32,
-                            },
-                            end: Position {
-                                line: i as u32,
-                                character: pos.end as u32,
-                            },
-                        },
-                        token_type: *map.get(&key).unwrap(),
-                    })
-                } else {
-                    None
-                }
+    let src = db.source(file).unwrap();
+    let lexed = db.tokens(file);
+    let ranged_types = lexed
+        .tokens
+        .iter()
+        .flatten()
+        .filter_map(|(tok, span)| {
+            to_token_type(tok).map(|key| RangedTokenType {
+                range: to_range(&src, span),
+                token_type: *map.get(&key).unwrap(),
             })
-            .collect::<Vec<_>>();
-        ranged_types.extend(types);
-        diagnostics.extend(diag_errs);
-    });
+        })
+        .collect::<Vec<_>>();
+
+    let mut diags = db.diagnostics(file).to_vec();
+    if diags.iter().all(|diag| diag.severity == Severity::Warning) {
+        diags.extend(db.lints(file).iter().cloned());
+        let types = db.types(file);
+        diags.extend(
+            types
+                .errors
+                .iter()
+                .map(|e| interpreter::Diagnostic::from_error(file, e.clone())),
+        );
+    }
+    log::debug!("{:?}", diags);
+    let diagnostics = diags.iter().map(|diag| to_diagnostic(&src, diag)).collect();
 
     ranged_types.iter().for_each(|t| {
         log::debug!(
@@ -0,0 +1,134 @@
+use analysis::resolver::Resolution;
+use interface::Span;
+use interpreter::db::Database;
+use std::sync::Arc;
+use tower_lsp::lsp_types::{Position, Range};
+
+///
+/// the names in a source resolved against each other and the standard builtins,
+/// if it parses
+///
+fn resolve_src(db: &mut Database, file: &str) -> Option<Arc<Resolution>> {
+    db.ast(file).funcs.as_ref()?;
+    Some(db.resolution(file))
+}
+
+///
+/// char offset of a position, whose character counts UTF-16 code units as in the protocol;
+/// a position inside a char is that of the char after it
+///
+pub fn to_offset(src: &str, pos: Position) -> usize {
+    let mut line = 0;
+    let mut character = 0;
+    for (i, c) in src.chars().enumerate() {
+        if line == pos.line && character >= pos.character {
+            return i;
+        }
+        if c == '\n' {
+            if line == pos.line {
+                return i;
+            }
+            line += 1;
+            character = 0;
+        } else {
+            character += c.len_utf16() as u32;
+        }
+    }
+    src.chars().count()
+}
+
+/// position of a char offset, counting UTF-16 code units in the line
+pub fn to_position(src: &str, offset: usize) -> Position {
+    let mut pos = Position::new(0, 0);
+    for c in src.chars().take(offset) {
+        if c == '\n' {
+            pos.line += 1;
+            pos.character = 0;
+        } else {
+            pos.character += c.len_utf16() as u32;
+        }
+    }
+    pos
+}
+
+pub fn to_range(src: &str, span: &Span) -> Range {
+    Range::new(to_position(src, span.start), to_position(src, span.end))
+}
+
+///
+/// where the name at a position is defined
+///
+pub fn definition(db: &mut Database, file: &str, pos: Position) -> Option<Range> {
+    let src = db.source(file)?;
+    let src = &*src;
+    let res = resolve_src(db, file)?;
+    let def = res.lookup(to_offset(src, pos))?;
+    res.defs[def].span.as_ref().map(|span| to_range(src, span))
+}
+
+///
+/// every use of the name at a position, and its definition if asked for
+///
+pub fn references(
+    db: &mut Database,
+    file: &str,
+    pos: Position,
+    include_declaration: bool,
+) -> Vec<Range> {
+    let src = match db.source(file) {
+        Some(src) => src,
+        None => return vec![],
+    };
+    let src = &*src;
+    let res = match resolve_src(db, file) {
+        Some(res) => res,
+        None => return vec![],
+    };
+    let def = match res.lookup(to_offset(src, pos)) {
+        Some(def) => def,
+        None => return vec![],
+    };
+    let decl = res.defs[def].span.clone().filter(|_| include_declaration);
+    decl.into_iter()
+        .chain(res.references(def))
+        .map(|span| to_range(src, &span))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use interpreter::db::Database;
+    use tower_lsp::lsp_types::{Position, Range};
+
+    use super::{definition, references};
+
+    #[test]
+    fn finds_definitions_and_references() {
+        let mut db = Database::new();
+        db.set_source(
+            "a.ipu",
+            "fn double(x) { x * 2 }\r\nfn main() { double(double(1)) }",
+        );
+        let db = &mut db;
+        let range =
+            |line, start, end| Range::new(Position::new(line, start), Position::new(line, end));
+
+        assert_eq!(
+            definition(db, "a.ipu", Position::new(0, 16)),
+            Some(range(0, 10, 11))
+        );
+        assert_eq!(
+            definition(db, "a.ipu", Position::new(1, 14)),
+            Some(range(0, 3, 9))
+        );
+        assert_eq!(definition(db, "a.ipu", Position::new(1, 0)), None);
+        assert_eq!(
+            references(db, "a.ipu", Position::new(0, 5), true),
+            vec![range(0, 3, 9), range(1, 12, 18), range(1, 19, 25)]
+        );
+        assert_eq!(
+            references(db, "a.ipu", Position::new(0, 15), false),
+            vec![range(0, 15, 16)]
+        );
+    }
+}
@@ -44,11 +44,12 @@ impl RangedTokenType {
 
 pub fn to_token_type(token: &Token) -> Option<String> {
     match token {
-        Token::Var | Token::Fn | Token::If | Token::Else | Token::Return => {
+        Token::Var | Token::Fn | Token::Memo | Token::If | Token::Else | Token::Return => {
             Some("keyword".to_string())
         }
         Token::Ident(_) => Some("variable".to_string()),
         Token::Num(_) => Some("number".to_string()),
+        Token::Str(_) => Some("string".to_string()),
         Token::Op(_) => Some("operator".to_string()),
         Token::Comment(_) => Some("comment".to_string()),
         _ => None,
@@ -57,7 +58,10 @@ pub fn to_token_type(token: &Token) -> Option<String> {
 
 /// calc relative position to create semantic tokens
 pub fn to_semantic_tokens(types: Vec<RangedTokenType>) -> Vec<SemanticToken> {
-    vec![
+    if types.is_empty() {
+        return vec![];
+    }
+    [
         vec![types[0].semantic_token()],
         types
             .into_iter()
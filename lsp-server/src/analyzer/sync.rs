@@ -0,0 +1,66 @@
+use interpreter::db::Database;
+use parser::incremental::Edit;
+use tower_lsp::lsp_types::TextDocumentContentChangeEvent;
+
+use super::navigation::to_offset;
+
+///
+/// apply the changes to an open document, each to the document
+/// as the changes before it left it
+///
+pub fn apply_changes(db: &mut Database, file: &str, changes: Vec<TextDocumentContentChangeEvent>) {
+    for change in changes {
+        match (change.range, db.source(file)) {
+            (Some(range), Some(src)) => {
+                let range = to_offset(&src, range.start)..to_offset(&src, range.end);
+                db.edit_source(file, &Edit::new(range, &change.text));
+            }
+            // a change without a range is the whole document
+            _ => db.set_source(file, &change.text),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use interpreter::db::Database;
+    use tower_lsp::lsp_types::{Position, Range, TextDocumentContentChangeEvent};
+
+    use super::apply_changes;
+
+    fn change(line: u32, start: u32, end: u32, text: &str) -> TextDocumentContentChangeEvent {
+        TextDocumentContentChangeEvent {
+            range: Some(Range::new(
+                Position::new(line, start),
+                Position::new(line, end),
+            )),
+            range_length: None,
+            text: text.to_string(),
+        }
+    }
+
+    #[test]
+    fn counts_positions_in_utf16() {
+        let mut db = Database::new();
+        db.set_source("a.ipu", "fn main() {\n    println(\"😀\"); x\n}\n");
+        // the emoji is two code units, so `x` is at 19 rather than 18
+        apply_changes(
+            &mut db,
+            "a.ipu",
+            vec![change(1, 19, 20, "1"), change(1, 15, 15, "!")],
+        );
+        assert_eq!(
+            db.source("a.ipu").as_deref(),
+            Some("fn main() {\n    println(\"😀!\"); 1\n}\n")
+        );
+        assert!(db.diagnostics("a.ipu").is_empty());
+
+        let whole = TextDocumentContentChangeEvent {
+            range: None,
+            range_length: None,
+            text: "fn main() { 2 }".to_string(),
+        };
+        apply_changes(&mut db, "a.ipu", vec![whole]);
+        assert_eq!(db.source("a.ipu").as_deref(), Some("fn main() { 2 }"));
+    }
+}
@@ -0,0 +1,76 @@
+use interpreter::db::Database;
+use tower_lsp::lsp_types::{Position, Range};
+
+use super::navigation::{to_offset, to_range};
+
+///
+/// the name at a position and its inferred type, such as `n: int`
+///
+pub fn hover(db: &mut Database, file: &str, pos: Position) -> Option<(Range, String)> {
+    let src = db.source(file)?;
+    let src = &*src;
+    db.ast(file).funcs.as_ref()?;
+    let types = db.types(file);
+    let (span, ty) = types.at(to_offset(src, pos))?;
+    let name = src
+        .chars()
+        .skip(span.start)
+        .take(span.end - span.start)
+        .collect::<String>();
+    Some((to_range(src, span), format!("{}: {}", name, ty)))
+}
+
+///
+/// the inferred type of each function, shown next to its name
+///
+pub fn signatures(db: &mut Database, file: &str) -> Vec<(Range, String)> {
+    let (src, ast) = match db.source(file) {
+        Some(src) => (src, db.ast(file)),
+        None => return vec![],
+    };
+    let funcs = match &ast.funcs {
+        Some(funcs) => funcs,
+        None => return vec![],
+    };
+    let types = db.types(file);
+    let mut sigs = funcs
+        .iter()
+        .map(|(name, func)| {
+            (
+                func.name_span.clone(),
+                format!("{}: {}", name, types.funcs[name]),
+            )
+        })
+        .collect::<Vec<_>>();
+    sigs.sort_by_key(|(span, _)| span.start);
+    sigs.into_iter()
+        .map(|(span, sig)| (to_range(&src, &span), sig))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use interpreter::db::Database;
+    use tower_lsp::lsp_types::{Position, Range};
+
+    use super::{hover, signatures};
+
+    #[test]
+    fn shows_inferred_types() {
+        let mut db = Database::new();
+        db.set_source("a.ipu", "fn inc(n) { n + 1 }\nfn main() { inc(1) }");
+        let range =
+            |line, start, end| Range::new(Position::new(line, start), Position::new(line, end));
+        assert_eq!(
+            hover(&mut db, "a.ipu", Position::new(0, 12)),
+            Some((range(0, 12, 13), "n: int".to_string()))
+        );
+        assert_eq!(
+            signatures(&mut db, "a.ipu"),
+            vec![
+                (range(0, 3, 6), "inc: fn(int) -> int".to_string()),
+                (range(1, 3, 7), "main: fn() -> int".to_string()),
+            ]
+        );
+    }
+}
@@ -1,15 +1,35 @@
-use std::fs::File;
-use std::io::prelude::*;
+use interpreter::{db::Database, lints::find_config};
+use std::sync::Mutex;
 use tower_lsp::jsonrpc::Result;
 use tower_lsp::lsp_types::*;
 use tower_lsp::{Client, LanguageServer};
 
 use crate::analyzer::analyze_src;
+use crate::analyzer::navigation::{definition, references};
+use crate::analyzer::sync::apply_changes;
+use crate::analyzer::type_hints::{hover, signatures};
 use crate::globals::TOKEN_TYPES;
 
-#[derive(Debug)]
 pub struct Backend {
     pub client: Client,
+    /// open documents, and what has been worked out about them
+    pub db: Mutex<Database>,
+}
+
+impl Backend {
+    ///
+    /// run `f` on a document, reading it from disk unless it is open;
+    /// `None` if it can be read from neither
+    ///
+    fn with_file<T>(&self, uri: &Url, f: impl FnOnce(&mut Database, &str) -> T) -> Option<T> {
+        let mut db = self.db.lock().unwrap();
+        let file = uri.as_str();
+        if db.source(file).is_none() {
+            let src = std::fs::read_to_string(uri.to_file_path().ok()?).ok()?;
+            db.set_source(file, &src);
+        }
+        Some(f(&mut db, file))
+    }
 }
 
 #[tower_lsp::async_trait]
@@ -38,7 +58,7 @@ impl LanguageServer for Backend {
             server_info: None,
             capabilities: ServerCapabilities {
                 text_document_sync: Some(TextDocumentSyncCapability::Kind(
-                    TextDocumentSyncKind::Full,
+                    TextDocumentSyncKind::Incremental,
                 )),
                 completion_provider: Some(CompletionOptions {
                     resolve_provider: Some(false),
@@ -61,12 +81,18 @@ impl LanguageServer for Backend {
                         },
                     ),
                 ),
+                definition_provider: Some(OneOf::Left(true)),
+                references_provider: Some(OneOf::Left(true)),
+                hover_provider: Some(HoverProviderCapability::Simple(true)),
+                code_lens_provider: Some(CodeLensOptions {
+                    resolve_provider: Some(false),
+                }),
                 ..ServerCapabilities::default()
             },
         });
     }
 
-    async fn initialized(&self, params: InitializedParams) {
+    async fn initialized(&self, _: InitializedParams) {
         log::debug!("server initialized");
 
         // self.client
@@ -80,15 +106,30 @@ impl LanguageServer for Backend {
     }
 
     async fn did_open(&self, params: DidOpenTextDocumentParams) {
-        log::debug!("did_open");
+        log::debug!("did_open: {:?}", &params.text_document.uri);
+        let doc = params.text_document;
+        self.db
+            .lock()
+            .unwrap()
+            .set_source(doc.uri.as_str(), &doc.text);
     }
 
     async fn did_close(&self, params: DidCloseTextDocumentParams) {
         log::debug!("did_close: {:?}", &params.text_document.uri);
+        // closed documents are read from disk again when asked about
+        self.db
+            .lock()
+            .unwrap()
+            .remove_source(params.text_document.uri.as_str());
     }
 
     async fn did_change(&self, params: DidChangeTextDocumentParams) {
         log::debug!("did_change: {:?}", &params.text_document.uri);
+        apply_changes(
+            &mut self.db.lock().unwrap(),
+            params.text_document.uri.as_str(),
+            params.content_changes,
+        );
     }
 
     async fn completion(&self, params: CompletionParams) -> Result<Option<CompletionResponse>> {
@@ -109,14 +150,14 @@ impl LanguageServer for Backend {
     ) -> Result<Option<SemanticTokensResult>> {
         log::debug!("semantic_tokens_full: {:?}", &params);
         // let tokens: Vec<SemanticToken> = vec![];
-        let path = params.text_document.uri.to_file_path().unwrap();
-        log::debug!("open file: {:?}, exists: {:?}", path, path.exists());
-
-        if let Ok(mut f) = File::open(&path) {
-            let mut contents = String::new();
-            f.read_to_string(&mut contents).unwrap();
-            let (data, diagnostics) = analyze_src(contents);
-
+        let uri = &params.text_document.uri;
+        if let Ok(path) = uri.to_file_path() {
+            match find_config(&path) {
+                Ok(config) => self.db.lock().unwrap().set_lint_config(config),
+                Err(e) => log::warn!("{}", e.message),
+            }
+        }
+        if let Some((data, diagnostics)) = self.with_file(uri, analyze_src) {
             log::debug!("diagnostics: {:?}", diagnostics);
 
             self.client
@@ -131,11 +172,63 @@ impl LanguageServer for Backend {
         Ok(None)
     }
 
-    async fn hover(&self, _: HoverParams) -> Result<Option<Hover>> {
-        log::debug!("hover");
-        Ok(Some(Hover {
-            contents: HoverContents::Scalar(MarkedString::String("You're hovering!".to_string())),
-            range: None,
+    async fn goto_definition(
+        &self,
+        params: GotoDefinitionParams,
+    ) -> Result<Option<GotoDefinitionResponse>> {
+        log::debug!("goto_definition: {:?}", &params);
+        let params = params.text_document_position_params;
+        let uri = params.text_document.uri;
+        let range = self.with_file(&uri, |db, file| definition(db, file, params.position));
+        Ok(range
+            .flatten()
+            .map(|range| GotoDefinitionResponse::Scalar(Location::new(uri, range))))
+    }
+
+    async fn references(&self, params: ReferenceParams) -> Result<Option<Vec<Location>>> {
+        log::debug!("references: {:?}", &params);
+        let uri = params.text_document_position.text_document.uri;
+        let ranges = self.with_file(&uri, |db, file| {
+            references(
+                db,
+                file,
+                params.text_document_position.position,
+                params.context.include_declaration,
+            )
+        });
+        Ok(ranges.map(|ranges| {
+            ranges
+                .into_iter()
+                .map(|range| Location::new(uri.clone(), range))
+                .collect()
+        }))
+    }
+
+    async fn hover(&self, params: HoverParams) -> Result<Option<Hover>> {
+        log::debug!("hover: {:?}", &params);
+        let params = params.text_document_position_params;
+        let hovered = self.with_file(&params.text_document.uri, |db, file| {
+            hover(db, file, params.position)
+        });
+        Ok(hovered.flatten().map(|(range, ty)| Hover {
+            contents: HoverContents::Scalar(MarkedString::String(ty)),
+            range: Some(range),
         }))
     }
+
+    // inlay hints are not in this version of the protocol types, so signatures are code lenses
+    async fn code_lens(&self, params: CodeLensParams) -> Result<Option<Vec<CodeLens>>> {
+        log::debug!("code_lens: {:?}", &params);
+        Ok(self
+            .with_file(&params.text_document.uri, signatures)
+            .map(|sigs| {
+                sigs.into_iter()
+                    .map(|(range, sig)| CodeLens {
+                        range,
+                        command: Some(Command::new(sig, String::new(), None)),
+                        data: None,
+                    })
+                    .collect()
+            }))
+    }
 }
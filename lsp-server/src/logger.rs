@@ -6,7 +6,6 @@ pub fn init_logger() {
     let log_path = format!("lsp-{}.log", timestamp);
     let path = Path::new(&log_path);
     let file = OpenOptions::new()
-        .write(true)
         .create(true)
         .append(true)
         .open(path)
@@ -1,5 +1,7 @@
+use interpreter::db::Database;
 use language_server::Backend;
 use logger::init_logger;
+use std::sync::Mutex;
 use tower_lsp::{LspService, Server};
 
 mod analyzer;
@@ -14,7 +16,10 @@ async fn main() {
     let stdin = tokio::io::stdin();
     let stdout = tokio::io::stdout();
 
-    let (service, messages) = LspService::new(|client| Backend { client });
+    let (service, messages) = LspService::new(|client| Backend {
+        client,
+        db: Mutex::new(Database::new()),
+    });
     Server::new(stdin, stdout)
         .interleave(messages)
         .serve(service)
@@ -0,0 +1,44 @@
+//!
+//! how long an edit takes to reparse in a large program, against parsing it from scratch
+//!
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use parser::{
+    cst::parse_cst,
+    incremental::{reparse, Edit},
+};
+
+/// a program of `funcs` functions of about five lines each
+fn program(funcs: usize) -> String {
+    (0..funcs)
+        .map(|i| {
+            format!(
+                "// step {0}\nfn f{0}(n: int) -> int {{\n    var x = n * {0} + 1;\n    if x > 100 {{ f{1}(x - 100) }} else {{ x }}\n}}\n\n",
+                i,
+                i.saturating_sub(1)
+            )
+        })
+        .collect()
+}
+
+fn bench(c: &mut Criterion) {
+    for funcs in [100, 1000] {
+        let src = program(funcs);
+        let tree = parse_cst(&src);
+        // typing a digit in the middle of the program
+        // the program is ascii, so its bytes are its chars
+        let at = src.find(&format!("n * {} ", funcs / 2)).unwrap() + 4;
+        let edit = Edit::new(at..at, "7");
+
+        let lines = src.lines().count();
+        c.bench_function(&format!("parse {} lines", lines), |b| {
+            b.iter(|| parse_cst(black_box(&src)))
+        });
+        c.bench_function(&format!("reparse {} lines", lines), |b| {
+            b.iter(|| reparse(black_box(&tree), black_box(&edit)))
+        });
+    }
+}
+
+criterion_group!(benches, bench);
+criterion_main!(benches);
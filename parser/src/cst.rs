@@ -0,0 +1,714 @@
+//!
+//! lossless concrete syntax tree, which keeps every byte of the source,
+//! whitespace and comments included, so that tools can rewrite programs
+//! without disturbing what they do not touch
+//!
+
+use interface::{tokens::Token, Spanned};
+use rowan::{GreenNode, GreenNodeBuilder};
+
+use crate::lexer::lex;
+
+/// kinds of the tokens and nodes of the tree
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[repr(u16)]
+pub enum SyntaxKind {
+    // trivia
+    Whitespace,
+    Comment,
+
+    // tokens
+    Number,
+    String,
+    Ident,
+    Op,
+    LParen,
+    RParen,
+    LBrack,
+    RBrack,
+    LBrace,
+    RBrace,
+    Semi,
+    Comma,
+    Colon,
+    Hash,
+    FnKw,
+    MemoKw,
+    VarKw,
+    ReturnKw,
+    IfKw,
+    ElseKw,
+
+    // nodes
+    Fn,
+    Attr,
+    ParamList,
+    Param,
+    RetType,
+    Type,
+    Block,
+    VarStmt,
+    Literal,
+    NameRef,
+    ParenExpr,
+    BinaryExpr,
+    CallExpr,
+    ArgList,
+    IfExpr,
+    ReturnExpr,
+    /// source that could not be lexed or parsed, either a token or a node
+    Error,
+    Root,
+}
+
+impl SyntaxKind {
+    /// every kind, in the order of their discriminants
+    const ALL: [SyntaxKind; 40] = [
+        SyntaxKind::Whitespace,
+        SyntaxKind::Comment,
+        SyntaxKind::Number,
+        SyntaxKind::String,
+        SyntaxKind::Ident,
+        SyntaxKind::Op,
+        SyntaxKind::LParen,
+        SyntaxKind::RParen,
+        SyntaxKind::LBrack,
+        SyntaxKind::RBrack,
+        SyntaxKind::LBrace,
+        SyntaxKind::RBrace,
+        SyntaxKind::Semi,
+        SyntaxKind::Comma,
+        SyntaxKind::Colon,
+        SyntaxKind::Hash,
+        SyntaxKind::FnKw,
+        SyntaxKind::MemoKw,
+        SyntaxKind::VarKw,
+        SyntaxKind::ReturnKw,
+        SyntaxKind::IfKw,
+        SyntaxKind::ElseKw,
+        SyntaxKind::Fn,
+        SyntaxKind::Attr,
+        SyntaxKind::ParamList,
+        SyntaxKind::Param,
+        SyntaxKind::RetType,
+        SyntaxKind::Type,
+        SyntaxKind::Block,
+        SyntaxKind::VarStmt,
+        SyntaxKind::Literal,
+        SyntaxKind::NameRef,
+        SyntaxKind::ParenExpr,
+        SyntaxKind::BinaryExpr,
+        SyntaxKind::CallExpr,
+        SyntaxKind::ArgList,
+        SyntaxKind::IfExpr,
+        SyntaxKind::ReturnExpr,
+        SyntaxKind::Error,
+        SyntaxKind::Root,
+    ];
+
+    /// whitespace and comments, which carry no meaning
+    pub fn is_trivia(self) -> bool {
+        matches!(self, SyntaxKind::Whitespace | SyntaxKind::Comment)
+    }
+
+    fn of_token(tok: &Token) -> Self {
+        match tok {
+            Token::Num(_) => SyntaxKind::Number,
+            Token::Str(_) => SyntaxKind::String,
+            Token::Op(_) => SyntaxKind::Op,
+            Token::Ctrl('(') => SyntaxKind::LParen,
+            Token::Ctrl(')') => SyntaxKind::RParen,
+            Token::Ctrl('[') => SyntaxKind::LBrack,
+            Token::Ctrl(']') => SyntaxKind::RBrack,
+            Token::Ctrl('{') => SyntaxKind::LBrace,
+            Token::Ctrl('}') => SyntaxKind::RBrace,
+            Token::Ctrl(';') => SyntaxKind::Semi,
+            Token::Ctrl(',') => SyntaxKind::Comma,
+            Token::Ctrl(':') => SyntaxKind::Colon,
+            Token::Ctrl('#') => SyntaxKind::Hash,
+            Token::Ctrl(_) => SyntaxKind::Error,
+            Token::Ident(_) => SyntaxKind::Ident,
+            Token::Fn => SyntaxKind::FnKw,
+            Token::Memo => SyntaxKind::MemoKw,
+            Token::Var => SyntaxKind::VarKw,
+            Token::Return => SyntaxKind::ReturnKw,
+            Token::If => SyntaxKind::IfKw,
+            Token::Else => SyntaxKind::ElseKw,
+            Token::Comment(_) => SyntaxKind::Comment,
+        }
+    }
+}
+
+impl From<SyntaxKind> for rowan::SyntaxKind {
+    fn from(kind: SyntaxKind) -> Self {
+        Self(kind as u16)
+    }
+}
+
+/// the language of the tree, for rowan
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Lang {}
+
+impl rowan::Language for Lang {
+    type Kind = SyntaxKind;
+
+    fn kind_from_raw(raw: rowan::SyntaxKind) -> SyntaxKind {
+        SyntaxKind::ALL[raw.0 as usize]
+    }
+
+    fn kind_to_raw(kind: SyntaxKind) -> rowan::SyntaxKind {
+        kind.into()
+    }
+}
+
+pub type SyntaxNode = rowan::SyntaxNode<Lang>;
+pub type SyntaxToken = rowan::SyntaxToken<Lang>;
+pub type SyntaxElement = rowan::SyntaxElement<Lang>;
+
+///
+/// whitespace and comments just before a token, nearest last
+///
+pub fn leading_trivia(token: &SyntaxToken) -> Vec<SyntaxToken> {
+    let mut trivia = std::iter::successors(token.prev_token(), |tok| tok.prev_token())
+        .take_while(|tok| tok.kind().is_trivia())
+        .collect::<Vec<_>>();
+    trivia.reverse();
+    trivia
+}
+
+/// the token the lexer made of a token of the tree, `None` for whitespace and skipped text
+pub fn lexed_token(tok: &SyntaxToken) -> Option<Token> {
+    let text = tok.text();
+    Some(match tok.kind() {
+        SyntaxKind::Number => Token::Num(text.to_string()),
+        // without the quotes
+        SyntaxKind::String => Token::Str(text[1..text.len() - 1].to_string()),
+        SyntaxKind::Ident => Token::Ident(text.to_string()),
+        SyntaxKind::Op => Token::Op(text.to_string()),
+        SyntaxKind::Comment => Token::Comment(text.to_string()),
+        SyntaxKind::LParen
+        | SyntaxKind::RParen
+        | SyntaxKind::LBrack
+        | SyntaxKind::RBrack
+        | SyntaxKind::LBrace
+        | SyntaxKind::RBrace
+        | SyntaxKind::Semi
+        | SyntaxKind::Comma
+        | SyntaxKind::Colon
+        | SyntaxKind::Hash => Token::Ctrl(text.chars().next()?),
+        SyntaxKind::FnKw => Token::Fn,
+        SyntaxKind::MemoKw => Token::Memo,
+        SyntaxKind::VarKw => Token::Var,
+        SyntaxKind::ReturnKw => Token::Return,
+        SyntaxKind::IfKw => Token::If,
+        SyntaxKind::ElseKw => Token::Else,
+        _ => return None,
+    })
+}
+
+///
+/// the tokens of a tree as the lexer makes them, comments included, with spans in chars
+/// counted from `offset`, and whether the lexer made tokens of all of its text
+///
+pub fn lexed_tokens(node: &SyntaxNode, offset: usize) -> (Vec<Spanned<Token>>, bool) {
+    let mut tokens = Vec::new();
+    let mut clean = true;
+    let mut start = offset;
+    for tok in node
+        .descendants_with_tokens()
+        .filter_map(|el| el.into_token())
+    {
+        let end = start + tok.text().chars().count();
+        match lexed_token(&tok) {
+            Some(token) => tokens.push((token, start..end)),
+            None => clean &= tok.kind() != SyntaxKind::Error,
+        }
+        start = end;
+    }
+    (tokens, clean)
+}
+
+/// a token of the source, trivia included
+struct Raw<'s> {
+    kind: SyntaxKind,
+    text: &'s str,
+    /// the lexed token, for tokens that are not trivia or errors
+    token: Option<Token>,
+}
+
+///
+/// cut the whole source into tokens: what the lexer produced, with
+/// the whitespace and the text it skipped over in between
+///
+fn raw_tokens(src: &str) -> Vec<Raw<'_>> {
+    // spans count chars, but the tree is built from byte slices
+    let bytes = src
+        .char_indices()
+        .map(|(i, _)| i)
+        .chain(std::iter::once(src.len()))
+        .collect::<Vec<_>>();
+    let mut raw = Vec::new();
+    let mut cursor = 0;
+    for (tok, span) in lex(src).0.unwrap_or_default() {
+        let (start, end) = (bytes[span.start], bytes[span.end]);
+        if start < cursor {
+            continue;
+        }
+        split_gap(&src[cursor..start], &mut raw);
+        let kind = SyntaxKind::of_token(&tok);
+        raw.push(Raw {
+            kind,
+            text: &src[start..end],
+            token: (!kind.is_trivia()).then_some(tok),
+        });
+        cursor = end;
+    }
+    split_gap(&src[cursor..], &mut raw);
+    raw
+}
+
+/// split text between tokens into runs of whitespace and of anything the lexer skipped
+fn split_gap<'s>(mut text: &'s str, raw: &mut Vec<Raw<'s>>) {
+    while let Some(c) = text.chars().next() {
+        let space = c.is_whitespace();
+        let len = text
+            .find(|c: char| c.is_whitespace() != space)
+            .unwrap_or(text.len());
+        raw.push(Raw {
+            kind: if space {
+                SyntaxKind::Whitespace
+            } else {
+                SyntaxKind::Error
+            },
+            text: &text[..len],
+            token: None,
+        });
+        text = &text[len..];
+    }
+}
+
+///
+/// build the lossless tree of a source, which prints back to exactly the source
+/// however malformed it is
+///
+pub fn parse_cst(src: &str) -> SyntaxNode {
+    let mut parser = CstParser {
+        raw: raw_tokens(src),
+        pos: 0,
+        builder: GreenNodeBuilder::new(),
+    };
+    parser.root();
+    SyntaxNode::new_root(parser.finish())
+}
+
+/// binding power of a binary operator, higher binding tighter
+fn precedence(op: &str) -> Option<u8> {
+    match op {
+        "||" => Some(1),
+        "&&" => Some(2),
+        "==" | "!=" => Some(3),
+        "<" | ">" | "<=" | ">=" => Some(4),
+        "+" | "-" => Some(5),
+        "*" | "/" | "%" => Some(6),
+        _ => None,
+    }
+}
+
+///
+/// recursive descent over the raw tokens, following the grammar of `parsers`;
+/// it never fails, wrapping what it does not expect in `Error` nodes.
+/// However malformed an item is, it ends before the next `#`, `memo` or `fn`,
+/// so that items can be reparsed one at a time
+///
+struct CstParser<'s> {
+    raw: Vec<Raw<'s>>,
+    pos: usize,
+    builder: GreenNodeBuilder<'static>,
+}
+
+impl CstParser<'_> {
+    fn finish(self) -> GreenNode {
+        self.builder.finish()
+    }
+
+    /// move trivia into the node being built, so that nodes start at a real token
+    fn trivia(&mut self) {
+        while let Some(raw) = self.raw.get(self.pos) {
+            if !raw.kind.is_trivia() {
+                break;
+            }
+            self.builder.token(raw.kind.into(), raw.text);
+            self.pos += 1;
+        }
+    }
+
+    /// the next token that is not trivia
+    fn peek(&self) -> Option<&Raw<'_>> {
+        self.raw[self.pos..]
+            .iter()
+            .find(|raw| !raw.kind.is_trivia())
+    }
+
+    fn at(&self, kind: SyntaxKind) -> bool {
+        self.peek().is_some_and(|raw| raw.kind == kind)
+    }
+
+    fn at_op(&self, op: &str) -> bool {
+        matches!(self.peek(), Some(Raw { token: Some(Token::Op(o)), .. }) if o == op)
+    }
+
+    fn at_fn_start(&self) -> bool {
+        self.at(SyntaxKind::Hash) || self.at(SyntaxKind::MemoKw) || self.at(SyntaxKind::FnKw)
+    }
+
+    /// whether the item being parsed cannot go on
+    fn at_item_end(&self) -> bool {
+        self.peek().is_none() || self.at_fn_start()
+    }
+
+    fn bump(&mut self) {
+        self.trivia();
+        if let Some(raw) = self.raw.get(self.pos) {
+            self.builder.token(raw.kind.into(), raw.text);
+            self.pos += 1;
+        }
+    }
+
+    fn eat(&mut self, kind: SyntaxKind) -> bool {
+        let at = self.at(kind);
+        if at {
+            self.bump();
+        }
+        at
+    }
+
+    fn start(&mut self, kind: SyntaxKind) {
+        self.trivia();
+        self.builder.start_node(kind.into());
+    }
+
+    fn checkpoint(&mut self) -> rowan::Checkpoint {
+        self.trivia();
+        self.builder.checkpoint()
+    }
+
+    fn finish_node(&mut self) {
+        self.builder.finish_node();
+    }
+
+    /// wrap the next token in an `Error` node
+    fn error(&mut self) {
+        self.start(SyntaxKind::Error);
+        self.bump();
+        self.finish_node();
+    }
+
+    fn root(&mut self) {
+        self.builder.start_node(SyntaxKind::Root.into());
+        while self.peek().is_some() {
+            if self.at_fn_start() {
+                self.func();
+            } else {
+                // skip to something that looks like a function
+                self.start(SyntaxKind::Error);
+                while self.peek().is_some() && !self.at_fn_start() {
+                    self.bump();
+                }
+                self.finish_node();
+            }
+        }
+        self.trivia();
+        self.finish_node();
+    }
+
+    fn func(&mut self) {
+        self.start(SyntaxKind::Fn);
+        while self.at(SyntaxKind::Hash) {
+            self.attr();
+        }
+        self.eat(SyntaxKind::MemoKw);
+        self.eat(SyntaxKind::FnKw);
+        self.eat(SyntaxKind::Ident);
+        if self.at(SyntaxKind::LParen) {
+            self.params();
+        }
+        if self.at_op("->") {
+            self.start(SyntaxKind::RetType);
+            self.bump();
+            self.type_();
+            self.finish_node();
+        }
+        if self.at(SyntaxKind::LBrace) {
+            self.block();
+        }
+        self.finish_node();
+    }
+
+    fn attr(&mut self) {
+        self.start(SyntaxKind::Attr);
+        self.bump();
+        if self.eat(SyntaxKind::LBrack) {
+            if !self.eat(SyntaxKind::Ident) {
+                self.eat(SyntaxKind::MemoKw);
+            }
+            if self.eat(SyntaxKind::LParen) {
+                while !self.at_item_end()
+                    && !self.at(SyntaxKind::RParen)
+                    && !self.at(SyntaxKind::RBrack)
+                {
+                    self.bump();
+                }
+                self.eat(SyntaxKind::RParen);
+            }
+            self.eat(SyntaxKind::RBrack);
+        }
+        self.finish_node();
+    }
+
+    fn params(&mut self) {
+        self.start(SyntaxKind::ParamList);
+        self.bump();
+        while !self.at_item_end() && !self.at(SyntaxKind::RParen) && !self.at(SyntaxKind::LBrace) {
+            if self.at(SyntaxKind::Ident) {
+                self.start(SyntaxKind::Param);
+                self.bump();
+                if self.eat(SyntaxKind::Colon) {
+                    self.type_();
+                }
+                self.finish_node();
+            } else if !self.eat(SyntaxKind::Comma) {
+                self.error();
+            }
+        }
+        self.eat(SyntaxKind::RParen);
+        self.finish_node();
+    }
+
+    fn type_(&mut self) {
+        self.start(SyntaxKind::Type);
+        if self.eat(SyntaxKind::LBrack) {
+            if !self.at(SyntaxKind::RBrack) {
+                self.type_();
+            }
+            self.eat(SyntaxKind::RBrack);
+        } else {
+            self.eat(SyntaxKind::Ident);
+        }
+        self.finish_node();
+    }
+
+    fn block(&mut self) {
+        self.start(SyntaxKind::Block);
+        self.bump();
+        self.stmts(SyntaxKind::RBrace);
+        self.eat(SyntaxKind::RBrace);
+        self.finish_node();
+    }
+
+    /// statements separated by `;` up to a closing delimiter
+    fn stmts(&mut self, close: SyntaxKind) {
+        // an unclosed parenthesis ends with its block
+        while !self.at_item_end() && !self.at(close) && !self.at(SyntaxKind::RBrace) {
+            if self.eat(SyntaxKind::Semi) {
+                continue;
+            }
+            let pos = self.pos;
+            self.expr(0);
+            if self.pos == pos {
+                self.error();
+            }
+        }
+    }
+
+    /// an expression whose operators bind tighter than `min`
+    fn expr(&mut self, min: u8) {
+        // the statements after a `var` are its body, so nothing continues it
+        if self.at(SyntaxKind::VarKw) {
+            self.var();
+            return;
+        }
+        let checkpoint = self.checkpoint();
+        if !self.postfix() {
+            return;
+        }
+        while let Some(Raw {
+            token: Some(Token::Op(op)),
+            ..
+        }) = self.peek()
+        {
+            let prec = match precedence(op) {
+                Some(prec) if prec > min => prec,
+                _ => break,
+            };
+            // operators of the same precedence associate to the left
+            self.builder
+                .start_node_at(checkpoint, SyntaxKind::BinaryExpr.into());
+            self.bump();
+            self.expr(prec);
+            self.finish_node();
+        }
+    }
+
+    /// an atom followed by calls; whether anything was parsed
+    fn postfix(&mut self) -> bool {
+        let checkpoint = self.checkpoint();
+        if !self.atom() {
+            return false;
+        }
+        while self.at(SyntaxKind::LParen) {
+            self.builder
+                .start_node_at(checkpoint, SyntaxKind::CallExpr.into());
+            self.start(SyntaxKind::ArgList);
+            self.bump();
+            while !self.at_item_end() && !self.at(SyntaxKind::RParen) {
+                if self.at(SyntaxKind::RBrace) || self.at(SyntaxKind::Semi) {
+                    break;
+                }
+                if self.eat(SyntaxKind::Comma) {
+                    continue;
+                }
+                let pos = self.pos;
+                self.expr(0);
+                if self.pos == pos {
+                    self.error();
+                }
+            }
+            self.eat(SyntaxKind::RParen);
+            self.finish_node();
+            self.finish_node();
+        }
+        true
+    }
+
+    fn atom(&mut self) -> bool {
+        let Some(kind) = self.peek().map(|raw| raw.kind) else {
+            return false;
+        };
+        match kind {
+            SyntaxKind::Number | SyntaxKind::String => {
+                self.start(SyntaxKind::Literal);
+                self.bump();
+                self.finish_node();
+            }
+            SyntaxKind::Ident => {
+                self.start(SyntaxKind::NameRef);
+                self.bump();
+                self.finish_node();
+            }
+            SyntaxKind::LParen => {
+                self.start(SyntaxKind::ParenExpr);
+                self.bump();
+                self.stmts(SyntaxKind::RParen);
+                self.eat(SyntaxKind::RParen);
+                self.finish_node();
+            }
+            SyntaxKind::LBrace => self.block(),
+            SyntaxKind::IfKw => self.if_(),
+            SyntaxKind::ReturnKw => {
+                self.start(SyntaxKind::ReturnExpr);
+                self.bump();
+                self.expr(0);
+                self.finish_node();
+            }
+            _ => return false,
+        }
+        true
+    }
+
+    fn var(&mut self) {
+        self.start(SyntaxKind::VarStmt);
+        self.bump();
+        self.eat(SyntaxKind::Ident);
+        if self.eat(SyntaxKind::Colon) {
+            self.type_();
+        }
+        if self.at_op("=") {
+            self.bump();
+            self.expr(0);
+        }
+        self.eat(SyntaxKind::Semi);
+        self.finish_node();
+    }
+
+    fn if_(&mut self) {
+        self.start(SyntaxKind::IfExpr);
+        self.bump();
+        self.expr(0);
+        if self.at(SyntaxKind::LBrace) {
+            self.block();
+        }
+        if self.eat(SyntaxKind::ElseKw) {
+            if self.at(SyntaxKind::IfKw) {
+                self.if_();
+            } else if self.at(SyntaxKind::LBrace) {
+                self.block();
+            }
+        }
+        self.finish_node();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{leading_trivia, lexed_tokens, parse_cst, SyntaxKind};
+    use crate::lexer::lex;
+
+    #[test]
+    fn kinds_round_trip() {
+        for (i, kind) in SyntaxKind::ALL.iter().enumerate() {
+            assert_eq!(*kind as usize, i);
+        }
+    }
+
+    #[test]
+    fn keeps_every_byte() {
+        for src in [
+            "// add\nfn add(a: int, b) -> int {\r\n  a + b * 2; // twice\n}\n",
+            "fn main() {\n    val a = 1 ^;\n    a + 1#;\n}",
+            "}}} fn ( \"unclosed",
+            "",
+        ] {
+            assert_eq!(parse_cst(src).to_string(), src);
+        }
+    }
+
+    #[test]
+    fn makes_the_tokens_of_the_lexer() {
+        let src = "// sümme\nfn add(a: int, b) -> [int] {\r\n  a + b >= \"ä\"; # }\n";
+        let (tokens, clean) = lexed_tokens(&parse_cst(src), 0);
+        assert!(clean);
+        assert_eq!(Some(tokens), lex(src).0);
+        // the lexer skips the `^`
+        assert!(!lexed_tokens(&parse_cst("fn f() { 1 ^ 2 }"), 0).1);
+    }
+
+    #[test]
+    fn builds_nodes() {
+        let src = "#[memo]\nfn f(n) {\n    // double\n    var x = n * 2; g(x + 1, 3) == 1 || 0\n}";
+        let root = parse_cst(src);
+        let func = root.first_child().unwrap();
+        assert_eq!(func.kind(), SyntaxKind::Fn);
+        let kinds = func.children().map(|node| node.kind()).collect::<Vec<_>>();
+        assert_eq!(
+            kinds,
+            [SyntaxKind::Attr, SyntaxKind::ParamList, SyntaxKind::Block]
+        );
+
+        let block = func.last_child().unwrap();
+        let stmts = block.children().collect::<Vec<_>>();
+        assert_eq!(stmts[0].kind(), SyntaxKind::VarStmt);
+        assert_eq!(stmts[0].to_string(), "var x = n * 2;");
+        // `||` binds loosest, then `==`
+        assert_eq!(stmts[1].kind(), SyntaxKind::BinaryExpr);
+        let lhs = stmts[1].first_child().unwrap();
+        assert_eq!(lhs.to_string(), "g(x + 1, 3) == 1");
+        assert_eq!(lhs.first_child().unwrap().kind(), SyntaxKind::CallExpr);
+
+        let var = stmts[0].first_token().unwrap();
+        let trivia = leading_trivia(&var)
+            .iter()
+            .map(|tok| tok.text().to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(trivia, ["\n    ", "// double", "\n    "]);
+    }
+}
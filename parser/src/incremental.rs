@@ -0,0 +1,203 @@
+//!
+//! reparsing of the lossless syntax tree after an edit, which only lexes and parses
+//! again the function items around the edit and keeps the rest of the old tree
+//!
+
+use rowan::{GreenNode, GreenNodeData, GreenToken, GreenTokenData, NodeOrToken, SyntaxText};
+use std::ops::Range;
+
+use crate::cst::{parse_cst, SyntaxElement, SyntaxKind, SyntaxNode};
+
+/// a change to a source: the chars in `range` replaced by `text`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Edit {
+    pub range: Range<usize>,
+    pub text: String,
+}
+
+impl Edit {
+    pub fn new(range: Range<usize>, text: &str) -> Self {
+        Self {
+            range,
+            text: text.to_string(),
+        }
+    }
+
+    /// the source after the edit
+    pub fn apply(&self, src: &str) -> String {
+        let byte = |offset| src.char_indices().nth(offset).map_or(src.len(), |(i, _)| i);
+        let mut src = src.to_string();
+        src.replace_range(byte(self.range.start)..byte(self.range.end), &self.text);
+        src
+    }
+}
+
+/// byte offset of the char at `offset` in a text, or its length if that is past the end
+fn byte_offset(text: &SyntaxText, offset: usize) -> usize {
+    let (mut chars, mut bytes) = (0, 0);
+    let found = text.try_for_each_chunk(|chunk| {
+        for (i, _) in chunk.char_indices() {
+            if chars == offset {
+                return Err(bytes + i);
+            }
+            chars += 1;
+        }
+        bytes += chunk.len();
+        Ok(())
+    });
+    found.err().unwrap_or(bytes)
+}
+
+///
+/// the tree of the source of `old` after `edit`, the same as parsing the edited source
+/// from scratch would build
+///
+pub fn reparse(old: &SyntaxNode, edit: &Edit) -> SyntaxNode {
+    let text = old.text();
+    let range = byte_offset(&text, edit.range.start)..byte_offset(&text, edit.range.end);
+    reparse_items(old, range, &edit.text)
+        .unwrap_or_else(|| parse_cst(&edit.apply(&old.to_string())))
+}
+
+fn start(el: &SyntaxElement) -> usize {
+    el.text_range().start().into()
+}
+
+///
+/// whether a node has a `"` the lexer skipped, which text after it could close;
+/// skipped text ends up in error tokens or in the token after it
+///
+fn has_stray_quote(node: &SyntaxNode) -> bool {
+    node.descendants_with_tokens()
+        .filter_map(|el| el.into_token())
+        .any(|tok| {
+            !matches!(tok.kind(), SyntaxKind::String | SyntaxKind::Comment)
+                && tok.text().contains('"')
+        })
+}
+
+///
+/// reparse the items around an edit on their own, together with the item after them
+/// to check that they still end where they did; `None` if the whole source must be reparsed.
+/// The edit replaces the bytes in `range`, since rowan counts in bytes
+///
+fn reparse_items(old: &SyntaxNode, range: Range<usize>, edit: &str) -> Option<SyntaxNode> {
+    let children = old.children_with_tokens().collect::<Vec<_>>();
+    // functions and the junk between them are nodes, and everything else at the root is trivia
+    let items = children
+        .iter()
+        .enumerate()
+        .filter(|(_, el)| el.as_node().is_some());
+    // an edit at the very start of an item may join it to the one before
+    let first = items
+        .clone()
+        .rev()
+        .find(|(_, el)| start(el) < range.start)
+        .map_or(0, |(i, _)| i);
+    let next = items
+        .clone()
+        .find(|(_, el)| start(el) > range.end)
+        .map(|(i, _)| i);
+    let last = next.map_or(children.len(), |i| i + 1);
+
+    let offset = children.get(first).map_or(0, start);
+    let mut text = children[first..last]
+        .iter()
+        .map(ToString::to_string)
+        .collect::<String>();
+    text.replace_range(range.start - offset..range.end - offset, edit);
+    // a string left unclosed before the edit may be closed by a quote in it
+    if text.contains('"')
+        && children[..first]
+            .iter()
+            .filter_map(|el| el.as_node())
+            .any(has_stray_quote)
+    {
+        return None;
+    }
+
+    let reparsed = parse_cst(&text);
+    if let Some(next) = next {
+        let new_next = reparsed.last_child()?;
+        if new_next.text_range().end() != reparsed.text_range().end()
+            || *new_next.green() != *children[next].as_node()?.green()
+        {
+            return None;
+        }
+    }
+    if last < children.len() && has_stray_quote(&reparsed) {
+        return None;
+    }
+
+    let old = old.green();
+    let old = old.children().collect::<Vec<_>>();
+    let new = reparsed.green();
+    let children = old[..first]
+        .iter()
+        .copied()
+        .chain(new.children())
+        .chain(old[last..].iter().copied())
+        .map(to_owned)
+        .collect::<Vec<_>>();
+    Some(SyntaxNode::new_root(GreenNode::new(
+        SyntaxKind::Root.into(),
+        children,
+    )))
+}
+
+fn to_owned(
+    el: NodeOrToken<&GreenNodeData, &GreenTokenData>,
+) -> NodeOrToken<GreenNode, GreenToken> {
+    match el {
+        NodeOrToken::Node(node) => NodeOrToken::Node(node.to_owned()),
+        NodeOrToken::Token(tok) => NodeOrToken::Token(tok.to_owned()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{byte_offset, reparse, reparse_items, Edit};
+    use crate::cst::parse_cst;
+
+    const SRC: &str = "// sums\nfn add(a, b) {\n    a + b\n}\n\nfn main() {\n    add(1, 2)\n}\n\n#[test]\nfn adds() {\n    assert(add(1, 2) == 3)\n}\n";
+
+    /// reparse after an edit, checking against a parse from scratch
+    fn check(edit: Edit, incremental: bool) {
+        let old = parse_cst(SRC);
+        let new = reparse(&old, &edit);
+        let expected = parse_cst(&edit.apply(SRC));
+        assert_eq!(format!("{:#?}", new), format!("{:#?}", expected));
+        let range =
+            byte_offset(&old.text(), edit.range.start)..byte_offset(&old.text(), edit.range.end);
+        assert_eq!(
+            reparse_items(&old, range, &edit.text).is_some(),
+            incremental
+        );
+    }
+
+    #[test]
+    fn reparses_items_around_edits() {
+        // the source is ascii, so its bytes are its chars
+        let at = |s: &str| SRC.find(s).unwrap();
+        // inside a body
+        check(Edit::new(at("2)") + 1..at("2)") + 1, "0"), true);
+        // across two items
+        check(Edit::new(at("b\n}") + 1..at("add(1, 2)\n}"), ""), true);
+        // opening a block swallows nothing, since items end at the next `fn`
+        check(Edit::new(at("a + b")..at("a + b") + 5, "{ a"), true);
+        // joining an item to the next
+        check(Edit::new(at("}\n\n#") + 1..at("#"), " "), true);
+        // an unclosed string runs into the items after the edit
+        check(Edit::new(at("a + b")..at("a + b"), "\""), false);
+    }
+
+    #[test]
+    fn counts_edits_in_chars() {
+        let src = "fn greet() { \"héllo\" }\nfn main() { greet() }\n";
+        let at = src.chars().position(|c| c == 'o').unwrap();
+        let edit = Edit::new(at..at + 1, "ö");
+        assert_eq!(edit.apply(src), src.replace("llo", "llö"));
+        let new = reparse(&parse_cst(src), &edit);
+        assert_eq!(new.to_string(), edit.apply(src));
+    }
+}
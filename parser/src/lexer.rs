@@ -3,30 +3,38 @@ use interface::{tokens::Token, Span};
 
 fn lexer() -> impl Parser<char, Vec<(Token, Span)>, Error = Simple<char>> {
     // A parser for strings
-    // let str_ = just('"')
-    //     .ignore_then(filter(|c| *c != '"').repeated())
-    //     .then_ignore(just('"'))
-    //     .collect::<String>()
-    //     .map(Token::Str);
+    let str_ = just('"')
+        .ignore_then(filter(|c| *c != '"').repeated())
+        .then_ignore(just('"'))
+        .collect::<String>()
+        .map(Token::Str);
 
     // A parser for operators
+    // Multi-character operators must be tried before their prefixes
     let op = just("+")
+        .or(just("->"))
         .or(just("-"))
         .or(just("*"))
         .or(just("/"))
         .or(just("%"))
         .or(just("=="))
+        .or(just("!="))
+        .or(just("<="))
+        .or(just(">="))
+        .or(just("&&"))
+        .or(just("||"))
         .or(just("="))
         .or(just("<"))
         .or(just(">"))
         .map(|c| Token::Op(c.to_string()));
 
     // A parser for control characters (delimiters, semicolons, etc.)
-    let ctrl = one_of("()[]{};,:").map(|c| Token::Ctrl(c));
+    let ctrl = one_of("()[]{};,:#").map(Token::Ctrl);
 
     // A parser for identifiers and keywords
     let ident = text::ident().map(|ident: String| match ident.as_str() {
         "fn" => Token::Fn,
+        "memo" => Token::Memo,
         "var" => Token::Var,
         "return" => Token::Return,
         "if" => Token::If,
@@ -37,10 +45,12 @@ fn lexer() -> impl Parser<char, Vec<(Token, Span)>, Error = Simple<char>> {
         _ => Token::Ident(ident),
     });
 
+    // A comment runs to the end of the line, which is left to the whitespace
+    // so that its span covers exactly its text
     let comment = just("//")
-        .then(take_until(just('\n')))
-        .padded()
-        .map(|(a, (b, _))| Token::Comment(format!("{}{}", a, b.into_iter().collect::<String>())));
+        .ignore_then(none_of("\r\n").repeated())
+        .collect::<String>()
+        .map(|text| Token::Comment(format!("//{}", text)));
 
     // A parser for numbers
     let num = text::int(10).map(Token::Num);
@@ -48,7 +58,7 @@ fn lexer() -> impl Parser<char, Vec<(Token, Span)>, Error = Simple<char>> {
     // A single token can be one of the above
     let token = comment
         .or(num)
-        // .or(str_)
+        .or(str_)
         .or(op)
         .or(ctrl)
         .or(ident)
@@ -60,10 +70,13 @@ fn lexer() -> impl Parser<char, Vec<(Token, Span)>, Error = Simple<char>> {
         .repeated()
 }
 
+/// result of [lex]: tokens (if recoverable) and lex errors
+pub type LexResult = (Option<Vec<(Token, Span)>>, Vec<Simple<char>>);
+
 ///
 /// do lex
 ///
-pub fn lex(src: &str) -> (Option<Vec<(Token, Span)>>, Vec<Simple<char>>) {
+pub fn lex(src: &str) -> LexResult {
     let (tokens, lex_errs) = lexer().parse_recovery(src);
     (tokens, lex_errs)
 }
@@ -87,10 +100,33 @@ mod tests {
             (Some(vec![(Token::Op("==".to_string()), 0..2)]), vec![])
         );
 
+        assert_eq!(
+            lex("<="),
+            (Some(vec![(Token::Op("<=".to_string()), 0..2)]), vec![])
+        );
+
+        assert_eq!(
+            lex("->"),
+            (Some(vec![(Token::Op("->".to_string()), 0..2)]), vec![])
+        );
+
+        assert_eq!(
+            lex("#["),
+            (
+                Some(vec![(Token::Ctrl('#'), 0..1), (Token::Ctrl('['), 1..2)]),
+                vec![]
+            )
+        );
+
+        assert_eq!(
+            lex("memo fn"),
+            (Some(vec![(Token::Memo, 0..4), (Token::Fn, 5..7)]), vec![])
+        );
+
         assert_eq!(
             lex("// a\n"),
             (
-                Some(vec![(Token::Comment("// a".to_string()), 0..5)]),
+                Some(vec![(Token::Comment("// a".to_string()), 0..4)]),
                 vec![]
             )
         );
@@ -99,11 +135,22 @@ mod tests {
             lex("// a\n1"),
             (
                 Some(vec![
-                    (Token::Comment("// a".to_string()), 0..5),
+                    (Token::Comment("// a".to_string()), 0..4),
                     (Token::Num("1".to_string()), 5..6)
                 ]),
                 vec![]
             )
+        );
+
+        assert_eq!(
+            lex("1 // a"),
+            (
+                Some(vec![
+                    (Token::Num("1".to_string()), 0..1),
+                    (Token::Comment("// a".to_string()), 2..6)
+                ]),
+                vec![]
+            )
         )
     }
 }
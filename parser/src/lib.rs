@@ -1,2 +1,8 @@
+// chumsky's `Simple` errors are large by design and are returned from every parser closure
+#![allow(clippy::result_large_err)]
+
+pub mod cst;
+pub mod incremental;
 pub mod lexer;
 pub mod parsers;
+pub mod printer;
@@ -0,0 +1,35 @@
+use interface::{nodes::attr::Attr, tokens::Token};
+
+use chumsky::prelude::*;
+
+/// an attribute such as `#[memo]` or `#[deprecated("use g")]`
+pub fn attr_parser() -> impl Parser<Token, Attr, Error = Simple<Token>> + Clone {
+    let name = filter_map(|span, tok| match tok {
+        Token::Ident(name) => Ok(name),
+        // `#[memo]` is spelled like the keyword
+        Token::Memo => Ok(Token::Memo.to_string()),
+        _ => Err(Simple::expected_input_found(span, Vec::new(), Some(tok))),
+    })
+    .map_with_span(|name, span| (name, span))
+    .labelled("attribute name");
+
+    let arg = filter(|tok| matches!(tok, Token::Num(_) | Token::Str(_) | Token::Ident(_)))
+        .map_with_span(|tok, span| (tok, span))
+        .labelled("attribute argument");
+    let args = arg
+        .separated_by(just(Token::Ctrl(',')))
+        .allow_trailing()
+        .delimited_by(just(Token::Ctrl('(')), just(Token::Ctrl(')')));
+
+    just(Token::Ctrl('#'))
+        .ignore_then(
+            name.then(args.or_not())
+                .delimited_by(just(Token::Ctrl('[')), just(Token::Ctrl(']'))),
+        )
+        .map_with_span(|(name, args), span| Attr {
+            name,
+            args: args.unwrap_or_default(),
+            span,
+        })
+        .labelled("attribute")
+}
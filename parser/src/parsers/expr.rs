@@ -1,21 +1,52 @@
 use interface::{
-    nodes::{binaryop::BinaryOp, expr::Expr, value::Value},
+    nodes::{binaryop::BinaryOp, expr::Expr, types::Type, value::Value},
     tokens::Token,
     Spanned,
 };
 
 use chumsky::prelude::*;
 
+use super::types::type_parser;
+
+/// the binding of a `var`, such as `var x: int = 1`, whose value `val` parses
+pub fn var_parser<P>(
+    val: P,
+) -> impl Parser<Token, (Spanned<String>, Option<Spanned<Type>>, Spanned<Expr>), Error = Simple<Token>>
+       + Clone
+where
+    P: Parser<Token, Spanned<Expr>, Error = Simple<Token>> + Clone,
+{
+    let ident = filter_map(|span, tok| match tok {
+        Token::Ident(ident) => Ok(ident.clone()),
+        _ => Err(Simple::expected_input_found(span, Vec::new(), Some(tok))),
+    })
+    .labelled("identifier");
+
+    just(Token::Var)
+        .ignore_then(ident.map_with_span(|name, span| (name, span)))
+        .then(just(Token::Ctrl(':')).ignore_then(type_parser()).or_not())
+        .then_ignore(just(Token::Op("=".to_string())))
+        .then(val)
+        .map(|((name, ty), val)| (name, ty, val))
+}
+
 pub fn expr_parser() -> impl Parser<Token, Spanned<Expr>, Error = Simple<Token>> + Clone {
     recursive(|expr| {
         let raw_expr = recursive(|raw_expr| {
             let val = filter_map(|span, tok| match tok {
                 // Token::Null => Ok(Expr::Value(Value::Null)),
                 // Token::Bool(x) => Ok(Expr::Value(Value::Bool(x))),
-                Token::Num(n) => Ok(Expr::Value(Value::I32(n.parse().unwrap()))),
-                // Token::Str(s) => Ok(Expr::Value(Value::Str(s))),
+                Token::Num(n) => Ok(n.parse().map(|n| Expr::Value(Value::I32(n))).map_err(|_| n)),
+                Token::Str(s) => Ok(Ok(Expr::Value(Value::Str(s)))),
                 _ => Err(Simple::expected_input_found(span, Vec::new(), Some(tok))),
             })
+            // a number that does not fit is reported without giving up on the rest
+            .validate(|val, span, emit| {
+                val.unwrap_or_else(|n| {
+                    emit(Simple::custom(span, format!("Number '{}' is too large", n)));
+                    Expr::Error
+                })
+            })
             .labelled("value");
 
             let ident = filter_map(|span, tok| match tok {
@@ -32,14 +63,11 @@ pub fn expr_parser() -> impl Parser<Token, Spanned<Expr>, Error = Simple<Token>>
                 .or_not()
                 .map(|item| item.unwrap_or_else(Vec::new));
 
-            // A let expression
-            let let_ = just(Token::Var)
-                .ignore_then(ident)
-                .then_ignore(just(Token::Op("=".to_string())))
-                .then(raw_expr)
+            // A let expression, with an optional type annotation
+            let let_ = var_parser(raw_expr)
                 .then_ignore(just(Token::Ctrl(';')))
                 .then(expr.clone())
-                .map(|((name, val), body)| Expr::Var(name, Box::new(val), Box::new(body)));
+                .map(|((name, ty, val), body)| Expr::Var(name, ty, Box::new(val), Box::new(body)));
 
             let r#return = just(Token::Return)
                 .ignore_then(expr.clone())
@@ -158,15 +186,12 @@ pub fn expr_parser() -> impl Parser<Token, Spanned<Expr>, Error = Simple<Token>>
 
             // ||
             let op = just(Token::Op("||".to_string())).to(BinaryOp::Or);
-            let or = and
-                .clone()
+            and.clone()
                 .then(op.then(and).repeated())
                 .foldl(|a, (op, b)| {
                     let span = a.1.start..b.1.end;
                     (Expr::Binary(Box::new(a), op, Box::new(b)), span)
-                });
-
-            or
+                })
 
             // let r#return = just(Token::Return)
             //     .ignore_then(or.clone())
@@ -6,29 +6,41 @@ use std::collections::HashMap;
 
 use chumsky::prelude::*;
 
-use super::expr::expr_parser;
+use super::{attrs::attr_parser, expr::expr_parser, types::type_parser};
 
-pub fn funcs_parser() -> impl Parser<Token, HashMap<String, Func>, Error = Simple<Token>> + Clone {
+/// a single `fn` item, which may be marked `memo` and have attributes
+pub fn func_parser() -> impl Parser<Token, (String, Func), Error = Simple<Token>> + Clone {
     let ident = filter_map(|span, tok| match tok {
         Token::Ident(ident) => Ok(ident.clone()),
         _ => Err(Simple::expected_input_found(span, Vec::new(), Some(tok))),
     });
 
-    // Argument lists are just identifiers separated by commas, surrounded by parentheses
+    // Argument lists are identifiers with optional type annotations separated by commas,
+    // surrounded by parentheses
     let args = ident
-        .clone()
+        .map_with_span(|name, span| (name, span))
+        .then(just(Token::Ctrl(':')).ignore_then(type_parser()).or_not())
         .separated_by(just(Token::Ctrl(',')))
         .allow_trailing()
         .delimited_by(just(Token::Ctrl('(')), just(Token::Ctrl(')')))
-        .labelled("function args");
+        .labelled("function args")
+        .map_with_span(|args, span| (args, span));
 
-    let func = just(Token::Fn)
-        .ignore_then(
+    attr_parser()
+        .repeated()
+        .then(just(Token::Memo).or_not())
+        .then_ignore(just(Token::Fn))
+        .then(
             ident
                 .map_with_span(|name, span| (name, span))
                 .labelled("function name"),
         )
         .then(args)
+        .then(
+            just(Token::Op("->".to_string()))
+                .ignore_then(type_parser())
+                .or_not(),
+        )
         .then(
             expr_parser()
                 .delimited_by(just(Token::Ctrl('{')), just(Token::Ctrl('}')))
@@ -43,21 +55,48 @@ pub fn funcs_parser() -> impl Parser<Token, HashMap<String, Func>, Error = Simpl
                     |span| (Expr::Error, span),
                 )),
         )
-        .map(|((name, args), body)| (name, Func { args, body }))
-        .labelled("function");
+        .map(
+            |(((((attrs, memo), (name, name_span)), (args, args_span)), ret_type), body)| {
+                let (args, arg_types): (Vec<_>, _) = args.into_iter().unzip();
+                let (args, arg_spans) = args.into_iter().unzip();
+                (
+                    name,
+                    Func {
+                        args,
+                        body,
+                        name_span,
+                        args_span,
+                        arg_spans,
+                        arg_types,
+                        ret_type,
+                        // `#[memo]` is the same as the keyword
+                        memo: memo.is_some() || attrs.iter().any(|attr| attr.name.0 == "memo"),
+                        attrs,
+                    },
+                )
+            },
+        )
+        .labelled("function")
+}
+
+/// collect functions into a table, rejecting duplicated names
+pub fn collect_funcs(fs: Vec<(String, Func)>) -> Result<HashMap<String, Func>, Simple<Token>> {
+    let mut funcs = HashMap::new();
+    for (name, f) in fs {
+        let name_span = f.name_span.clone();
+        if funcs.insert(name.clone(), f).is_some() {
+            return Err(Simple::custom(
+                name_span,
+                format!("Function '{}' already exists", name),
+            ));
+        }
+    }
+    Ok(funcs)
+}
 
-    func.repeated()
-        .try_map(|fs, _| {
-            let mut funcs = HashMap::new();
-            for ((name, name_span), f) in fs {
-                if funcs.insert(name.clone(), f).is_some() {
-                    return Err(Simple::custom(
-                        name_span.clone(),
-                        format!("Function '{}' already exists", name),
-                    ));
-                }
-            }
-            Ok(funcs)
-        })
+pub fn funcs_parser() -> impl Parser<Token, HashMap<String, Func>, Error = Simple<Token>> + Clone {
+    func_parser()
+        .repeated()
+        .try_map(|fs, _| collect_funcs(fs))
         .then_ignore(end())
 }
@@ -1,12 +1,28 @@
-use interface::{nodes::func::Func, tokens::Token, Span, Spanned};
+use interface::{
+    nodes::{expr::Expr, func::Func},
+    tokens::Token,
+    Span, Spanned,
+};
 use std::{collections::HashMap, vec::IntoIter};
 
-use chumsky::{prelude::Simple, Parser, Stream};
+use chumsky::{
+    prelude::{end, Simple},
+    Parser, Stream,
+};
 
-use self::funcs::funcs_parser;
+use crate::lexer::lex;
 
+use self::{
+    expr::expr_parser,
+    funcs::funcs_parser,
+    repl::{repl_parser, ReplEntry},
+};
+
+pub mod attrs;
 pub mod expr;
 pub mod funcs;
+pub mod repl;
+pub mod types;
 
 ///
 /// do parse
@@ -16,3 +32,42 @@ pub fn parse(
 ) -> (Option<HashMap<String, Func>>, Vec<Simple<Token>>) {
     funcs_parser().parse_recovery(token_stream)
 }
+
+///
+/// lex and parse a whole program that has no errors, such as those of tests;
+/// panics on errors
+///
+pub fn parse_program(src: &str) -> HashMap<String, Func> {
+    let (tokens, errs) = lex(src);
+    assert!(errs.is_empty(), "lex errors: {:?}", errs);
+    let tokens = tokens
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|t| !matches!(t.0, Token::Comment(_)))
+        .collect::<Vec<_>>();
+    let len = src.chars().count();
+    match funcs_parser().parse(Stream::from_iter(len..len + 1, tokens.into_iter())) {
+        Ok(funcs) => funcs,
+        Err(errs) => panic!("parse errors: {:?}", errs),
+    }
+}
+
+///
+/// parse a single REPL entry
+///
+pub fn parse_repl(
+    token_stream: Stream<Token, Span, IntoIter<Spanned<Token>>>,
+) -> (Option<ReplEntry>, Vec<Simple<Token>>) {
+    repl_parser().parse_recovery(token_stream)
+}
+
+///
+/// parse a single expression
+///
+pub fn parse_expr(
+    token_stream: Stream<Token, Span, IntoIter<Spanned<Token>>>,
+) -> (Option<Spanned<Expr>>, Vec<Simple<Token>>) {
+    expr_parser()
+        .then_ignore(end())
+        .parse_recovery(token_stream)
+}
@@ -0,0 +1,41 @@
+use interface::{
+    nodes::{expr::Expr, func::Func, types::Type},
+    tokens::Token,
+    Spanned,
+};
+use std::collections::HashMap;
+
+use chumsky::prelude::*;
+
+use super::{
+    expr::{expr_parser, var_parser},
+    funcs::{collect_funcs, func_parser},
+};
+
+/// a single entry typed into the REPL
+#[derive(Debug)]
+pub enum ReplEntry {
+    /// one or more `fn` items
+    Funcs(HashMap<String, Func>),
+    /// a top-level `var name: type = value;` that stays in scope for later entries
+    Binding(Spanned<String>, Option<Spanned<Type>>, Spanned<Expr>),
+    Expr(Spanned<Expr>),
+}
+
+pub fn repl_parser() -> impl Parser<Token, ReplEntry, Error = Simple<Token>> + Clone {
+    let funcs = func_parser()
+        .repeated()
+        .at_least(1)
+        .try_map(|fs, _| collect_funcs(fs))
+        .map(ReplEntry::Funcs);
+
+    // `var x = 1;` without a body is only valid at the top level of the REPL
+    let binding =
+        var_parser(expr_parser()).map(|(name, ty, val)| ReplEntry::Binding(name, ty, val));
+
+    // a `var` with a body is a normal expression, so try that first
+    funcs
+        .or(expr_parser().then_ignore(end()).map(ReplEntry::Expr))
+        .or(binding)
+        .then_ignore(end())
+}
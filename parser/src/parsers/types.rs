@@ -0,0 +1,21 @@
+use interface::{nodes::types::Type, tokens::Token, Spanned};
+
+use chumsky::prelude::*;
+
+/// a type annotation, such as `int` or `[str]`
+pub fn type_parser() -> impl Parser<Token, Spanned<Type>, Error = Simple<Token>> + Clone {
+    recursive(|ty| {
+        let named = filter_map(|span, tok| match tok {
+            Token::Ident(name) => Type::from_name(&name)
+                .ok_or_else(|| Simple::custom(span, format!("Unknown type '{}'", name))),
+            _ => Err(Simple::expected_input_found(span, Vec::new(), Some(tok))),
+        });
+        let list = ty
+            .delimited_by(just(Token::Ctrl('[')), just(Token::Ctrl(']')))
+            .map(|(ty, _): Spanned<Type>| Type::List(Box::new(ty)));
+        named
+            .or(list)
+            .map_with_span(|ty, span| (ty, span))
+            .labelled("type")
+    })
+}
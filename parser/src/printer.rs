@@ -0,0 +1,526 @@
+use interface::{
+    nodes::{attr::Attr, expr::Expr, func::Func, types::Type, value::Value},
+    tokens::Token,
+    Spanned,
+};
+use std::collections::{HashMap, VecDeque};
+
+const INDENT: &str = "    ";
+
+/// a statement of a flattened `Then` / `Var` chain
+enum Stmt<'a> {
+    Expr(&'a Spanned<Expr>),
+    /// the name, its type, where the name or type ends, and the value
+    Var(&'a str, Option<&'a Type>, usize, &'a Spanned<Expr>),
+}
+
+///
+/// pretty-print functions in source order
+///
+pub fn print_funcs(funcs: &HashMap<String, Func>) -> String {
+    Printer::default().funcs(funcs)
+}
+
+///
+/// pretty-print functions in source order, keeping the comments among `tokens`,
+/// which must be the tokens `funcs` were parsed from
+///
+pub fn format_funcs(src: &str, tokens: &[Spanned<Token>], funcs: &HashMap<String, Func>) -> String {
+    let comments = tokens
+        .iter()
+        .filter_map(|(tok, span)| match tok {
+            Token::Comment(text) => Some((text.clone(), span.clone())),
+            _ => None,
+        })
+        .collect();
+    let ctrl = |c| {
+        tokens
+            .iter()
+            .filter(|(tok, _)| *tok == Token::Ctrl(c))
+            .map(|(_, span)| span.start)
+            .collect()
+    };
+    let mut printer = Printer {
+        src: src.chars().collect(),
+        comments,
+        openers: ctrl('{'),
+        closers: ctrl('}'),
+    };
+    let funcs = printer.funcs(funcs);
+    let rest = printer.comments(usize::MAX, 0, funcs.is_empty());
+    funcs + &rest
+}
+
+///
+/// pretty-print a single function
+///
+pub fn print_func(name: &str, func: &Func) -> String {
+    Printer::default().func(name, func)
+}
+
+///
+/// pretty-print an expression on its own
+///
+pub fn print_expr(expr: &Spanned<Expr>) -> String {
+    let mut printer = Printer::default();
+    let list = stmts(expr);
+    list.iter()
+        .enumerate()
+        .map(|(i, (s, _))| printer.stmt(s, 0, i + 1 == list.len()))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+///
+/// the attributes and head of a function, without its body
+///
+pub fn print_signature(name: &str, func: &Func) -> String {
+    Printer::default().signature(name, func)
+}
+
+fn print_attr(attr: &Attr) -> String {
+    if attr.args.is_empty() {
+        return format!("#[{}]", attr.name.0);
+    }
+    let args = attr
+        .args
+        .iter()
+        .map(|(arg, _)| arg.to_string())
+        .collect::<Vec<_>>();
+    format!("#[{}({})]", attr.name.0, args.join(", "))
+}
+
+fn indent(depth: usize) -> String {
+    INDENT.repeat(depth)
+}
+
+/// flatten `a; b; c` and `var x = a; b` into a statement list
+fn stmts(expr: &Spanned<Expr>) -> Vec<Spanned<Stmt<'_>>> {
+    let mut list = Vec::new();
+    let mut cur = expr;
+    loop {
+        match &cur.0 {
+            Expr::Then(a, b) => {
+                list.push((Stmt::Expr(a), a.1.clone()));
+                cur = b;
+            }
+            Expr::Var(name, ty, val, body) => {
+                let end = ty.as_ref().map_or(name.1.end, |(_, span)| span.end);
+                let stmt = Stmt::Var(&name.0, ty.as_ref().map(|(ty, _)| ty), end, val);
+                list.push((stmt, cur.1.start..val.1.end));
+                cur = body;
+            }
+            _ => {
+                list.push((Stmt::Expr(cur), cur.1.clone()));
+                return list;
+            }
+        }
+    }
+}
+
+///
+/// prints functions, putting the comments of the source they came from
+/// back before the statement they precede or after the token they follow
+///
+#[derive(Default)]
+struct Printer {
+    /// the source, which spans index by char
+    src: Vec<char>,
+    /// comments not printed yet, in source order
+    comments: VecDeque<Spanned<String>>,
+    /// where each `{` is, in source order
+    openers: Vec<usize>,
+    /// where each `}` is, in source order
+    closers: Vec<usize>,
+}
+
+impl Printer {
+    fn funcs(&mut self, funcs: &HashMap<String, Func>) -> String {
+        let mut funcs = funcs.iter().collect::<Vec<_>>();
+        funcs.sort_by_key(|(_, f)| f.name_span.start);
+        funcs
+            .into_iter()
+            .map(|(name, f)| {
+                let start = f
+                    .attrs
+                    .first()
+                    .map_or(f.name_span.start, |attr| attr.span.start);
+                self.leading(start, 0, true) + &self.func(name, f)
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    fn func(&mut self, name: &str, func: &Func) -> String {
+        let signature = self.signature(name, func);
+        let end = func
+            .ret_type
+            .as_ref()
+            .map_or(func.args_span.end, |(_, span)| span.end);
+        let first = stmts(&func.body)[0].1.start;
+        let brace = self.gap(end, self.opener_before(first).unwrap_or(end), " ", "");
+        let body = self.block(&func.body, 0);
+        let close = self.block_closer(&func.body);
+        let trailing = self.trailing(close.saturating_add(1), usize::MAX);
+        format!("{}{}{}{}\n", signature, brace, body, trailing)
+    }
+
+    fn signature(&mut self, name: &str, func: &Func) -> String {
+        let mut out = String::new();
+        for (i, attr) in func.attrs.iter().enumerate() {
+            let next = func
+                .attrs
+                .get(i + 1)
+                .map_or(func.name_span.start, |attr| attr.span.start);
+            out += &print_attr(attr);
+            out += &self.gap(attr.span.end, next, "\n", "");
+        }
+        // `#[memo]` already says so
+        if func.memo && !func.attrs.iter().any(|attr| attr.name.0 == "memo") {
+            out += "memo ";
+        }
+        out += &format!("fn {}(", name);
+        let (open, close) = (func.args_span.start + 1, func.args_span.end - 1);
+        let mut end = open;
+        for (i, (arg, ty)) in func.args.iter().zip(&func.arg_types).enumerate() {
+            let span = &func.arg_spans[i];
+            let sep = if i == 0 { "" } else { ", " };
+            out += &self.gap(end, span.start, sep, INDENT);
+            out += arg;
+            end = span.end;
+            if let Some((ty, span)) = ty {
+                out += &format!(": {}", ty);
+                end = span.end;
+            }
+        }
+        out += &self.gap(end, close, "", "");
+        out.push(')');
+        if let Some((ty, span)) = &func.ret_type {
+            out += &self.gap(func.args_span.end, span.start, " -> ", INDENT);
+            out += &ty.to_string();
+        }
+        out
+    }
+
+    /// whether there is an empty line just before `pos` in the source
+    fn blank_before(&self, pos: usize) -> bool {
+        let newlines = self.src[..pos.min(self.src.len())]
+            .iter()
+            .rev()
+            .take_while(|c| c.is_whitespace())
+            .filter(|c| **c == '\n')
+            .count();
+        newlines >= 2
+    }
+
+    ///
+    /// comments before `pos` on lines of their own, keeping single empty lines between them;
+    /// `first` is whether they start their block
+    ///
+    fn comments(&mut self, pos: usize, depth: usize, mut first: bool) -> String {
+        let mut out = String::new();
+        while let Some((text, span)) = self.comments.front() {
+            if span.start >= pos {
+                break;
+            }
+            if !first && self.blank_before(span.start) {
+                out.push('\n');
+            }
+            out += &format!("{}{}\n", indent(depth), text);
+            first = false;
+            self.comments.pop_front();
+        }
+        out
+    }
+
+    /// comments before an item starting at `pos`, and the empty line before it if any
+    fn leading(&mut self, pos: usize, depth: usize, first: bool) -> String {
+        let mut out = self.comments(pos, depth, first);
+        if !(first && out.is_empty()) && self.blank_before(pos) {
+            out.push('\n');
+        }
+        out
+    }
+
+    /// a comment after `end` on the same line, which must come before `limit`
+    fn trailing(&mut self, end: usize, limit: usize) -> String {
+        match self.comments.front() {
+            Some((text, span))
+                if span.start >= end
+                    && span.start < limit
+                    && !self.src[end..span.start].contains(&'\n') =>
+            {
+                let text = format!(" {}", text);
+                self.comments.pop_front();
+                text
+            }
+            _ => String::new(),
+        }
+    }
+
+    ///
+    /// what goes between tokens at `from` and `to`: `sep`, or the comments between them
+    /// attached to the token they follow, with `cont` starting each line after a comment
+    ///
+    fn gap(&mut self, from: usize, to: usize, sep: &str, cont: &str) -> String {
+        let start = self.comments.partition_point(|(_, span)| span.start < from);
+        let end = self.comments.partition_point(|(_, span)| span.start < to);
+        if start >= end {
+            return sep.to_string();
+        }
+        let comments = self.comments.drain(start..end).collect::<Vec<_>>();
+        // where the separator is, if it is a token
+        let token = sep.trim().chars().collect::<Vec<_>>();
+        let at = (from..to)
+            .find(|&pos| {
+                !token.is_empty()
+                    && !comments.iter().any(|(_, span)| span.contains(&pos))
+                    && self.src[pos..].starts_with(&token)
+            })
+            .unwrap_or(from);
+        let (before, after) = comments
+            .into_iter()
+            .partition::<Vec<_>, _>(|(_, span)| span.start < at);
+        match (before.is_empty(), after.is_empty()) {
+            (true, _) => sep.trim_end().to_string() + &self.attach(&after, at, cont),
+            (false, true) => self.attach(&before, from, cont) + sep.trim_start(),
+            (false, false) => {
+                self.attach(&before, from, cont) + sep.trim() + &self.attach(&after, at, cont)
+            }
+        }
+    }
+
+    /// comments after a token ending at `end`, the first one on its line if it is in the source
+    fn attach(&self, comments: &[Spanned<String>], mut end: usize, cont: &str) -> String {
+        let mut out = String::new();
+        for (text, span) in comments {
+            if self.src[end.min(span.start)..span.start].contains(&'\n') {
+                out += &format!("\n{}", cont);
+            } else {
+                out.push(' ');
+            }
+            out += text;
+            end = span.end;
+        }
+        out + "\n" + cont
+    }
+
+    /// where the `{` opening a block whose first statement starts at `start` is
+    fn opener_before(&self, start: usize) -> Option<usize> {
+        let i = self.openers.partition_point(|pos| *pos < start);
+        i.checked_sub(1).map(|i| self.openers[i])
+    }
+
+    /// where the `}` closing a block whose last statement ends at `end` is
+    fn closer_after(&self, end: usize) -> usize {
+        let i = self.closers.partition_point(|pos| *pos < end);
+        self.closers.get(i).copied().unwrap_or(usize::MAX)
+    }
+
+    fn block_closer(&self, expr: &Spanned<Expr>) -> usize {
+        let list = stmts(expr);
+        self.closer_after(list.last().map_or(expr.1.end, |(_, span)| span.end))
+    }
+
+    /// where the `{` opening a block is, or else `or`
+    fn block_opener(&self, expr: &Spanned<Expr>, or: usize) -> usize {
+        self.opener_before(stmts(expr)[0].1.start).unwrap_or(or)
+    }
+
+    fn block(&mut self, expr: &Spanned<Expr>, depth: usize) -> String {
+        let list = stmts(expr);
+        let close = self.block_closer(expr);
+        let open = match self.opener_before(list[0].1.start) {
+            Some(open) => self.trailing(open + 1, list[0].1.start),
+            None => String::new(),
+        };
+        let mut body = String::new();
+        for (i, (s, span)) in list.iter().enumerate() {
+            let limit = list.get(i + 1).map_or(close, |(_, next)| next.start);
+            body += &self.leading(span.start, depth + 1, i == 0);
+            let line = self.stmt(s, depth + 1, i + 1 == list.len());
+            body += &format!(
+                "{}{}{}\n",
+                indent(depth + 1),
+                line,
+                self.trailing(span.end, limit)
+            );
+        }
+        body += &self.comments(close, depth + 1, false);
+        format!("{{{}\n{}{}}}", open, body, indent(depth))
+    }
+
+    fn stmt(&mut self, s: &Stmt, depth: usize, last: bool) -> String {
+        match s {
+            Stmt::Var(name, ty, end, val) => {
+                let ty = ty.map_or(String::new(), |ty| format!(": {}", ty));
+                let eq = self.gap(*end, val.1.start, " = ", &indent(depth + 1));
+                format!("var {}{}{}{};", name, ty, eq, self.operand(val, depth))
+            }
+            Stmt::Expr(e) => match &e.0 {
+                Expr::If(..) if last => self.if_(e, depth),
+                Expr::If(..) => format!("{};", self.if_(e, depth)),
+                // only reachable for a nested chain, which must be kept together
+                Expr::Then(..) => format!("{};", self.block(e, depth)),
+                Expr::Return(a) if last => format!("return {};", self.operand(a, depth)),
+                _ => format!("{};", self.operand(e, depth)),
+            },
+        }
+    }
+
+    fn if_(&mut self, expr: &Spanned<Expr>, depth: usize) -> String {
+        if let Expr::If(cond, a, b) = &expr.0 {
+            let cond_ = self.operand(cond, depth);
+            let open = self.block_opener(a, cond.1.end);
+            let brace = self.gap(cond.1.end, open, " ", &indent(depth));
+            let head = format!("if {}{}{}", cond_, brace, self.block(a, depth));
+            let close = self.block_closer(a).saturating_add(1);
+            match &b.0 {
+                // the parser magics up a `null` for a missing `else`
+                Expr::Value(Value::Null) => head,
+                Expr::If(..) => {
+                    let else_ = self.gap(close, b.1.start, " else ", &indent(depth));
+                    format!("{}{}{}", head, else_, self.if_(b, depth))
+                }
+                _ => {
+                    let open = self.block_opener(b, close);
+                    let else_ = self.gap(close, open, " else ", &indent(depth));
+                    format!("{}{}{}", head, else_, self.block(b, depth))
+                }
+            }
+        } else {
+            self.operand(expr, depth)
+        }
+    }
+
+    /// print an expression that appears inside another expression
+    fn operand(&mut self, expr: &Spanned<Expr>, depth: usize) -> String {
+        match &expr.0 {
+            Expr::Error => "<error>".to_string(),
+            Expr::Value(Value::Str(s)) => format!("\"{}\"", s),
+            Expr::Value(val) => val.to_string(),
+            Expr::Local(name) => name.clone(),
+            Expr::Binary(a, op, b) => {
+                let mut wrap = |e: &Spanned<Expr>, strict: bool| match &e.0 {
+                    Expr::Binary(_, inner, _)
+                        if inner.precedence() < op.precedence()
+                            || (strict && inner.precedence() == op.precedence()) =>
+                    {
+                        format!("({})", self.operand(e, depth))
+                    }
+                    _ => self.operand(e, depth),
+                };
+                let (left, right) = (wrap(a, false), wrap(b, true));
+                let op = self.gap(a.1.end, b.1.start, &format!(" {} ", op), &indent(depth + 1));
+                format!("{}{}{}", left, op, right)
+            }
+            Expr::Call(f, (args, span)) => {
+                let mut out = match &f.0 {
+                    Expr::Local(_) | Expr::Call(..) | Expr::Value(_) => self.operand(f, depth),
+                    _ => format!("({})", self.operand(f, depth)),
+                };
+                out.push('(');
+                let mut end = span.start + 1;
+                for (i, arg) in args.iter().enumerate() {
+                    let sep = if i == 0 { "" } else { ", " };
+                    out += &self.gap(end, arg.1.start, sep, &indent(depth + 1));
+                    out += &self.operand(arg, depth);
+                    end = arg.1.end;
+                }
+                out += &self.gap(end, span.end - 1, "", &indent(depth));
+                out + ")"
+            }
+            Expr::If(..) => format!("({})", self.if_(expr, depth)),
+            Expr::Return(a) => format!("(return {})", self.operand(a, depth)),
+            Expr::Then(..) | Expr::Var(..) => {
+                let list = stmts(expr);
+                let inner = list
+                    .iter()
+                    .enumerate()
+                    .map(|(i, (s, _))| self.stmt(s, depth, i + 1 == list.len()))
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                format!("({})", inner.trim_end_matches(';'))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::format_funcs;
+    use crate::{lexer::lex, parsers::parse_program};
+
+    fn format(src: &str) -> String {
+        let tokens = lex(src).0.unwrap();
+        format_funcs(src, &tokens, &parse_program(src))
+    }
+
+    #[test]
+    fn keeps_comments() {
+        let src = "// adds things
+fn add(a,b){
+  // the sum
+  var c=a+b; // of both
+
+  if c>10 {c} else {
+    // small
+    0
+    // still small
+  }
+  // done
+} // after add
+#[test]
+fn t(){assert(add(1,2)==3)}
+// the end";
+        let formatted = "// adds things
+fn add(a, b) {
+    // the sum
+    var c = a + b; // of both
+
+    if c > 10 {
+        c;
+    } else {
+        // small
+        0;
+        // still small
+    }
+    // done
+} // after add
+
+#[test]
+fn t() {
+    assert(add(1, 2) == 3);
+}
+// the end
+";
+        assert_eq!(format(src), formatted);
+        assert_eq!(format(formatted), formatted);
+    }
+
+    #[test]
+    fn keeps_trailing_comments_after_their_token() {
+        let cases = [
+            (
+                "#[test] // after attr\nfn f(a, // arg\n b) { // open\n  a + b }",
+                "#[test] // after attr\nfn f(a, // arg\n    b) { // open\n    a + b;\n}\n",
+            ),
+            (
+                "fn f(a) { if a // cond\n{ 1 } }",
+                "fn f(a) {\n    if a // cond\n    {\n        1;\n    }\n}\n",
+            ),
+            (
+                "fn f() { foo(1, // one\n 2) }",
+                "fn f() {\n    foo(1, // one\n        2);\n}\n",
+            ),
+            (
+                "fn f(a) { if a { 1 } // then\n else { 2 }; var x = // x\n 1 // one\n + 2; x }",
+                "fn f(a) {\n    if a {\n        1;\n    } // then\n    else {\n        2;\n    };\n    var x = // x\n        1 // one\n        + 2;\n    x;\n}\n",
+            ),
+        ];
+        for (src, formatted) in cases {
+            assert_eq!(format(src), formatted);
+            assert_eq!(format(formatted), formatted);
+        }
+    }
+}
@@ -0,0 +1,302 @@
+//!
+//! property tests for the lexer, the parser and the printer,
+//! driven by a generator of well-formed programs
+//!
+
+use chumsky::{
+    error::{Simple, SimpleReason},
+    Stream,
+};
+use interface::{
+    nodes::{attr::Attr, expr::Expr, func::Func, types::Type},
+    tokens::Token,
+    Span, Spanned,
+};
+use parser::{
+    cst::{parse_cst, SyntaxKind},
+    incremental::{reparse, Edit},
+    lexer::lex,
+    parsers::parse,
+    printer::print_funcs,
+};
+use proptest::prelude::*;
+use std::collections::HashMap;
+
+/// words that lex as keywords rather than identifiers
+const KEYWORDS: [&str; 6] = ["fn", "memo", "var", "return", "if", "else"];
+
+const OPS: [&str; 13] = [
+    "+", "-", "*", "/", "%", "==", "!=", "<", ">", "<=", ">=", "&&", "||",
+];
+
+fn ident() -> impl Strategy<Value = String> {
+    "[a-z][a-z0-9_]{0,4}".prop_filter("keyword", |name| !KEYWORDS.contains(&name.as_str()))
+}
+
+fn type_() -> impl Strategy<Value = String> {
+    prop::sample::select(&["int", "bool", "str", "null"][..])
+        .prop_map(str::to_string)
+        .prop_recursive(2, 4, 1, |ty| ty.prop_map(|ty| format!("[{}]", ty)))
+}
+
+///
+/// source of a well-formed expression; compound expressions are parenthesised
+/// so that they can appear anywhere
+///
+fn expr() -> impl Strategy<Value = String> {
+    let leaf = prop_oneof![
+        (0..=i32::MAX).prop_map(|n| n.to_string()),
+        "[a-z ]{0,8}".prop_map(|s| format!("\"{}\"", s)),
+        ident(),
+    ];
+    leaf.prop_recursive(4, 32, 3, |e| {
+        prop_oneof![
+            (e.clone(), prop::sample::select(&OPS[..]), e.clone())
+                .prop_map(|(a, op, b)| format!("({} {} {})", a, op, b)),
+            (e.clone(), prop::collection::vec(e.clone(), 0..3)).prop_map(|(f, args)| format!(
+                "{}({})",
+                f,
+                args.join(", ")
+            )),
+            (e.clone(), e.clone(), prop::option::of(e.clone())).prop_map(|(c, a, b)| match b {
+                Some(b) => format!("(if {} {{ {} }} else {{ {} }})", c, a, b),
+                None => format!("(if {} {{ {} }})", c, a),
+            }),
+            (ident(), prop::option::of(type_()), e.clone(), e.clone()).prop_map(
+                |(name, ty, val, body)| match ty {
+                    Some(ty) => format!("(var {}: {} = {}; {})", name, ty, val, body),
+                    None => format!("(var {} = {}; {})", name, val, body),
+                }
+            ),
+            (e.clone(), e.clone()).prop_map(|(a, b)| format!("({}; {})", a, b)),
+            e.clone().prop_map(|a| format!("(return {})", a)),
+        ]
+    })
+}
+
+/// source of a well-formed function named `name`
+fn func(name: String) -> impl Strategy<Value = String> {
+    let attr = prop_oneof![
+        Just("#[test]".to_string()),
+        "[a-z ]{0,8}".prop_map(|s| format!("#[deprecated(\"{}\")]", s)),
+    ];
+    let arg = (ident(), prop::option::of(type_())).prop_map(|(name, ty)| match ty {
+        Some(ty) => format!("{}: {}", name, ty),
+        None => name,
+    });
+    (
+        prop::collection::vec(attr, 0..2),
+        any::<bool>(),
+        prop::collection::vec(arg, 0..3),
+        prop::option::of(type_()),
+        prop::collection::vec(expr(), 1..4),
+    )
+        .prop_map(move |(attrs, memo, args, ret, body)| {
+            format!(
+                "{}{}fn {}({}){} {{ {} }}\n",
+                attrs.concat(),
+                if memo { "memo " } else { "" },
+                name,
+                args.join(", "),
+                ret.map(|ty| format!(" -> {}", ty)).unwrap_or_default(),
+                body.join("; ")
+            )
+        })
+}
+
+/// text an edit may insert, which can open or close anything
+fn insertion() -> impl Strategy<Value = String> {
+    let piece = prop::sample::select(
+        &[
+            "\"",
+            "{",
+            "}",
+            "(",
+            ")",
+            "fn f9() { 1 }",
+            "#[test]",
+            "memo ",
+            "// c",
+            "\n",
+            " ",
+            "x",
+            "1",
+            "+",
+            ";",
+        ][..],
+    );
+    prop::collection::vec(piece, 0..4).prop_map(|pieces| pieces.concat())
+}
+
+/// source of a well-formed program
+fn program() -> impl Strategy<Value = String> {
+    (1..4usize)
+        .prop_flat_map(|n| (0..n).map(|i| func(format!("f{}", i))).collect::<Vec<_>>())
+        .prop_map(|funcs| funcs.concat())
+}
+
+fn parse_src(src: &str) -> (Option<HashMap<String, Func>>, Vec<Simple<Token>>) {
+    let tokens = lex(src)
+        .0
+        .unwrap()
+        .into_iter()
+        .filter(|t| !matches!(t.0, Token::Comment(_)))
+        .collect::<Vec<_>>();
+    let len = src.chars().count();
+    parse(Stream::from_iter(len..len + 1, tokens.into_iter()))
+}
+
+fn erase<T>(node: &Spanned<T>, f: impl Fn(&T) -> T) -> Spanned<T> {
+    (f(&node.0), 0..0)
+}
+
+fn erase_expr(expr: &Spanned<Expr>) -> Spanned<Expr> {
+    let b = |e: &Spanned<Expr>| Box::new(erase_expr(e));
+    erase(expr, |e| match e {
+        Expr::Var(name, ty, val, body) => Expr::Var(
+            erase(name, String::clone),
+            ty.as_ref().map(|ty| erase(ty, Type::clone)),
+            b(val),
+            b(body),
+        ),
+        Expr::Then(a, c) => Expr::Then(b(a), b(c)),
+        Expr::Binary(a, op, c) => Expr::Binary(b(a), *op, b(c)),
+        Expr::Call(f, args) => Expr::Call(
+            b(f),
+            erase(args, |args| args.iter().map(erase_expr).collect()),
+        ),
+        Expr::Return(a) => Expr::Return(b(a)),
+        Expr::If(c, x, y) => Expr::If(b(c), b(x), b(y)),
+        Expr::Error | Expr::Value(_) | Expr::Local(_) => e.clone(),
+    })
+}
+
+/// a dump of the functions without spans, which change when the source is reprinted
+fn shape(funcs: &HashMap<String, Func>) -> String {
+    let mut funcs = funcs
+        .iter()
+        .map(|(name, func)| {
+            let func = Func {
+                body: erase_expr(&func.body),
+                name_span: Span::default(),
+                args_span: Span::default(),
+                arg_spans: Vec::new(),
+                arg_types: func
+                    .arg_types
+                    .iter()
+                    .map(|ty| ty.as_ref().map(|ty| erase(ty, Type::clone)))
+                    .collect(),
+                ret_type: func.ret_type.as_ref().map(|ty| erase(ty, Type::clone)),
+                attrs: func
+                    .attrs
+                    .iter()
+                    .map(|attr| Attr {
+                        name: erase(&attr.name, String::clone),
+                        args: attr
+                            .args
+                            .iter()
+                            .map(|arg| erase(arg, Token::clone))
+                            .collect(),
+                        span: Span::default(),
+                    })
+                    .collect(),
+                ..func.clone()
+            };
+            format!("{} {:?}", name, func)
+        })
+        .collect::<Vec<_>>();
+    funcs.sort();
+    funcs.join("\n")
+}
+
+proptest! {
+    #[test]
+    fn lexing_never_panics(src in "\\PC{0,64}") {
+        let (tokens, errs) = lex(&src);
+        prop_assert!(tokens.is_some() || !errs.is_empty());
+    }
+
+    #[test]
+    fn lexing_programs_never_fails(src in program()) {
+        prop_assert_eq!(lex(&src).1, Vec::new());
+    }
+
+    #[test]
+    fn parsing_always_terminates(src in "[a-z0-9 (){};,:#+*=<>|&\\[\\]\"-]{0,48}") {
+        // arbitrary input must give a result or an error, never hang or panic
+        let (funcs, errs) = parse_src(&src);
+        prop_assert!(funcs.is_some() || !errs.is_empty());
+    }
+
+    #[test]
+    fn parsing_mangled_programs_terminates(src in program(), cut in any::<prop::sample::Index>()) {
+        let chars = src.chars().collect::<Vec<_>>();
+        let mut mangled = chars.clone();
+        mangled.remove(cut.index(chars.len()));
+        let (funcs, errs) = parse_src(&mangled.into_iter().collect::<String>());
+        prop_assert!(funcs.is_some() || !errs.is_empty());
+    }
+
+    #[test]
+    fn cst_keeps_any_input(src in "(\\PC|\\s){0,64}") {
+        prop_assert_eq!(parse_cst(&src).to_string(), src);
+    }
+
+    #[test]
+    fn cst_covers_programs(src in program()) {
+        let root = parse_cst(&src);
+        prop_assert_eq!(root.to_string(), src.clone());
+        let errors = root
+            .descendants_with_tokens()
+            .filter(|el| el.kind() == SyntaxKind::Error)
+            .map(|el| el.to_string())
+            .collect::<Vec<_>>();
+        prop_assert!(errors.is_empty(), "{:?} in:\n{}", errors, src);
+    }
+
+    #[test]
+    fn reparsing_matches_parsing(
+        src in program(),
+        a in any::<prop::sample::Index>(),
+        b in any::<prop::sample::Index>(),
+        text in insertion(),
+    ) {
+        let len = src.chars().count();
+        let (a, b) = (a.index(len + 1), b.index(len + 1));
+        let edit = Edit::new(a.min(b)..a.max(b), &text);
+        let reparsed = reparse(&parse_cst(&src), &edit);
+        let parsed = parse_cst(&edit.apply(&src));
+        prop_assert_eq!(format!("{:#?}", reparsed), format!("{:#?}", parsed));
+    }
+
+    #[test]
+    fn printing_round_trips(src in program()) {
+        let (funcs, errs) = parse_src(&src);
+        prop_assert_eq!(errs, Vec::new(), "generated program does not parse:\n{}", src);
+        let funcs = funcs.unwrap();
+        let printed = print_funcs(&funcs);
+        let (reparsed, errs) = parse_src(&printed);
+        prop_assert_eq!(errs, Vec::new(), "printed program does not parse:\n{}", printed);
+        let reparsed = reparsed.unwrap();
+        prop_assert_eq!(shape(&funcs), shape(&reparsed), "tree changed when printed as:\n{}", printed);
+        // printing is stable once the layout is the printer's own
+        prop_assert_eq!(print_funcs(&reparsed), printed);
+    }
+}
+
+#[test]
+fn rejects_numbers_too_large() {
+    let (funcs, errs) = parse_src("fn main() { 99999999999 }");
+    assert!(matches!(funcs.unwrap()["main"].body.0, Expr::Error));
+    let errs = errs
+        .iter()
+        .map(|e| (e.span(), e.reason().clone()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        errs,
+        vec![(
+            12..23,
+            SimpleReason::Custom("Number '99999999999' is too large".to_string())
+        )]
+    );
+}